@@ -6,6 +6,7 @@
 //! 1. It should preserve comments that are given
 //! 2. The output must be mostly identical to the pre-existing file as much as possible
 
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Write};
 use std::io::BufRead;
 use std::path::Path;
@@ -14,9 +15,13 @@ use std::sync::OnceLock;
 use std::time::Duration;
 
 use regex::Regex;
+use unicode_width::UnicodeWidthStr;
+
+use crate::utils::SignedDuration;
 
 /// An error that occurs during parsing
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
     /// An I/O error occurred.
     Io(std::io::Error),
@@ -32,6 +37,18 @@ pub enum ErrorKind {
     InvalidEventType,
     /// There is an invalid event in the file
     InvalidEvent,
+    /// A major section (e.g. `[Events]`) was given more than once.
+    ///
+    /// This is only raised when parsing in strict mode.
+    DuplicateSection(String),
+    /// An event ends before it starts.
+    ///
+    /// This is only raised when parsing in strict mode.
+    InvalidTiming,
+    /// An event has fewer fields than its `Format` line declares.
+    ///
+    /// This is only raised when parsing in strict mode.
+    MissingEventFields { expected: usize, found: usize },
 }
 
 #[derive(Debug)]
@@ -87,25 +104,138 @@ impl Display for Error {
             ErrorKind::InvalidStyle => write!(f, "line {}: style is invalid", self.line),
             ErrorKind::InvalidEventType => write!(f, "line {}: event type is invalid", self.line),
             ErrorKind::InvalidEvent => write!(f, "line {}: event is invalid", self.line),
+            ErrorKind::DuplicateSection(title) => {
+                write!(f, "line {}: duplicate [{title}] section", self.line)
+            }
+            ErrorKind::InvalidTiming => {
+                write!(f, "line {}: event ends before it starts", self.line)
+            }
+            ErrorKind::MissingEventFields { expected, found } => write!(
+                f,
+                "line {}: event has {found} field(s) but the format expects {expected}",
+                self.line
+            ),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+/// An error that occurs when renaming a style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameStyleError {
+    /// The style being renamed isn't defined or referenced by any event.
+    NotFound(String),
+    /// There is already a style with the new name.
+    AlreadyExists(String),
+}
+
+impl Display for RenameStyleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenameStyleError::NotFound(name) => write!(f, "style {name} not found"),
+            RenameStyleError::AlreadyExists(name) => write!(f, "style {name} already exists"),
+        }
+    }
+}
+
+impl std::error::Error for RenameStyleError {}
+
+/// The kind of problem found by [`Ass::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssueKind {
+    /// There is no `[V4+ Styles]` section.
+    MissingStyles,
+    /// The event references a style that isn't defined.
+    UndefinedStyle(String),
+    /// The event ends before it starts.
+    InvertedTiming,
+    /// The dialogue starts and ends at the same time so it's never shown.
+    ZeroDuration,
+    /// The event overlaps with the event at the given index on the same layer and style.
+    Overlap(usize),
+    /// The style has an alignment outside of 1 to 9.
+    InvalidStyleAlignment { style: String, alignment: u8 },
+    /// The event has an `\an` override tag outside of 1 to 9.
+    InvalidAlignmentTag(String),
+}
+
+impl Display for ValidationIssueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssueKind::MissingStyles => f.write_str("missing [V4+ Styles] section"),
+            ValidationIssueKind::UndefinedStyle(name) => write!(f, "style {name} is not defined"),
+            ValidationIssueKind::InvertedTiming => f.write_str("ends before it starts"),
+            ValidationIssueKind::ZeroDuration => f.write_str("starts and ends at the same time"),
+            ValidationIssueKind::Overlap(index) => write!(f, "overlaps with event {index}"),
+            ValidationIssueKind::InvalidStyleAlignment { style, alignment } => {
+                write!(f, "style {style} has an invalid alignment of {alignment}")
+            }
+            ValidationIssueKind::InvalidAlignmentTag(tag) => {
+                write!(f, "invalid alignment tag {tag}")
+            }
+        }
+    }
+}
+
+/// A problem found by [`Ass::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub kind: ValidationIssueKind,
+    /// The index of the affected event, if any.
+    pub event: Option<usize>,
+    /// The start time of the affected event, if any.
+    pub start: Option<Duration>,
+}
+
+impl ValidationIssue {
+    fn new(kind: ValidationIssueKind) -> Self {
+        Self {
+            kind,
+            event: None,
+            start: None,
+        }
+    }
+
+    fn for_event(kind: ValidationIssueKind, index: usize, event: &Event) -> Self {
+        Self {
+            kind,
+            event: Some(index),
+            start: Some(event.start),
+        }
+    }
+}
+
 pub trait ToAss {
     fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()>;
+
+    /// Writes the .ass representation with the given options.
+    ///
+    /// The default implementation ignores the options.
+    fn to_ass_with<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
+        let _ = options;
+        self.to_ass(writer)
+    }
 }
 
 /// A line in an .ass file
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Line {
     /// Represents a key and a value pairing
     Variable(String),
     /// Represents a comment
     Comment(String),
     /// Represents an embedded UUEncoding line
+    ///
+    /// These only appear in the `[Fonts]` and `[Graphics]` sections.
     Encoded(String),
+    /// Represents a line that isn't any of the other kinds
+    Raw(String),
     /// Represents an empty line
     Empty,
 }
@@ -125,17 +255,30 @@ impl Line {
         Self::Variable(format!("{key}: {value}"))
     }
 
-    pub(crate) fn parse(s: &str) -> Option<Self> {
+    /// Parses a line of a section.
+    ///
+    /// Encoded lines are only detected if `encoded` is `true`, i.e. the
+    /// line comes after the header of an embedded file. Otherwise a short
+    /// line such as `NOTE` would be mistaken for encoded data. Lines that
+    /// aren't recognised, including ones too long to be encoded data, are
+    /// kept as [`Line::Raw`].
+    pub(crate) fn parse(s: &str, encoded: bool) -> Self {
+        // Embedded files are wrapped at 80 characters per line
+        const MAX_ENCODED_LENGTH: usize = 80;
+
         if s.is_empty() {
-            Some(Self::Empty)
+            Self::Empty
         } else if let Some(suffix) = s.strip_prefix(';') {
-            Some(Self::Comment(suffix.to_owned()))
+            Self::Comment(suffix.to_owned())
         } else if s.split_once(": ").is_some() {
-            Some(Self::Variable(s.to_owned()))
-        } else if s.len() <= 80 && s.as_bytes().iter().all(|s| (33..97).contains(s)) {
-            Some(Self::Encoded(s.to_owned()))
+            Self::Variable(s.to_owned())
+        } else if encoded
+            && s.len() <= MAX_ENCODED_LENGTH
+            && s.as_bytes().iter().all(|s| (33..97).contains(s))
+        {
+            Self::Encoded(s.to_owned())
         } else {
-            None
+            Self::Raw(s.to_owned())
         }
     }
 
@@ -150,6 +293,7 @@ impl Line {
                 .unwrap_or_default(),
             Line::Comment(_) => true,
             Line::Encoded(_) => false,
+            Line::Raw(_) => false,
             Line::Empty => false,
         }
     }
@@ -192,11 +336,51 @@ impl ToAss for Line {
             Line::Variable(s) => writeln!(writer, "{s}"),
             Line::Comment(c) => writeln!(writer, ";{c}"),
             Line::Encoded(e) => writeln!(writer, "{e}"),
+            Line::Raw(s) => writeln!(writer, "{s}"),
             Line::Empty => writeln!(writer),
         }
     }
 }
 
+/// The fields written in the `Format:` line of the `[V4+ Styles]` section.
+const STYLE_FORMAT: [&str; 23] = [
+    "Name",
+    "Fontname",
+    "Fontsize",
+    "PrimaryColour",
+    "SecondaryColour",
+    "OutlineColour",
+    "BackColour",
+    "Bold",
+    "Italic",
+    "Underline",
+    "StrikeOut",
+    "ScaleX",
+    "ScaleY",
+    "Spacing",
+    "Angle",
+    "BorderStyle",
+    "Outline",
+    "Shadow",
+    "Alignment",
+    "MarginL",
+    "MarginR",
+    "MarginV",
+    "Encoding",
+];
+
+/// The fields written in the `Format:` line of the `[Events]` section.
+const EVENT_FORMAT: [&str; 10] = [
+    "Layer", "Start", "End", "Style", "Name", "MarginL", "MarginR", "MarginV", "Effect", "Text",
+];
+
+/// Parses the fields of a `Format:` line.
+///
+/// Both `Name, Fontname` and `Name,Fontname` are accepted.
+fn parse_format(value: &str) -> Vec<String> {
+    value.split(',').map(|s| s.trim().to_owned()).collect()
+}
+
 trait SectionParse {
     fn process_line(&mut self, line: Line) -> Result<(), Error>;
 }
@@ -227,20 +411,35 @@ impl Default for ScriptInfo {
 
 impl SectionParse for ScriptInfo {
     fn process_line(&mut self, line: Line) -> Result<(), Error> {
-        if line.is_encoded() {
-            Err(ErrorKind::Invalid.into())
-        } else {
-            self.lines.push(line);
-            Ok(())
-        }
+        self.lines.push(line);
+        Ok(())
     }
 }
 
+/// Returns the lines without any trailing empty lines.
+///
+/// The blank line between sections is written by [`Ass::save_to_writer`] instead.
+fn trim_trailing_empty(lines: &[Line]) -> &[Line] {
+    let end = lines
+        .iter()
+        .rposition(|l| !l.is_empty())
+        .map_or(0, |i| i + 1);
+    &lines[..end]
+}
+
 impl ToAss for ScriptInfo {
     fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.to_ass_with(writer, &WriteOptions::default())
+    }
+
+    fn to_ass_with<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
         writeln!(writer, "[Script Info]")?;
-        for line in &self.lines {
-            line.to_ass(writer)?;
+        for line in trim_trailing_empty(&self.lines) {
+            line.to_ass_with(writer, options)?;
         }
         Ok(())
     }
@@ -253,20 +452,77 @@ impl ScriptInfo {
 
     /// Returns the title of the script
     pub fn title(&self) -> &str {
-        self.lines
-            .iter()
-            .filter_map(|l| l.item())
-            .find_map(|(key, value)| (key == "Title").then_some(value))
-            .unwrap_or("<untitled>")
+        self.get("Title").unwrap_or("<untitled>")
     }
 
     /// Returns the version of the script
     pub fn version(&self) -> &str {
-        self.lines
-            .iter()
-            .filter_map(|l| l.item())
-            .find_map(|(key, value)| (key == "ScriptType").then_some(value))
-            .unwrap_or_default()
+        self.get("ScriptType").unwrap_or_default()
+    }
+
+    /// Returns the value of the first item with the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.items()
+            .find_map(|(k, value)| (k == key).then_some(value))
+    }
+
+    /// Returns the value of the given key parsed as `T`.
+    ///
+    /// Returns `None` if the key is missing or the value doesn't parse.
+    fn get_parsed<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|v| v.trim().parse().ok())
+    }
+
+    /// Returns the `PlayResX` of the script, i.e. the width the script was made for.
+    pub fn play_res_x(&self) -> Option<u32> {
+        self.get_parsed("PlayResX")
+    }
+
+    /// Returns the `PlayResY` of the script, i.e. the height the script was made for.
+    pub fn play_res_y(&self) -> Option<u32> {
+        self.get_parsed("PlayResY")
+    }
+
+    /// Returns the `PlayDepth` of the script, i.e. the colour depth in bits.
+    pub fn play_depth(&self) -> Option<u32> {
+        self.get_parsed("PlayDepth")
+    }
+
+    /// Returns the `Timer` of the script.
+    ///
+    /// This is the speed of the script as a percentage, where `100.0` is
+    /// the normal speed.
+    pub fn timer(&self) -> Option<f64> {
+        self.get_parsed("Timer")
+    }
+
+    /// Returns how the `WrapStyle` of the script wraps long lines.
+    pub fn wrap_style(&self) -> Option<WrapStyle> {
+        self.get_parsed::<u8>("WrapStyle")
+            .and_then(WrapStyle::from_number)
+    }
+
+    /// Returns how the `Collisions` of the script moves overlapping events.
+    pub fn collisions(&self) -> Option<Collisions> {
+        match self.get("Collisions")?.trim() {
+            s if s.eq_ignore_ascii_case("normal") => Some(Collisions::Normal),
+            s if s.eq_ignore_ascii_case("reverse") => Some(Collisions::Reverse),
+            _ => None,
+        }
+    }
+
+    /// Returns whether `ScaledBorderAndShadow` is enabled.
+    pub fn scaled_border_and_shadow(&self) -> Option<bool> {
+        match self.get("ScaledBorderAndShadow")?.trim() {
+            s if s.eq_ignore_ascii_case("yes") => Some(true),
+            s if s.eq_ignore_ascii_case("no") => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns the key-value pairs of the script info in order.
+    pub fn items(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|l| l.item())
     }
 
     pub fn remove_comments(&mut self) {
@@ -274,6 +530,40 @@ impl ScriptInfo {
     }
 }
 
+/// How long lines are wrapped, see [`ScriptInfo::wrap_style`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WrapStyle {
+    /// Lines are wrapped evenly, with the top line being wider. This is `0`.
+    Smart,
+    /// Lines are wrapped as late as possible. This is `1`.
+    EndOfLine,
+    /// Lines are never wrapped, only `\N` breaks them. This is `2`.
+    None,
+    /// Lines are wrapped evenly, with the bottom line being wider. This is `3`.
+    SmartLower,
+}
+
+impl WrapStyle {
+    fn from_number(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Self::Smart),
+            1 => Some(Self::EndOfLine),
+            2 => Some(Self::None),
+            3 => Some(Self::SmartLower),
+            _ => None,
+        }
+    }
+}
+
+/// How overlapping events are moved, see [`ScriptInfo::collisions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Collisions {
+    /// Later events are placed below the earlier ones.
+    Normal,
+    /// Later events are placed above the earlier ones.
+    Reverse,
+}
+
 /// A generic section in the .ass file.
 ///
 /// This is one that doesn't have any dedicated parseable information.
@@ -292,9 +582,17 @@ impl SectionParse for GenericSection {
 
 impl ToAss for GenericSection {
     fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.to_ass_with(writer, &WriteOptions::default())
+    }
+
+    fn to_ass_with<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
         writeln!(writer, "[{}]", self.title)?;
-        for line in &self.lines {
-            line.to_ass(writer)?;
+        for line in trim_trailing_empty(&self.lines) {
+            line.to_ass_with(writer, options)?;
         }
         Ok(())
     }
@@ -313,7 +611,29 @@ impl GenericSection {
     }
 }
 
+/// The basic colour names from HTML 4 that [`Colour::parse`] accepts.
+const COLOUR_NAMES: [(&str, Colour); 16] = [
+    ("black", Colour::BLACK),
+    ("silver", Colour::from_rgb(192, 192, 192)),
+    ("gray", Colour::from_rgb(128, 128, 128)),
+    ("white", Colour::WHITE),
+    ("maroon", Colour::from_rgb(128, 0, 0)),
+    ("red", Colour::RED),
+    ("purple", Colour::from_rgb(128, 0, 128)),
+    ("fuchsia", Colour::from_rgb(255, 0, 255)),
+    ("green", Colour::from_rgb(0, 128, 0)),
+    ("lime", Colour::from_rgb(0, 255, 0)),
+    ("olive", Colour::from_rgb(128, 128, 0)),
+    ("yellow", Colour::from_rgb(255, 255, 0)),
+    ("navy", Colour::from_rgb(0, 0, 128)),
+    ("blue", Colour::from_rgb(0, 0, 255)),
+    ("teal", Colour::from_rgb(0, 128, 128)),
+    ("aqua", Colour::from_rgb(0, 255, 255)),
+];
+
 /// Colour that is used in a style or .ass script.
+///
+/// With the `serde` feature this is serialized as a `#RRGGBBAA` string.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Colour {
     pub red: u8,
@@ -357,6 +677,46 @@ impl Colour {
         })
     }
 
+    /// Leniently parses a colour, e.g. from the command line.
+    ///
+    /// The following are tried in order:
+    ///
+    /// - `#RRGGBB` or `#RRGGBBAA` hex, the same format as [`Colour::to_hex`]
+    /// - `&HAABBGGRR` as used in .ass files, see [`Colour::from_ass`]
+    /// - A decimal integer in the same `AABBGGRR` layout, as used in old .ssa files
+    /// - One of the 16 basic HTML colour names, e.g. `white` or `navy`, in any case
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(hex) = s.strip_prefix('#') {
+            if !matches!(hex.len(), 6 | 8) {
+                return None;
+            }
+            let num = u32::from_str_radix(hex, 16).ok()?;
+            return Some(if hex.len() == 6 {
+                Self::from_rgb((num >> 16) as u8, (num >> 8) as u8, num as u8)
+            } else {
+                Self::from_rgba(
+                    (num >> 24) as u8,
+                    (num >> 16) as u8,
+                    (num >> 8) as u8,
+                    num as u8,
+                )
+            });
+        }
+
+        if let Some(colour) = Self::from_ass(s) {
+            return Some(colour);
+        }
+
+        if let Ok(num) = s.parse::<u32>() {
+            return Self::from_ass(&format!("&H{num:X}"));
+        }
+
+        COLOUR_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|&(_, colour)| colour)
+    }
+
     pub fn to_hex(&self) -> String {
         format!(
             "#{:02X}{:02X}{:02X}{:02X}",
@@ -380,6 +740,65 @@ impl Colour {
 
         0.2126 * r + 0.7152 * g + 0.0722 * b
     }
+
+    /// Returns the contrast ratio between the two colours, from 1 to 21.
+    ///
+    /// The alpha channel is ignored.
+    pub fn contrast_ratio(&self, other: &Colour) -> f32 {
+        // Source: https://www.w3.org/TR/WCAG20/#contrast-ratiodef
+        let (l1, l2) = (self.relative_luminance(), other.relative_luminance());
+        (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+    }
+
+    /// Linearly interpolates every channel, including the alpha, towards `other`.
+    ///
+    /// A `t` of 0 returns this colour and 1 returns `other`. Values
+    /// outside of that range are clamped.
+    pub fn blend(&self, other: &Colour, t: f32) -> Colour {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Colour {
+            red: mix(self.red, other.red),
+            green: mix(self.green, other.green),
+            blue: mix(self.blue, other.blue),
+            alpha: mix(self.alpha, other.alpha),
+        }
+    }
+
+    /// Composites this colour on top of the background colour.
+    ///
+    /// Keep in mind that .ass alpha is a transparency, i.e. 0 is opaque
+    /// and 255 is fully transparent.
+    pub fn over(&self, background: &Colour) -> Colour {
+        let opacity = |c: &Colour| 1.0 - c.alpha as f32 / 255.0;
+        let (fg, bg) = (opacity(self), opacity(background));
+        let out = fg + bg * (1.0 - fg);
+        if out == 0.0 {
+            return Colour::from_rgba(0, 0, 0, 255);
+        }
+        let mix = |f: u8, b: u8| ((f as f32 * fg + b as f32 * bg * (1.0 - fg)) / out).round() as u8;
+        Colour {
+            red: mix(self.red, background.red),
+            green: mix(self.green, background.green),
+            blue: mix(self.blue, background.blue),
+            alpha: ((1.0 - out) * 255.0).round() as u8,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Colour {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Colour {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Colour::parse(&s).ok_or_else(|| serde::de::Error::custom(format!("invalid colour `{s}`")))
+    }
 }
 
 impl Display for Colour {
@@ -396,7 +815,8 @@ impl Display for Colour {
 }
 
 /// A style for the .ass script
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Style {
     pub name: String,
     pub font_name: String,
@@ -454,6 +874,16 @@ impl Default for Style {
 }
 
 impl Style {
+    /// Returns `true` if the styles look the same, i.e. everything
+    /// but the name is equal.
+    pub fn has_same_properties(&self, other: &Style) -> bool {
+        let unnamed = |style: &Style| Style {
+            name: String::new(),
+            ..style.clone()
+        };
+        unnamed(self) == unnamed(other)
+    }
+
     /// The default style used for the sub-tools conversion scheme.
     ///
     /// The font should be Yu Gothic UI + bold if Japanese is found
@@ -520,31 +950,124 @@ impl ToAss for Style {
     }
 }
 
+/// A line in the body of a [`StylesSection`] or [`EventsSection`].
+///
+/// `;` comment lines are kept as their own entries so they stay where
+/// they were written when the items around them are removed or changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry<T> {
+    /// A `;` comment line, without the `;`.
+    Comment(String),
+    /// A `Style:` line or an event line.
+    Item(T),
+}
+
+impl<T> Entry<T> {
+    /// Returns the item if the entry isn't a comment.
+    pub fn item(&self) -> Option<&T> {
+        match self {
+            Entry::Item(item) => Some(item),
+            Entry::Comment(_) => None,
+        }
+    }
+
+    /// Returns the item if the entry isn't a comment.
+    pub fn item_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Entry::Item(item) => Some(item),
+            Entry::Comment(_) => None,
+        }
+    }
+
+    /// Converts the entry into its item, or `None` for a comment.
+    pub fn into_item(self) -> Option<T> {
+        match self {
+            Entry::Item(item) => Some(item),
+            Entry::Comment(_) => None,
+        }
+    }
+
+    /// Returns `true` if the entry is a `;` comment line.
+    #[must_use]
+    pub fn is_comment(&self) -> bool {
+        matches!(self, Self::Comment(_))
+    }
+}
+
+impl<T: ToAss> ToAss for Entry<T> {
+    fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.to_ass_with(writer, &WriteOptions::default())
+    }
+
+    fn to_ass_with<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
+        match self {
+            Entry::Comment(comment) => writeln!(writer, ";{comment}"),
+            Entry::Item(item) => item.to_ass_with(writer, options),
+        }
+    }
+}
+
 /// The section that denotes the styles in the script
 #[derive(Debug, Clone)]
 pub struct StylesSection {
     format: Vec<String>,
-    pub styles: Vec<Style>,
+    /// Whether the section was read from an SSA `[V4 Styles]` header.
+    legacy: bool,
+    /// The styles and `;` comments in the order they're written.
+    pub entries: Vec<Entry<Style>>,
 }
 
 impl Default for StylesSection {
     fn default() -> Self {
-        const DEFAULT_FORMAT: &str = "Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding";
         Self {
-            format: DEFAULT_FORMAT.split(", ").map(String::from).collect(),
-            styles: vec![Style::default()],
+            format: STYLE_FORMAT.map(String::from).to_vec(),
+            legacy: false,
+            entries: vec![Entry::Item(Style::default())],
         }
     }
 }
 
 impl StylesSection {
-    fn new() -> Self {
+    fn new(legacy: bool) -> Self {
         Self {
             format: Vec::new(),
-            styles: Vec::new(),
+            legacy,
+            entries: Vec::new(),
         }
     }
 
+    /// Returns the styles in the order they're written, skipping the comments.
+    ///
+    /// This replaces the old `styles` field, use [`entries`](Self::entries)
+    /// to see the comments as well.
+    pub fn styles(&self) -> impl Iterator<Item = &Style> {
+        self.entries.iter().filter_map(Entry::item)
+    }
+
+    /// Returns the styles in the order they're written, skipping the comments.
+    pub fn styles_mut(&mut self) -> impl Iterator<Item = &mut Style> {
+        self.entries.iter_mut().filter_map(Entry::item_mut)
+    }
+
+    /// Adds a style after the last entry.
+    pub fn push(&mut self, style: Style) {
+        self.entries.push(Entry::Item(style));
+    }
+
+    /// Keeps only the styles that the predicate returns `true` for.
+    ///
+    /// Comments are always kept.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Style) -> bool,
+    {
+        self.entries.retain(|e| e.item().is_none_or(&mut f));
+    }
+
     fn style_from_format(&self, data: &str) -> Option<Style> {
         let mut style = Style::default();
         // Technically, I don't think the order of these will ever change... but just for the sake of being "correct"
@@ -554,10 +1077,11 @@ impl StylesSection {
                 "Name" => style.name = value.to_owned(),
                 "Fontname" => style.font_name = value.to_owned(),
                 "Fontsize" => style.font_size = value.parse().ok()?,
-                "PrimaryColour" => style.primary_colour = Colour::from_ass(value)?,
-                "SecondaryColour" => style.secondary_colour = Colour::from_ass(value)?,
-                "OutlineColour" => style.outline_colour = Colour::from_ass(value)?,
-                "BackColour" => style.background_colour = Colour::from_ass(value)?,
+                "PrimaryColour" => style.primary_colour = self.colour(value)?,
+                "SecondaryColour" => style.secondary_colour = self.colour(value)?,
+                // SSA's tertiary colour is the outline colour of v4+
+                "OutlineColour" | "TertiaryColour" => style.outline_colour = self.colour(value)?,
+                "BackColour" => style.background_colour = self.colour(value)?,
                 "Bold" => style.bold = value != "0",
                 "Italic" => style.italic = value != "0",
                 "Underline" => style.underline = value != "0",
@@ -569,38 +1093,66 @@ impl StylesSection {
                 "BorderStyle" => style.border_style = value.parse().ok()?,
                 "Outline" => style.outline = value.parse().ok()?,
                 "Shadow" => style.shadow = value.parse().ok()?,
+                "Alignment" if self.legacy => {
+                    style.alignment = legacy_alignment(value.parse().ok()?)?
+                }
                 "Alignment" => style.alignment = value.parse().ok()?,
                 "MarginL" => style.margin_l = value.parse().ok()?,
                 "MarginR" => style.margin_r = value.parse().ok()?,
-                "MarginV" => style.margin_v = value.parse().ok()?,
+                "MarginV" | "MarginT" => style.margin_v = value.parse().ok()?,
                 "Encoding" => style.encoding = value.parse().ok()?,
                 _ => {}
             }
         }
         Some(style)
     }
+
+    /// Parses the value of a colour field.
+    ///
+    /// SSA styles usually write colours as a decimal `AABBGGRR` integer,
+    /// which is negative when the alpha is set.
+    fn colour(&self, value: &str) -> Option<Colour> {
+        match Colour::from_ass(value) {
+            Some(colour) => Some(colour),
+            None if self.legacy => {
+                let num = value.parse::<i64>().ok()?;
+                Colour::from_ass(&format!("&H{:X}", num as u32))
+            }
+            None => None,
+        }
+    }
+
+    pub fn remove_comments(&mut self) {
+        self.entries.retain(|e| !e.is_comment());
+    }
 }
 
 impl SectionParse for StylesSection {
     fn process_line(&mut self, line: Line) -> Result<(), Error> {
-        if line.is_empty() {
-            return Ok(());
+        match line {
+            // Stray lines that are not key-value pairs are skipped
+            Line::Empty | Line::Raw(_) => return Ok(()),
+            Line::Comment(comment) => {
+                self.entries.push(Entry::Comment(comment));
+                return Ok(());
+            }
+            _ => {}
         }
 
         let (key, value) = line.item().ok_or(ErrorKind::Invalid)?;
         match key {
             "Format" => {
-                self.format = value.split(", ").map(String::from).collect();
+                self.format = parse_format(value);
                 Ok(())
             }
             "Style" => {
                 if self.format.is_empty() {
                     Err(ErrorKind::MissingStyleFormat.into())
                 } else {
-                    self.styles.push(
-                        self.style_from_format(value)
-                            .ok_or(ErrorKind::InvalidStyle)?,
-                    );
+                    let style = self
+                        .style_from_format(value)
+                        .ok_or(ErrorKind::InvalidStyle)?;
+                    self.push(style);
                     Ok(())
                 }
             }
@@ -611,11 +1163,19 @@ impl SectionParse for StylesSection {
 
 impl ToAss for StylesSection {
     fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.to_ass_with(writer, &WriteOptions::default())
+    }
+
+    fn to_ass_with<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
         writeln!(writer, "[V4+ Styles]")?;
         // Yes, the order is hardcoded.
-        writeln!(writer, "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding")?;
-        for style in &self.styles {
-            style.to_ass(writer)?;
+        options.write_format(writer, &STYLE_FORMAT)?;
+        for entry in &self.entries {
+            entry.to_ass_with(writer, options)?;
         }
         Ok(())
     }
@@ -627,10 +1187,18 @@ fn ass_timestamp_to_duration(s: &str) -> Option<Duration> {
     let hours = units.next()?.parse::<u64>().ok()?;
     let minutes = units.next()?.parse::<u64>().ok()?;
     let seconds = units.next()?.parse::<u64>().ok()?;
-    let cs = subsec.parse::<u32>().ok()?;
+    // This is usually centiseconds but some files have more or less precision
+    if subsec.is_empty() || !subsec.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let ms = subsec
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(3)
+        .fold(0, |acc, b| acc * 10 + (b - b'0') as u32);
     Some(Duration::new(
         hours * 3600 + minutes * 60 + seconds,
-        cs * 10_000_000,
+        ms * 1_000_000,
     ))
 }
 
@@ -651,7 +1219,27 @@ impl<'a> Display for AssDuration<'a> {
     }
 }
 
+/// A timestamp that is written as it originally was if it wasn't modified.
+struct AssTimestamp<'a> {
+    duration: &'a Duration,
+    original: Option<&'a str>,
+}
+
+impl<'a> Display for AssTimestamp<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.original {
+            Some(original)
+                if ass_timestamp_to_duration(original).as_ref() == Some(self.duration) =>
+            {
+                f.write_str(original)
+            }
+            _ => AssDuration(self.duration).fmt(f),
+        }
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EventKind {
     #[default]
     Dialogue,
@@ -705,10 +1293,13 @@ impl EventKind {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
     pub kind: EventKind,
     pub layer: u8,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_millis"))]
     pub start: Duration,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_millis"))]
     pub end: Duration,
     pub style: String,
     pub name: String,
@@ -717,6 +1308,12 @@ pub struct Event {
     pub margin_v: u16,
     pub effect: String,
     pub text: String,
+    /// The start and end timestamps as they were written in the file.
+    ///
+    /// Each one is written back out as-is as long as it still matches
+    /// [`start`](Self::start) or [`end`](Self::end).
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) original_timestamps: Option<(String, String)>,
 }
 
 impl Default for Event {
@@ -733,75 +1330,419 @@ impl Default for Event {
             margin_v: 0,
             effect: String::new(),
             text: String::new(),
+            original_timestamps: None,
         }
     }
 }
 
-impl ToAss for Event {
-    fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
-        writeln!(
-            writer,
-            "{}: {},{},{},{},{},{},{},{},{},{}",
-            self.kind.as_str(),
-            self.layer,
-            AssDuration(&self.start),
-            AssDuration(&self.end),
-            self.style,
-            self.name,
-            self.margin_l,
-            self.margin_r,
-            self.margin_v,
-            self.effect,
-            self.text,
-        )
-    }
+/// A span of text inside an event's text.
+///
+/// The text of an event is made up of literal text and override blocks
+/// which are surrounded by braces, e.g. `{\an8}Hello {\b1}world{\b0}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TextSpan {
+    /// Literal text, including special characters such as `\N`.
+    Text(String),
+    /// An override block without the surrounding braces, e.g. `\an8\b1`.
+    Override(String),
 }
 
-impl Event {
-    pub fn shift_by(&mut self, seconds: f32) {
-        let duration = Duration::from_secs_f32(seconds.abs());
-        if seconds < 0.0 {
-            self.start = self.start.saturating_sub(duration);
-            self.end = self.end.saturating_sub(duration);
-        } else {
-            self.start = self.start.saturating_add(duration);
-            self.end = self.end.saturating_add(duration);
+impl TextSpan {
+    /// Splits the given text into its spans.
+    ///
+    /// An unterminated override block is considered literal text.
+    pub fn parse(mut s: &str) -> Vec<TextSpan> {
+        let mut spans = Vec::new();
+        while !s.is_empty() {
+            let block = s
+                .find('{')
+                .and_then(|start| Some((start, start + s[start..].find('}')?)));
+            match block {
+                Some((start, end)) => {
+                    if start != 0 {
+                        spans.push(TextSpan::Text(s[..start].to_owned()));
+                    }
+                    spans.push(TextSpan::Override(s[start + 1..end].to_owned()));
+                    s = &s[end + 1..];
+                }
+                None => {
+                    spans.push(TextSpan::Text(s.to_owned()));
+                    break;
+                }
+            }
         }
+        spans
     }
-}
-
-#[derive(Debug, Clone)]
-pub struct EventsSection {
-    format: Vec<String>,
-    pub events: Vec<Event>,
-}
 
-impl Default for EventsSection {
-    fn default() -> Self {
-        Self {
-            format: "Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text"
-                .split(", ")
-                .map(String::from)
-                .collect(),
-            events: Vec::new(),
+    /// Joins the spans back into event text.
+    pub fn join(spans: &[TextSpan]) -> String {
+        let mut buffer = String::new();
+        for span in spans {
+            let _ = write!(buffer, "{span}");
         }
+        buffer
     }
-}
 
-impl EventsSection {
-    fn new() -> Self {
-        Self {
-            format: Vec::new(),
-            events: Vec::new(),
+    /// Returns the individual tags inside an override block, e.g. `\an8` and `\b1`.
+    ///
+    /// Nested tags such as the ones inside `\t(...)` are not split. Anything before
+    /// the first tag (e.g. a comment) is returned as-is. Literal text has no tags.
+    pub fn tags(&self) -> Vec<&str> {
+        let TextSpan::Override(block) = self else {
+            return Vec::new();
+        };
+
+        let mut tags = Vec::new();
+        let mut depth = 0usize;
+        let mut start = 0;
+        for (index, ch) in block.char_indices() {
+            match ch {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                '\\' if depth == 0 && index != start => {
+                    tags.push(&block[start..index]);
+                    start = index;
+                }
+                _ => {}
+            }
         }
+        if start != block.len() {
+            tags.push(&block[start..]);
+        }
+        tags
     }
 
-    fn event_from_format(&self, kind: EventKind, data: &str) -> Option<Event> {
-        let mut event = Event {
-            kind,
-            ..Default::default()
-        };
+    /// Returns `true` if the span is [`Text`].
+    ///
+    /// [`Text`]: TextSpan::Text
+    #[must_use]
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Text(..))
+    }
+
+    /// Returns `true` if the span is [`Override`].
+    ///
+    /// [`Override`]: TextSpan::Override
+    #[must_use]
+    pub fn is_override(&self) -> bool {
+        matches!(self, Self::Override(..))
+    }
+}
+
+impl Display for TextSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextSpan::Text(text) => f.write_str(text),
+            TextSpan::Override(block) => write!(f, "{{{block}}}"),
+        }
+    }
+}
+
+/// Adds the text to the last syllable, or to a new one if there are none yet.
+fn push_syllable_text(syllables: &mut Vec<KaraokeSyllable>, text: &str) {
+    match syllables.last_mut() {
+        Some(syllable) => syllable.text.push_str(text),
+        None => syllables.push(KaraokeSyllable {
+            offset: Duration::ZERO,
+            duration: Duration::ZERO,
+            effect: KaraokeEffect::Instant,
+            text: text.to_owned(),
+        }),
+    }
+}
+
+/// How a karaoke syllable is highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KaraokeEffect {
+    /// The syllable is highlighted all at once, from `\k`.
+    Instant,
+    /// The highlight sweeps from left to right, from `\kf` or `\K`.
+    Sweep,
+    /// The outline is removed all at once, from `\ko`.
+    Outline,
+}
+
+/// A syllable of a karaoke event, see [`Event::karaoke_syllables`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KaraokeSyllable {
+    /// When the syllable starts being highlighted, relative to the start of the event.
+    pub offset: Duration,
+    /// How long the syllable takes to be highlighted.
+    pub duration: Duration,
+    pub effect: KaraokeEffect,
+    /// The text of the syllable, which can contain override tags.
+    pub text: String,
+}
+
+impl KaraokeSyllable {
+    /// Parses a karaoke tag such as `\k20` into an empty syllable at the given offset.
+    fn from_tag(tag: &str, offset: Duration) -> Option<Self> {
+        let (effect, centiseconds) = if let Some(value) = tag.strip_prefix("\\kf") {
+            (KaraokeEffect::Sweep, value)
+        } else if let Some(value) = tag.strip_prefix("\\ko") {
+            (KaraokeEffect::Outline, value)
+        } else if let Some(value) = tag.strip_prefix("\\K") {
+            (KaraokeEffect::Sweep, value)
+        } else {
+            (KaraokeEffect::Instant, tag.strip_prefix("\\k")?)
+        };
+        // `\kt` and other tags that start with `\k` aren't durations
+        let centiseconds = centiseconds.trim().parse::<f64>().ok()?;
+        Some(Self {
+            offset,
+            duration: Duration::from_millis((centiseconds.max(0.0) * 10.0).round() as u64),
+            effect,
+            text: String::new(),
+        })
+    }
+}
+
+impl ToAss for Event {
+    fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "{}: {},{},{},{},{},{},{},{},{},{}",
+            self.kind.as_str(),
+            self.layer,
+            AssTimestamp {
+                duration: &self.start,
+                original: self.original_timestamps().map(|(start, _)| start),
+            },
+            AssTimestamp {
+                duration: &self.end,
+                original: self.original_timestamps().map(|(_, end)| end),
+            },
+            self.style,
+            self.name,
+            self.margin_l,
+            self.margin_r,
+            self.margin_v,
+            self.effect,
+            self.text,
+        )
+    }
+}
+
+impl Event {
+    /// Returns the start and end timestamps as they were written in the file.
+    ///
+    /// Each one is written back out as-is as long as it still matches
+    /// [`start`](Self::start) or [`end`](Self::end), so changing the timing
+    /// doesn't need to touch them. This is `None` for events that weren't
+    /// parsed from a file.
+    pub fn original_timestamps(&self) -> Option<(&str, &str)> {
+        self.original_timestamps
+            .as_ref()
+            .map(|(start, end)| (start.as_str(), end.as_str()))
+    }
+
+    /// Returns `true` if the event doesn't end before it starts.
+    pub fn is_valid(&self) -> bool {
+        self.start <= self.end
+    }
+
+    /// Returns how long the event is shown for.
+    ///
+    /// Events that end before they start have a duration of zero.
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Returns the text of the event split into literal text and override blocks.
+    pub fn spans(&self) -> Vec<TextSpan> {
+        TextSpan::parse(&self.text)
+    }
+
+    /// Replaces the text of the event with the given spans.
+    pub fn set_spans(&mut self, spans: &[TextSpan]) {
+        self.text = TextSpan::join(spans);
+    }
+
+    /// Returns the text of the event without any override tags.
+    ///
+    /// Hard line breaks (`\N` and `\n`) are turned into new lines and hard spaces
+    /// (`\h`) are turned into regular spaces.
+    pub fn plain_text(&self) -> String {
+        self.spans()
+            .iter()
+            .filter_map(|s| match s {
+                TextSpan::Text(text) => Some(text.as_str()),
+                TextSpan::Override(_) => None,
+            })
+            .collect::<String>()
+            .replace("\\N", "\n")
+            .replace("\\n", "\n")
+            .replace("\\h", " ")
+    }
+
+    /// Returns `true` if the event has no visible text, e.g. it's empty or
+    /// only has whitespace and override tags.
+    pub fn is_blank(&self) -> bool {
+        self.plain_text().trim().is_empty()
+    }
+
+    /// Returns the karaoke syllables of the event, split on the `\k`, `\kf`, `\K`,
+    /// and `\ko` tags.
+    ///
+    /// Each syllable is the text after a karaoke tag up to the next one. Other
+    /// override tags are kept in the text. Text before the first karaoke tag
+    /// is a syllable that takes no time. Events without karaoke tags have no
+    /// syllables.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use sub_tools::ass::{Event, KaraokeEffect};
+    ///
+    /// let mut event = Event::default();
+    /// event.text = String::from(r"{\k20}ka{\kf35}ra{\k0}{\i1}o{\i0}");
+    /// let syllables = event.karaoke_syllables();
+    /// assert_eq!(syllables[1].text, "ra");
+    /// assert_eq!(syllables[1].offset, Duration::from_millis(200));
+    /// assert_eq!(syllables[1].duration, Duration::from_millis(350));
+    /// assert_eq!(syllables[1].effect, KaraokeEffect::Sweep);
+    /// assert_eq!(syllables[2].text, r"{\i1}o{\i0}");
+    /// ```
+    pub fn karaoke_syllables(&self) -> Vec<KaraokeSyllable> {
+        let spans = self.spans();
+        let is_karaoke = |tag: &&str| KaraokeSyllable::from_tag(tag, Duration::ZERO).is_some();
+        if !spans
+            .iter()
+            .flat_map(TextSpan::tags)
+            .any(|tag| is_karaoke(&tag))
+        {
+            return Vec::new();
+        }
 
+        let mut syllables = Vec::new();
+        let mut offset = Duration::ZERO;
+        for span in &spans {
+            let TextSpan::Override(_) = span else {
+                push_syllable_text(&mut syllables, &span.to_string());
+                continue;
+            };
+            // Tags that aren't karaoke tags are kept in the text of the syllable
+            let mut other_tags = String::new();
+            for tag in span.tags() {
+                let Some(syllable) = KaraokeSyllable::from_tag(tag, offset) else {
+                    other_tags.push_str(tag);
+                    continue;
+                };
+                if !other_tags.is_empty() {
+                    push_syllable_text(&mut syllables, &format!("{{{other_tags}}}"));
+                    other_tags.clear();
+                }
+                offset += syllable.duration;
+                syllables.push(syllable);
+            }
+            if !other_tags.is_empty() {
+                push_syllable_text(&mut syllables, &format!("{{{other_tags}}}"));
+            }
+        }
+        syllables
+    }
+
+    /// Moves the start and end by the given delta.
+    ///
+    /// Timings saturate at zero rather than going negative.
+    pub fn shift(&mut self, delta: SignedDuration) {
+        self.start = delta.apply(self.start);
+        self.end = delta.apply(self.end);
+    }
+
+    /// Moves the start and end by the given seconds, negative being backward.
+    ///
+    /// This is a convenience wrapper around [`shift`](Self::shift).
+    pub fn shift_by(&mut self, seconds: f32) {
+        self.shift(SignedDuration::from_secs_f32(seconds));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventsSection {
+    format: Vec<String>,
+    /// The events and `;` comments in the order they're written.
+    pub entries: Vec<Entry<Event>>,
+}
+
+impl Default for EventsSection {
+    fn default() -> Self {
+        Self {
+            format: EVENT_FORMAT.map(String::from).to_vec(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl EventsSection {
+    fn new() -> Self {
+        Self {
+            format: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the events in the order they're written, skipping the comments.
+    ///
+    /// This replaces the old `events` field, use [`entries`](Self::entries)
+    /// to see the comments as well.
+    pub fn events(&self) -> impl Iterator<Item = &Event> {
+        self.entries.iter().filter_map(Entry::item)
+    }
+
+    /// Returns the events in the order they're written, skipping the comments.
+    pub fn events_mut(&mut self) -> impl Iterator<Item = &mut Event> {
+        self.entries.iter_mut().filter_map(Entry::item_mut)
+    }
+
+    /// Consumes the section, returning the events without the comments.
+    pub fn into_events(self) -> impl Iterator<Item = Event> {
+        self.entries.into_iter().filter_map(Entry::into_item)
+    }
+
+    /// Adds an event after the last entry.
+    pub fn push(&mut self, event: Event) {
+        self.entries.push(Entry::Item(event));
+    }
+
+    /// Keeps only the events that the predicate returns `true` for.
+    ///
+    /// Comments are always kept.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        self.entries.retain(|e| e.item().is_none_or(&mut f));
+    }
+
+    /// Sorts the events with the comparison function, keeping the order of equal events.
+    ///
+    /// Comments stay on the lines they were on, so the events move around them.
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&Event, &Event) -> std::cmp::Ordering,
+    {
+        let mut events = self.events_mut().map(std::mem::take).collect::<Vec<_>>();
+        events.sort_by(compare);
+        for (slot, event) in self.events_mut().zip(events) {
+            *slot = event;
+        }
+    }
+
+    /// Returns an error if the event line has fewer fields than the format.
+    fn check_field_count(&self, data: &str) -> Option<ErrorKind> {
+        let expected = self.format.len();
+        let found = data.splitn(expected, ',').count();
+        (found < expected).then_some(ErrorKind::MissingEventFields { expected, found })
+    }
+
+    fn event_from_format(&self, kind: EventKind, data: &str) -> Option<Event> {
+        let mut event = Event {
+            kind,
+            ..Default::default()
+        };
+
+        let mut start = "";
+        let mut end = "";
         // See comment above about future proofing
         for (name, value) in self
             .format
@@ -811,8 +1752,14 @@ impl EventsSection {
         {
             match name {
                 "Layer" => event.layer = value.parse().ok()?,
-                "Start" => event.start = ass_timestamp_to_duration(value)?,
-                "End" => event.end = ass_timestamp_to_duration(value)?,
+                "Start" => {
+                    event.start = ass_timestamp_to_duration(value)?;
+                    start = value;
+                }
+                "End" => {
+                    event.end = ass_timestamp_to_duration(value)?;
+                    end = value;
+                }
                 "Style" => event.style = value.to_owned(),
                 "Name" => event.name = value.to_owned(),
                 "MarginL" => event.margin_l = value.parse().ok()?,
@@ -823,23 +1770,31 @@ impl EventsSection {
                 _ => {}
             }
         }
+        event.original_timestamps = Some((start.to_owned(), end.to_owned()));
         Some(event)
     }
 
     pub fn remove_comments(&mut self) {
-        self.events.retain(|e| !e.kind.is_comment());
+        self.entries
+            .retain(|e| e.item().is_some_and(|e| !e.kind.is_comment()));
     }
 }
 
 impl SectionParse for EventsSection {
     fn process_line(&mut self, line: Line) -> Result<(), Error> {
-        if line.is_empty() {
-            return Ok(());
+        match line {
+            // Stray lines that are not key-value pairs are skipped
+            Line::Empty | Line::Raw(_) => return Ok(()),
+            Line::Comment(comment) => {
+                self.entries.push(Entry::Comment(comment));
+                return Ok(());
+            }
+            _ => {}
         }
 
         let (key, value) = line.item().ok_or(ErrorKind::Invalid)?;
         if key == "Format" {
-            self.format = value.split(", ").map(String::from).collect();
+            self.format = parse_format(value);
             return Ok(());
         }
 
@@ -847,10 +1802,10 @@ impl SectionParse for EventsSection {
         if self.format.is_empty() {
             Err(ErrorKind::MissingStyleFormat.into())
         } else {
-            self.events.push(
-                self.event_from_format(event_kind, value)
-                    .ok_or(ErrorKind::InvalidStyle)?,
-            );
+            let event = self
+                .event_from_format(event_kind, value)
+                .ok_or(ErrorKind::InvalidStyle)?;
+            self.push(event);
             Ok(())
         }
     }
@@ -858,13 +1813,18 @@ impl SectionParse for EventsSection {
 
 impl ToAss for EventsSection {
     fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.to_ass_with(writer, &WriteOptions::default())
+    }
+
+    fn to_ass_with<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
         writeln!(writer, "[Events]")?;
-        writeln!(
-            writer,
-            "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text"
-        )?;
-        for event in &self.events {
-            event.to_ass(writer)?;
+        options.write_format(writer, &EVENT_FORMAT)?;
+        for entry in &self.entries {
+            entry.to_ass_with(writer, options)?;
         }
         Ok(())
     }
@@ -878,6 +1838,11 @@ pub enum Section {
     /// The script info of the file, denoted by `[Script Info]`.
     ScriptInfo(ScriptInfo),
     /// The styles section of the file, denoted by `[V4+ Styles]`.
+    ///
+    /// `[V4 Styles]` and `[V4++ Styles]` are read into this as well
+    /// but it's always written back as `[V4+ Styles]`. The fields of
+    /// SSA `[V4 Styles]` are converted to their v4+ equivalents, see
+    /// [`is_styles_header`].
     Styles(StylesSection),
     /// The event section of the file, denoted by `[Events]`.
     Events(EventsSection),
@@ -982,12 +1947,42 @@ impl Section {
         }
     }
 
+    /// Returns `true` if the next line of the section can be encoded data.
+    ///
+    /// Encoded data only appears in the `[Fonts]` and `[Graphics]` sections,
+    /// right after a `fontname:` or `filename:` header or more encoded data.
+    fn expects_encoded(&self) -> bool {
+        let Section::Generic(section) = self else {
+            return false;
+        };
+        if !matches!(section.title.as_str(), "Fonts" | "Graphics") {
+            return false;
+        }
+        match section.lines.last() {
+            Some(Line::Encoded(_)) => true,
+            Some(line) => line
+                .item()
+                .is_some_and(|(key, _)| matches!(key, "fontname" | "filename")),
+            None => false,
+        }
+    }
+
+    /// Returns the title of the section if it's one that should only appear once.
+    fn major_title(&self) -> Option<&'static str> {
+        match self {
+            Section::ScriptInfo(_) => Some("Script Info"),
+            Section::Styles(_) => Some("V4+ Styles"),
+            Section::Events(_) => Some("Events"),
+            Section::Generic(_) => None,
+        }
+    }
+
     pub fn remove_comments(&mut self) {
         match self {
             Section::ScriptInfo(script_info) => script_info.remove_comments(),
+            Section::Styles(styles_section) => styles_section.remove_comments(),
             Section::Events(events_section) => events_section.remove_comments(),
             Section::Generic(generic_section) => generic_section.remove_comments(),
-            _ => {}
         }
     }
 }
@@ -1005,17 +2000,19 @@ impl SectionParse for Section {
 
 impl ToAss for Section {
     fn to_ass<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.to_ass_with(writer, &WriteOptions::default())
+    }
+
+    fn to_ass_with<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
         match self {
-            Section::ScriptInfo(script_info) => script_info.to_ass(writer),
-            Section::Styles(styles_section) => {
-                styles_section.to_ass(writer)?;
-                writeln!(writer)
-            }
-            Section::Events(events_section) => {
-                events_section.to_ass(writer)?;
-                writeln!(writer)
-            }
-            Section::Generic(generic_section) => generic_section.to_ass(writer),
+            Section::ScriptInfo(script_info) => script_info.to_ass_with(writer, options),
+            Section::Styles(styles_section) => styles_section.to_ass_with(writer, options),
+            Section::Events(events_section) => events_section.to_ass_with(writer, options),
+            Section::Generic(generic_section) => generic_section.to_ass_with(writer, options),
         }
     }
 }
@@ -1025,7 +2022,49 @@ fn srt_tag_regex() -> &'static Regex {
     REGEX.get_or_init(|| Regex::new(r#"<(i|b|u|s)>(.+)</(?:i|b|u|s)>"#).unwrap())
 }
 
+/// Matches the alignment overrides that .srt players understand, either
+/// `{\anN}` or the older SSA `{\aN}`, as well as `<font>` tags.
+fn srt_artifact_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#"\{\\a(n?)(\d{1,2})\}|</?font[^>]*>"#).unwrap())
+}
+
+/// Converts an SSA `\a` alignment to the numpad layout used by `\an`.
+fn legacy_alignment(value: u8) -> Option<u8> {
+    match value {
+        1..=3 => Some(value),
+        5..=7 => Some(value + 2),
+        9..=11 => Some(value - 5),
+        _ => None,
+    }
+}
+
+/// Moves the alignment of an .srt cue to the start of the text.
+///
+/// Players honour an alignment anywhere in an .srt cue, but it only has
+/// one meaning per line so the first one wins and is written as `{\anN}`.
+/// `<font>` tags have no .ass equivalent and are removed.
+fn strip_srt_artifacts(line: &str) -> String {
+    let mut alignment = None;
+    let text = srt_artifact_regex().replace_all(line, |caps: &regex::Captures<'_>| {
+        if let Some(value) = caps.get(2).and_then(|m| m.as_str().parse::<u8>().ok()) {
+            let value = if caps[1].is_empty() {
+                legacy_alignment(value)
+            } else {
+                Some(value).filter(|v| (1..=9).contains(v))
+            };
+            alignment = alignment.or(value);
+        }
+        ""
+    });
+    match alignment {
+        Some(value) => format!("{{\\an{value}}}{}", text.trim_start()),
+        None => text.into_owned(),
+    }
+}
+
 fn srt_to_ass(line: &str) -> String {
+    let line = strip_srt_artifacts(line);
     // Replace HTML tags with proper ASS tags
     struct ReplaceTags;
 
@@ -1044,10 +2083,229 @@ fn srt_to_ass(line: &str) -> String {
     }
 
     srt_tag_regex()
-        .replace_all(line, ReplaceTags)
+        .replace_all(&line, ReplaceTags)
         .replace("\n", r#"\N"#)
 }
 
+fn ass_tag_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#"\{(.+)\}"#).unwrap())
+}
+
+fn allowed_ass_tags_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#"(\\an\d)"#).unwrap())
+}
+
+fn drawing_events_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#"\{\\p\d\}(.+)\{\\p\d\}"#).unwrap())
+}
+
+fn font_size_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#"^\{[^}]*\\fs(\d+(?:\.\d+)?)"#).unwrap())
+}
+
+fn special_ass_character_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#"\\(n|N|h)"#).unwrap())
+}
+
+/// Converts .ass text into .srt text, see [`crate::ass_to_dialogue`].
+#[allow(clippy::collapsible_match)]
+pub(crate) fn clean_ass_text(s: &str) -> String {
+    // Remove drawing events
+    let result = drawing_events_regex().replace_all(s, "");
+    // Remove all other ass tags
+    let result = ass_tag_regex().replace_all(&result, |captures: &regex::Captures| {
+        match allowed_ass_tags_regex().find(&captures[1]) {
+            Some(m) => {
+                let mut buffer = String::with_capacity(2 + m.len());
+                buffer.push('{');
+                buffer.push_str(m.as_str());
+                buffer.push('}');
+                buffer
+            }
+            None => String::new(),
+        }
+    });
+    // Replace special characters
+    let result =
+        special_ass_character_regex().replace_all(&result, |captures: &regex::Captures| {
+            match captures.get(1) {
+                Some(m) => {
+                    if m.as_str() == "N" {
+                        "\n"
+                    } else {
+                        " "
+                    }
+                }
+                None => " ", // This should technically be "unreachable"
+            }
+        });
+    // Blank lines are the dialogue separator in .srt files so they can't be
+    // in the text. Runs of line breaks are collapsed and leading and trailing
+    // line breaks are removed.
+    result
+        .split('\n')
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Wraps the cleaned text in a `<font size>` tag if the .ass text sets `\fs` at the start.
+///
+/// Size changes partway through the text can't be represented so they're ignored.
+pub(crate) fn add_font_size(ass_text: &str, cleaned: String) -> String {
+    let Some(captures) = font_size_regex().captures(ass_text) else {
+        return cleaned;
+    };
+    if cleaned.is_empty() {
+        return cleaned;
+    }
+    // Positioning has to stay at the very start of the text
+    let split = match cleaned.strip_prefix("{\\an") {
+        Some(rest) => rest.find('}').map_or(0, |i| i + 5),
+        None => 0,
+    };
+    let (position, text) = cleaned.split_at(split);
+    format!("{position}<font size=\"{}\">{text}</font>", &captures[1])
+}
+
+/// Options that control how an .ass file is parsed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ParseOptions {
+    /// Reject files that are technically parseable but malformed,
+    /// such as files with duplicate `[Events]` sections or events
+    /// that end before they start.
+    pub strict: bool,
+    /// Leave the `[Events]` section empty.
+    ///
+    /// This makes parsing large files a lot faster when only the
+    /// styles and other metadata are needed.
+    pub skip_events: bool,
+}
+
+impl ParseOptions {
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// Options that control how an .ass file is written.
+///
+/// New options may be added, so start from [`WriteOptions::default`] and
+/// set the fields that are needed:
+///
+/// ```
+/// let mut options = sub_tools::ass::WriteOptions::default();
+/// options.bom = true;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteOptions {
+    /// Separate the fields of `Format:` lines with `,` rather than `, `.
+    pub compact_format: bool,
+    /// Start the output with a UTF-8 byte order mark.
+    pub bom: bool,
+}
+
+impl WriteOptions {
+    fn write_format<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        fields: &[&str],
+    ) -> std::io::Result<()> {
+        let separator = if self.compact_format { "," } else { ", " };
+        writeln!(writer, "Format: {}", fields.join(separator))
+    }
+}
+
+/// The shared state between the different parsing entry points.
+struct Parser {
+    sections: Vec<Section>,
+    options: ParseOptions,
+    /// The errors that would have been raised in strict mode.
+    warnings: Vec<Error>,
+}
+
+impl Parser {
+    fn new(options: ParseOptions) -> Self {
+        Self {
+            sections: Vec::new(),
+            options,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn push_section(&mut self, section: Section) -> Result<(), Error> {
+        if self.options.strict {
+            if let Some(title) = section.major_title() {
+                if self.sections.iter().any(|s| s.major_title() == Some(title)) {
+                    return Err(ErrorKind::DuplicateSection(title.to_owned()).into());
+                }
+            }
+        }
+        self.sections.push(section);
+        Ok(())
+    }
+
+    fn process_line(&mut self, line: &str, line_number: usize) -> Result<(), Error> {
+        if line == "[Script Info]" {
+            self.push_section(Section::ScriptInfo(ScriptInfo::new()))
+        } else if is_styles_header(line) {
+            let legacy = line.eq_ignore_ascii_case("[V4 Styles]");
+            self.push_section(Section::Styles(StylesSection::new(legacy)))
+        } else if line == "[Events]" {
+            self.push_section(Section::Events(EventsSection::new()))
+        } else if let Some(title) = get_generic_section_title(line) {
+            self.push_section(Section::Generic(GenericSection::new(title)))
+        } else if let Some(section) = self.sections.last_mut() {
+            if self.options.skip_events && section.as_events().is_some() {
+                return Ok(());
+            }
+            let parsed = Line::parse(line, section.expects_encoded());
+            let missing_fields = match (section.as_events(), parsed.item()) {
+                (Some(events), Some((key, value))) if key != "Format" => {
+                    events.check_field_count(value)
+                }
+                _ => None,
+            };
+            section.process_line(parsed)?;
+            if let Some(kind) = missing_fields {
+                if self.options.strict {
+                    return Err(kind.into());
+                }
+                self.warnings.push(Error::from(kind).with_line(line_number));
+            }
+            // Any previous event would have been rejected already so only the last one needs checking
+            if self.options.strict {
+                let events = section.as_events();
+                if let Some(event) = events.and_then(|s| s.entries.last()?.item()) {
+                    if !event.is_valid() {
+                        return Err(ErrorKind::InvalidTiming.into());
+                    }
+                }
+            }
+            Ok(())
+        } else {
+            Err(ErrorKind::Invalid.into())
+        }
+    }
+
+    fn finish(self) -> (Ass, Vec<Error>) {
+        let ass = Ass {
+            sections: self.sections,
+        };
+        (ass, self.warnings)
+    }
+}
+
 /// A parsed .ass subtitle file.
 ///
 /// Only .ass v4+ is supported
@@ -1061,14 +2319,45 @@ fn get_generic_section_title(s: &str) -> Option<&str> {
     s.strip_prefix('[')?.strip_suffix(']')
 }
 
+/// Returns `true` if the line starts the styles section.
+///
+/// Besides `[V4+ Styles]`, this accepts `[V4 Styles]` from .ssa files and
+/// `[V4++ Styles]` from some newer tools, ignoring case. The fields are
+/// read using the `Format:` line so the ones that don't exist in v4+
+/// are ignored, except `MarginT` which is used as the vertical margin.
+///
+/// For `[V4 Styles]`, the SSA alignment is converted to the numpad layout,
+/// `TertiaryColour` is used as the outline colour and decimal colours are
+/// accepted. `AlphaLevel` was never used by SSA renderers and is dropped.
+fn is_styles_header(line: &str) -> bool {
+    get_generic_section_title(line).is_some_and(|title| {
+        let title = title.to_ascii_lowercase();
+        title.starts_with("v4") && title.ends_with(" styles")
+    })
+}
+
+/// Whether two style names refer to the same style.
+///
+/// Similar to libass, a leading `*` in either name is ignored.
+fn same_style(a: &str, b: &str) -> bool {
+    a.strip_prefix('*').unwrap_or(a) == b.strip_prefix('*').unwrap_or(b)
+}
+
 impl Ass {
     pub fn open(path: &Path) -> Result<Self, Error> {
         let file = std::fs::File::open(path)?;
         Self::from_reader(std::io::BufReader::new(file))
     }
 
-    pub fn from_reader<R: BufRead>(mut reader: R) -> Result<Self, Error> {
-        let mut sections = Vec::<Section>::new();
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, Error> {
+        Self::from_reader_with(reader, ParseOptions::default())
+    }
+
+    pub fn from_reader_with<R: BufRead>(
+        mut reader: R,
+        options: ParseOptions,
+    ) -> Result<Self, Error> {
+        let mut parser = Parser::new(options);
 
         // First line has to be [Script Info]
         // It also optionally has a UTF-8 BOM
@@ -1080,7 +2369,7 @@ impl Ass {
             .unwrap_or(buf.as_str())
             .trim_end();
         if cleaned == "[Script Info]" {
-            sections.push(Section::ScriptInfo(ScriptInfo::new()));
+            parser.push_section(Section::ScriptInfo(ScriptInfo::new()))?;
         } else {
             return Err(Error {
                 kind: ErrorKind::MissingScriptInfo,
@@ -1091,36 +2380,53 @@ impl Ass {
         for (index, line) in reader.lines().enumerate() {
             let line = line?;
             let line_number = index + 2;
+            parser
+                .process_line(&line, line_number)
+                .map_err(|e| e.with_line(line_number))?;
+        }
 
-            if line == "[V4+ Styles]" {
-                sections.push(Section::Styles(StylesSection::new()));
-            } else if line == "[Events]" {
-                sections.push(Section::Events(EventsSection::new()));
-            } else if let Some(title) = get_generic_section_title(&line) {
-                sections.push(Section::Generic(GenericSection::new(title)));
-            } else if let Some(section) = sections.last_mut() {
-                let Some(parsed) = Line::parse(&line) else {
-                    continue;
-                };
-                section
-                    .process_line(parsed)
-                    .map_err(|e| e.with_line(line_number))?;
-            } else {
-                return Err(Error {
-                    kind: ErrorKind::Invalid,
-                    line: line_number,
-                });
-            }
+        Ok(parser.finish().0)
+    }
+
+    /// Parses an .ass file from a string with the given options.
+    pub fn parse_with(buf: &str, options: ParseOptions) -> Result<Self, Error> {
+        Self::parse_with_warnings(buf, options).map(|(ass, _)| ass)
+    }
+
+    /// Parses an .ass file from a string with the given options, also
+    /// returning what was accepted despite being malformed.
+    ///
+    /// The warnings are the errors that [`ParseOptions::strict`] would have
+    /// raised instead, which is currently only
+    /// [`ErrorKind::MissingEventFields`]. Missing fields are left at their
+    /// default values.
+    pub fn parse_with_warnings(
+        buf: &str,
+        options: ParseOptions,
+    ) -> Result<(Self, Vec<Error>), Error> {
+        if !buf.starts_with("[Script Info]") {
+            return Err(Error {
+                kind: ErrorKind::MissingScriptInfo,
+                line: 1,
+            });
+        }
+
+        let mut parser = Parser::new(options);
+        for (index, line) in buf.lines().enumerate() {
+            let line_number = index + 1;
+            parser
+                .process_line(line, line_number)
+                .map_err(|e| e.with_line(line_number))?;
         }
 
-        Ok(Self { sections })
+        Ok(parser.finish())
     }
 
     pub fn from_srt(dialogue: Vec<crate::srt::Dialogue>) -> Self {
         let mut sections = Vec::with_capacity(3);
         sections.push(Section::ScriptInfo(ScriptInfo::default()));
         let mut styles = StylesSection::default();
-        styles.styles.clear();
+        styles.entries.clear();
         let mut style = Style::program_default();
         if dialogue
             .iter()
@@ -1129,16 +2435,18 @@ impl Ass {
             style.bold = true;
             style.name = String::from("Yu Gothic UI");
         }
-        styles.styles.push(style);
+        styles.push(style);
         sections.push(Section::Styles(styles));
         sections.push(Section::Events(EventsSection {
-            events: dialogue
+            entries: dialogue
                 .into_iter()
-                .map(|d| Event {
-                    text: srt_to_ass(&d.text),
-                    start: d.start,
-                    end: d.end,
-                    ..Default::default()
+                .map(|d| {
+                    Entry::Item(Event {
+                        text: srt_to_ass(&d.text),
+                        start: d.start,
+                        end: d.end,
+                        ..Default::default()
+                    })
                 })
                 .collect(),
             ..Default::default()
@@ -1146,33 +2454,608 @@ impl Ass {
         Self { sections }
     }
 
+    /// Assembles a script from its script info, styles, and events.
+    ///
+    /// If `script_info` is empty then the default script info is used.
+    pub fn from_parts<K, V>(
+        script_info: impl IntoIterator<Item = (K, V)>,
+        styles: Vec<Style>,
+        events: Vec<Event>,
+    ) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let lines = script_info
+            .into_iter()
+            .map(|(key, value)| Line::variable(key.as_ref(), value.as_ref()))
+            .collect::<Vec<_>>();
+        let script_info = if lines.is_empty() {
+            ScriptInfo::default()
+        } else {
+            ScriptInfo { lines }
+        };
+        Self {
+            sections: vec![
+                Section::ScriptInfo(script_info),
+                Section::Styles(StylesSection {
+                    entries: styles.into_iter().map(Entry::Item).collect(),
+                    ..Default::default()
+                }),
+                Section::Events(EventsSection {
+                    entries: events.into_iter().map(Entry::Item).collect(),
+                    ..Default::default()
+                }),
+            ],
+        }
+    }
+
     pub fn save(&self, path: &Path) -> std::io::Result<()> {
-        let mut fp = std::fs::File::create(path)?;
-        for section in &self.sections {
-            section.to_ass(&mut fp)?;
+        self.save_with(path, &WriteOptions::default())
+    }
+
+    /// Saves the file to the given path with the given options.
+    pub fn save_with(&self, path: &Path, options: &WriteOptions) -> std::io::Result<()> {
+        let fp = std::fs::File::create(path)?;
+        self.save_to_writer_with(std::io::BufWriter::new(fp), options)
+    }
+
+    /// Writes the file to the given writer.
+    ///
+    /// Sections are separated by a single blank line and the output ends
+    /// with exactly one newline.
+    pub fn save_to_writer<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        self.save_to_writer_with(writer, &WriteOptions::default())
+    }
+
+    /// Writes the file to the given writer with the given options.
+    pub fn save_to_writer_with<W: std::io::Write>(
+        &self,
+        mut writer: W,
+        options: &WriteOptions,
+    ) -> std::io::Result<()> {
+        if options.bom {
+            writer.write_all(&crate::UTF8_BOM)?;
         }
-        Ok(())
+        for (index, section) in self.sections.iter().enumerate() {
+            if index != 0 {
+                writeln!(writer)?;
+            }
+            section.to_ass_with(&mut writer, options)?;
+        }
+        writer.flush()
+    }
+
+    /// Returns a copy of the script with only its events.
+    ///
+    /// The script info is replaced with a minimal stub and the styles section
+    /// is kept but empty, so the result is still a valid script.
+    pub fn events_only(&self) -> Ass {
+        Ass::from_parts(
+            [("ScriptType", "v4.00+")],
+            Vec::new(),
+            self.events().cloned().collect(),
+        )
+    }
+
+    /// Returns the events as a table with aligned columns.
+    ///
+    /// This is meant for reviewing a script by hand and can't be parsed
+    /// back. Padding the fields of the events themselves isn't an option
+    /// since renderers don't agree on whether the spaces are significant.
+    pub fn events_table(&self) -> String {
+        const HEADER: [&str; 6] = ["Kind", "Layer", "Start", "End", "Style", "Name"];
+        let rows = self
+            .events()
+            .map(|event| {
+                [
+                    event.kind.as_str().to_owned(),
+                    event.layer.to_string(),
+                    AssDuration(&event.start).to_string(),
+                    AssDuration(&event.end).to_string(),
+                    event.style.clone(),
+                    event.name.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        // Display width rather than chars so e.g. Japanese style names line up
+        let mut widths = HEADER.map(UnicodeWidthStr::width);
+        for row in &rows {
+            for (width, field) in widths.iter_mut().zip(row) {
+                *width = (*width).max(field.width());
+            }
+        }
+
+        let mut table = String::new();
+        let mut push_row = |fields: &[&str], text: &str| {
+            for (field, width) in fields.iter().zip(widths) {
+                table.push_str(field);
+                table.push_str(&" ".repeat(width - field.width() + 2));
+            }
+            table.push_str(text);
+            table.push('\n');
+        };
+        push_row(&HEADER, "Text");
+        for (row, event) in rows.iter().zip(self.events()) {
+            push_row(&row.each_ref().map(String::as_str), &event.text);
+        }
+        table
+    }
+
+    /// Returns `true` if the file has a `[V4+ Styles]` section.
+    pub fn has_styles(&self) -> bool {
+        self.sections.iter().any(|s| s.as_styles().is_some())
+    }
+
+    /// Splits the file into multiple files, one for each style.
+    ///
+    /// Every file retains the other sections (e.g. `[Script Info]`) as-is but
+    /// only keeps the style it was split by and the events that reference it.
+    /// Comments and drawings are kept with the style they reference.
+    ///
+    /// Events that reference a style that isn't defined still get their own
+    /// file, just without any style.
+    pub fn split_by_style(&self) -> HashMap<String, Ass> {
+        let mut names = self.styles().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        for event in self.events() {
+            if !names.contains(&event.style.as_str()) {
+                names.push(&event.style);
+            }
+        }
+
+        names
+            .into_iter()
+            .map(|name| {
+                let mut ass = self.clone();
+                for section in &mut ass.sections {
+                    match section {
+                        Section::Styles(styles) => styles.retain(|s| s.name == name),
+                        Section::Events(events) => events.retain(|e| e.style == name),
+                        _ => {}
+                    }
+                }
+                (name.to_owned(), ass)
+            })
+            .collect()
     }
 
-    pub fn save_to_writer<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
-        for section in &self.sections {
-            section.to_ass(&mut writer)?;
+    /// Merges another file on top of this one.
+    ///
+    /// The styles of `other` are added to this file's styles and its events are
+    /// appended after this file's events. Styles from `other` whose name is already
+    /// taken are renamed with a numbered suffix (e.g. `Default-2`) and the events
+    /// that reference them are updated accordingly.
+    ///
+    /// Returns the renamed styles as `(old, new)` pairs.
+    pub fn overlay(&mut self, mut other: Ass) -> Vec<(String, String)> {
+        let mut names = self
+            .styles()
+            .map(|s| s.name.clone())
+            .collect::<HashSet<_>>();
+        let mut renamed = Vec::new();
+        for style in other.styles_mut() {
+            if names.contains(&style.name) {
+                let new_name = (2..)
+                    .map(|i| format!("{}-{i}", style.name))
+                    .find(|n| !names.contains(n))
+                    .unwrap();
+                let old_name = std::mem::replace(&mut style.name, new_name.clone());
+                renamed.push((old_name, new_name));
+            }
+            names.insert(style.name.clone());
         }
+
+        for event in other.events_mut() {
+            if let Some((_, new_name)) = renamed.iter().find(|(old, _)| *old == event.style) {
+                event.style.clone_from(new_name);
+            }
+        }
+
+        let styles = other
+            .sections
+            .iter_mut()
+            .filter_map(|s| s.as_styles_mut())
+            .flat_map(|s| s.entries.drain(..))
+            .collect::<Vec<_>>();
+        let entries = other
+            .sections
+            .iter_mut()
+            .filter_map(|s| s.as_events_mut())
+            .flat_map(|s| s.entries.drain(..))
+            .collect::<Vec<_>>();
+
+        self.styles_section_mut().entries.extend(styles);
+
+        match self.sections.iter_mut().find_map(|s| s.as_events_mut()) {
+            Some(section) => section.entries.extend(entries),
+            None => self.sections.push(Section::Events(EventsSection {
+                entries,
+                ..Default::default()
+            })),
+        }
+
+        renamed
+    }
+
+    /// Returns the style with the given name.
+    ///
+    /// Similar to libass, a leading `*` in either name is ignored.
+    pub fn style(&self, name: &str) -> Option<&Style> {
+        self.styles().find(|s| same_style(&s.name, name))
+    }
+
+    /// Renames a style along with every event and `\r` override tag that references it.
+    pub fn rename_style(&mut self, old: &str, new: &str) -> Result<(), RenameStyleError> {
+        if self.style(new).is_some() {
+            return Err(RenameStyleError::AlreadyExists(new.to_owned()));
+        }
+        if self.style(old).is_none() && !self.events().any(|e| same_style(&e.style, old)) {
+            return Err(RenameStyleError::NotFound(old.to_owned()));
+        }
+
+        for style in self.styles_mut().filter(|s| same_style(&s.name, old)) {
+            new.clone_into(&mut style.name);
+        }
+
+        self.repoint_style(old, new);
         Ok(())
     }
 
+    /// Points every event and `\r` override tag that references `old` to `new`.
+    fn repoint_style(&mut self, old: &str, new: &str) {
+        let old_reset = format!("\\r{old}");
+        let new_reset = format!("\\r{new}");
+        for event in self.events_mut() {
+            if same_style(&event.style, old) {
+                new.clone_into(&mut event.style);
+            }
+
+            if event.text.contains(&old_reset) {
+                let spans = event
+                    .spans()
+                    .into_iter()
+                    .map(|span| match &span {
+                        TextSpan::Override(_) => TextSpan::Override(
+                            span.tags()
+                                .into_iter()
+                                .map(|tag| if tag == old_reset { &new_reset } else { tag })
+                                .collect(),
+                        ),
+                        TextSpan::Text(_) => span,
+                    })
+                    .collect::<Vec<_>>();
+                event.set_spans(&spans);
+            }
+        }
+    }
+
+    /// Returns the `[V4+ Styles]` section, adding an empty one if there isn't one.
+    fn styles_section_mut(&mut self) -> &mut StylesSection {
+        let index = match self.sections.iter().position(|s| s.as_styles().is_some()) {
+            Some(index) => index,
+            None => {
+                // Styles conventionally go right after the script info
+                let index = self
+                    .sections
+                    .iter()
+                    .position(|s| s.as_script_info().is_none())
+                    .unwrap_or(self.sections.len());
+                self.sections.insert(
+                    index,
+                    Section::Styles(StylesSection {
+                        entries: Vec::new(),
+                        ..Default::default()
+                    }),
+                );
+                index
+            }
+        };
+        self.sections[index].as_styles_mut().unwrap()
+    }
+
+    /// Makes every event use the style with the given name and removes every other style.
+    ///
+    /// If the style doesn't exist then it's created from the default style
+    /// used for conversions. Note that `\r` override tags that reset to
+    /// another style still refer to the removed style.
+    pub fn unify_style(&mut self, name: &str) {
+        let style = self.style(name).cloned().unwrap_or_else(|| Style {
+            name: name.to_owned(),
+            ..Style::program_default()
+        });
+        for section in self.sections.iter_mut().filter_map(|s| s.as_styles_mut()) {
+            section.retain(|_| false);
+        }
+        self.styles_section_mut().push(style);
+        for event in self.events_mut() {
+            name.clone_into(&mut event.style);
+        }
+    }
+
+    /// Removes styles that have the same properties as an earlier style.
+    ///
+    /// Events and `\r` override tags that reference a removed style are
+    /// pointed to the style that was kept instead. This is mostly useful
+    /// after merging files that came with copies of the same styles.
+    ///
+    /// Returns the name of every removed style along with the name of the
+    /// style it was merged into.
+    pub fn dedup_styles(&mut self) -> Vec<(String, String)> {
+        let mut kept: Vec<Style> = Vec::new();
+        let mut merged = Vec::new();
+        for section in self.sections.iter_mut().filter_map(|s| s.as_styles_mut()) {
+            section.retain(
+                |style| match kept.iter().find(|k| k.has_same_properties(style)) {
+                    Some(original) => {
+                        merged.push((style.name.clone(), original.name.clone()));
+                        false
+                    }
+                    None => {
+                        kept.push(style.clone());
+                        true
+                    }
+                },
+            );
+        }
+
+        for (old, new) in &merged {
+            if old != new {
+                self.repoint_style(old, new);
+            }
+        }
+        merged
+    }
+
+    /// Removes styles that no event uses, either directly or through a `\r`
+    /// override tag.
+    ///
+    /// Returns the name of every removed style.
+    pub fn remove_unused_styles(&mut self) -> Vec<String> {
+        let mut used = HashSet::new();
+        for event in self.events() {
+            used.insert(event.style.clone());
+            for span in event.spans() {
+                used.extend(
+                    span.tags()
+                        .into_iter()
+                        .filter_map(|tag| tag.strip_prefix("\\r"))
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_owned),
+                );
+            }
+        }
+        let mut removed = Vec::new();
+        for section in self.sections.iter_mut().filter_map(|s| s.as_styles_mut()) {
+            section.retain(|style| {
+                let keep = used.contains(&style.name);
+                if !keep {
+                    removed.push(style.name.clone());
+                }
+                keep
+            });
+        }
+        removed
+    }
+
+    /// Returns the style names that events reference but aren't defined.
+    ///
+    /// Each name is paired with the number of events that reference it
+    /// and the names are in the order they first appear.
+    pub fn undefined_styles(&self) -> Vec<(&str, usize)> {
+        let mut result: Vec<(&str, usize)> = Vec::new();
+        for event in self.events() {
+            if self.style(&event.style).is_some() {
+                continue;
+            }
+            match result.iter_mut().find(|(name, _)| *name == event.style) {
+                Some((_, count)) => *count += 1,
+                None => result.push((&event.style, 1)),
+            }
+        }
+        result
+    }
+
+    /// Runs the structural checks on the file and returns every issue found.
+    ///
+    /// Issues that affect an event are in event order. Invalid colours and
+    /// other malformed values are already rejected at parse time.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let has_styles = self.has_styles();
+        if !has_styles {
+            issues.push(ValidationIssue::new(ValidationIssueKind::MissingStyles));
+        }
+
+        for style in self.styles().filter(|s| !(1..=9).contains(&s.alignment)) {
+            issues.push(ValidationIssue::new(
+                ValidationIssueKind::InvalidStyleAlignment {
+                    style: style.name.clone(),
+                    alignment: style.alignment,
+                },
+            ));
+        }
+
+        let events = self.events().collect::<Vec<_>>();
+        let mut event_issues = Vec::new();
+        for (index, event) in events.iter().enumerate() {
+            if has_styles && self.style(&event.style).is_none() {
+                event_issues.push(ValidationIssue::for_event(
+                    ValidationIssueKind::UndefinedStyle(event.style.clone()),
+                    index,
+                    event,
+                ));
+            }
+            if !event.is_valid() {
+                event_issues.push(ValidationIssue::for_event(
+                    ValidationIssueKind::InvertedTiming,
+                    index,
+                    event,
+                ));
+            } else if event.kind.is_dialogue() && event.duration().is_zero() {
+                event_issues.push(ValidationIssue::for_event(
+                    ValidationIssueKind::ZeroDuration,
+                    index,
+                    event,
+                ));
+            }
+            for span in event.spans().iter().filter(|s| s.is_override()) {
+                for tag in span.tags() {
+                    let Some(value) = tag.strip_prefix("\\an") else {
+                        continue;
+                    };
+                    if !matches!(value.parse::<u8>(), Ok(1..=9)) {
+                        event_issues.push(ValidationIssue::for_event(
+                            ValidationIssueKind::InvalidAlignmentTag(tag.to_owned()),
+                            index,
+                            event,
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Sorting by start within each layer and style means only the
+        // latest ending event so far needs to be compared against
+        let mut order = (0..events.len())
+            .filter(|&i| events[i].kind.is_dialogue())
+            .collect::<Vec<_>>();
+        order.sort_by_key(|&i| (events[i].layer, &events[i].style, events[i].start));
+        let mut latest: Option<usize> = None;
+        for index in order {
+            let event = events[index];
+            if let Some(previous) = latest {
+                let other = events[previous];
+                if other.layer != event.layer || other.style != event.style {
+                    latest = None;
+                } else if event.start < other.end {
+                    event_issues.push(ValidationIssue::for_event(
+                        ValidationIssueKind::Overlap(previous),
+                        index,
+                        event,
+                    ));
+                }
+            }
+            if latest.is_none_or(|i| events[i].end < event.end) {
+                latest = Some(index);
+            }
+        }
+
+        event_issues.sort_by_key(|issue| issue.event);
+        issues.extend(event_issues);
+        issues
+    }
+
+    pub fn styles(&self) -> impl Iterator<Item = &Style> {
+        self.sections
+            .iter()
+            .filter_map(|s| s.as_styles())
+            .flat_map(|s| s.styles())
+    }
+
+    pub fn styles_mut(&mut self) -> impl Iterator<Item = &mut Style> {
+        self.sections
+            .iter_mut()
+            .filter_map(|s| s.as_styles_mut())
+            .flat_map(|s| s.styles_mut())
+    }
+
+    /// Calls the closure with the start and end of every event so they can be modified.
+    ///
+    /// This is useful for custom re-timing logic, for example converting
+    /// subtitles timed for 23.976 FPS to 25 FPS:
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use sub_tools::ass::Ass;
+    ///
+    /// let mut ass = Ass::from_srt(vec![sub_tools::srt::Dialogue {
+    ///     position: 1,
+    ///     start: Duration::from_secs(25),
+    ///     end: Duration::from_secs(50),
+    ///     text: String::from("Hello"),
+    /// }]);
+    ///
+    /// let ratio = 24000.0 / 1001.0 / 25.0;
+    /// ass.retime(|start, end| {
+    ///     *start = start.mul_f64(ratio);
+    ///     *end = end.mul_f64(ratio);
+    /// });
+    ///
+    /// let event = ass.events().next().unwrap();
+    /// assert_eq!(event.start.as_millis(), 23976);
+    /// ```
+    pub fn retime<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Duration, &mut Duration),
+    {
+        for event in self.events_mut() {
+            f(&mut event.start, &mut event.end);
+        }
+    }
+
     pub fn events(&self) -> impl Iterator<Item = &Event> {
         self.sections
             .iter()
             .filter_map(|s| s.as_events())
-            .flat_map(|e| e.events.iter())
+            .flat_map(|e| e.events())
+    }
+
+    /// Keeps only the first `len` dialogue events, dropping every event after them.
+    ///
+    /// Comment events in between the kept dialogue are kept as well.
+    pub fn truncate_events(&mut self, len: usize) {
+        let mut count = 0;
+        self.retain_events(|e| {
+            let keep = count < len;
+            count += usize::from(e.kind.is_dialogue());
+            keep
+        });
     }
 
     pub fn events_mut(&mut self) -> impl Iterator<Item = &mut Event> {
         self.sections
             .iter_mut()
             .filter_map(|s| s.as_events_mut())
-            .flat_map(|e| e.events.iter_mut())
+            .flat_map(|e| e.events_mut())
+    }
+
+    /// Keeps only the events that the predicate returns `true` for.
+    pub fn retain_events<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Event) -> bool,
+    {
+        for section in self.sections.iter_mut().filter_map(|s| s.as_events_mut()) {
+            section.retain(&mut f);
+        }
+    }
+
+    /// Sets the layer of every event, or only of the events that use the given style.
+    ///
+    /// Returns how many events had their layer changed.
+    pub fn set_layer(&mut self, layer: u8, style: Option<&str>) -> usize {
+        let mut changed = 0;
+        for event in self
+            .events_mut()
+            .filter(|e| style.is_none_or(|s| e.style == s))
+        {
+            changed += usize::from(event.layer != layer);
+            event.layer = layer;
+        }
+        changed
+    }
+
+    /// Sorts the events by when they start.
+    ///
+    /// Events that start at the same time are sorted by their end, layer,
+    /// style and text, so the order doesn't depend on the order in the file.
+    pub fn sort_events(&mut self) {
+        fn key(e: &Event) -> (Duration, Duration, u8, &str, &str) {
+            (e.start, e.end, e.layer, &e.style, &e.text)
+        }
+        for section in self.sections.iter_mut().filter_map(|s| s.as_events_mut()) {
+            section.sort_by(|a, b| key(a).cmp(&key(b)));
+        }
     }
 }
 
@@ -1180,39 +3063,706 @@ impl FromStr for Ass {
     type Err = Error;
 
     fn from_str(buf: &str) -> Result<Self, Self::Err> {
-        if !buf.starts_with("[Script Info]") {
-            return Err(Error {
-                kind: ErrorKind::MissingScriptInfo,
-                line: 1,
-            });
-        }
+        Self::parse_with(buf, ParseOptions::default())
+    }
+}
 
-        let mut sections = Vec::new();
-        for (index, line) in buf.lines().enumerate() {
-            let line_number = index + 1;
-            if line == "[Script Info]" {
-                sections.push(Section::ScriptInfo(ScriptInfo::new()));
-            } else if line == "[V4+ Styles]" {
-                sections.push(Section::Styles(StylesSection::new()));
-            } else if line == "[Events]" {
-                sections.push(Section::Events(EventsSection::new()));
-            } else if let Some(title) = get_generic_section_title(line) {
-                sections.push(Section::Generic(GenericSection::new(title)));
-            } else if let Some(section) = sections.last_mut() {
-                let Some(parsed) = Line::parse(line) else {
-                    continue;
-                };
-                section
-                    .process_line(parsed)
-                    .map_err(|e| e.with_line(line_number))?;
-            } else {
-                return Err(Error {
-                    kind: ErrorKind::Invalid,
-                    line: line_number,
-                });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_section() {
+        let buf = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\n[Events]\n";
+        assert!(buf.parse::<Ass>().is_ok());
+
+        let error = Ass::parse_with(buf, ParseOptions::strict()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::DuplicateSection(title) if title == "Events"));
+        assert_eq!(error.line(), 7);
+    }
+
+    #[test]
+    fn test_inverted_timing() {
+        let buf = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:02.00,0:00:01.00,Default,,0,0,0,,Hello\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        assert!(!ass.events().next().unwrap().is_valid());
+
+        let error = Ass::parse_with(buf, ParseOptions::strict()).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::InvalidTiming));
+        assert_eq!(error.line(), 6);
+    }
+
+    #[test]
+    fn test_skip_events() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:02.00,0:00:01.00,Default,,0,0,0,,Hello\nnot an event\n\n[Fonts]\nfontname: a.ttf\n";
+        let options = ParseOptions {
+            strict: true,
+            skip_events: true,
+        };
+        let ass = Ass::parse_with(buf, options).unwrap();
+        assert_eq!(ass.events().count(), 0);
+        assert_eq!(ass.styles().next().unwrap().font_name, "Arial");
+        assert!(ass.sections.iter().any(|s| s.as_events().is_some()));
+    }
+
+    #[test]
+    fn test_missing_event_fields() {
+        let buf = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello, world\nDialogue: 0,0:00:03.00,0:00:04.00,Default\n";
+        let (ass, warnings) = Ass::parse_with_warnings(buf, ParseOptions::default()).unwrap();
+        let events = ass.events().collect::<Vec<_>>();
+        assert_eq!(events[0].text, "Hello, world");
+        assert_eq!(events[1].style, "Default");
+        assert_eq!(events[1].text, "");
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            warnings[0].kind(),
+            ErrorKind::MissingEventFields {
+                expected: 10,
+                found: 4
             }
+        ));
+        assert_eq!(warnings[0].line(), 7);
+
+        let error = Ass::parse_with(buf, ParseOptions::strict()).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::MissingEventFields {
+                expected: 10,
+                found: 4
+            }
+        ));
+        assert_eq!(error.line(), 7);
+    }
+
+    #[test]
+    fn test_text_spans() {
+        let text = r"{\an8}Hello {\b1\t(0,100,\fs20)}world{\b0}\N{unterminated";
+        let spans = TextSpan::parse(text);
+        assert_eq!(
+            spans,
+            [
+                TextSpan::Override(r"\an8".to_owned()),
+                TextSpan::Text("Hello ".to_owned()),
+                TextSpan::Override(r"\b1\t(0,100,\fs20)".to_owned()),
+                TextSpan::Text("world".to_owned()),
+                TextSpan::Override(r"\b0".to_owned()),
+                TextSpan::Text(r"\N{unterminated".to_owned()),
+            ]
+        );
+        assert_eq!(spans[2].tags(), [r"\b1", r"\t(0,100,\fs20)"]);
+        assert!(spans[1].tags().is_empty());
+        assert_eq!(TextSpan::join(&spans), text);
+    }
+
+    #[test]
+    fn test_rename_style() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\nStyle: JP,Arial,20\nStyle: EN,Arial,20\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,JP,,0,0,0,,{\\rJP2\\b1}A{\\rJP}B\n";
+        let mut ass = buf.parse::<Ass>().unwrap();
+        assert_eq!(
+            ass.rename_style("JP", "EN"),
+            Err(RenameStyleError::AlreadyExists("EN".to_owned()))
+        );
+        assert_eq!(
+            ass.rename_style("FR", "Other"),
+            Err(RenameStyleError::NotFound("FR".to_owned()))
+        );
+
+        ass.rename_style("JP", "Japanese").unwrap();
+        assert!(ass.style("Japanese").is_some());
+        assert!(ass.style("JP").is_none());
+        let event = ass.events().next().unwrap();
+        assert_eq!(event.style, "Japanese");
+        assert_eq!(event.text, r"{\rJP2\b1}A{\rJapanese}B");
+
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname\nStyle: *EN,Arial\n\n[Events]\nFormat: Layer, Start, End, Style, Text\nDialogue: 0,0:00:01.00,0:00:02.00,EN,A\nDialogue: 0,0:00:02.00,0:00:03.00,*EN,B\n";
+        let mut ass = buf.parse::<Ass>().unwrap();
+        ass.rename_style("*EN", "English").unwrap();
+        assert_eq!(ass.styles().next().unwrap().name, "English");
+        assert!(ass.events().all(|e| e.style == "English"));
+    }
+
+    #[test]
+    fn test_dedup_styles() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\nStyle: Default,Arial,20\nStyle: Big,Arial,40\nStyle: Default,Arial,20\nStyle: Copy,Arial,20\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Copy,,0,0,0,,A{\\rCopy}B\nDialogue: 0,0:00:02.00,0:00:03.00,Big,,0,0,0,,C\n";
+        let mut ass = buf.parse::<Ass>().unwrap();
+        let merged = ass.dedup_styles();
+        assert_eq!(
+            merged,
+            [
+                ("Default".to_owned(), "Default".to_owned()),
+                ("Copy".to_owned(), "Default".to_owned())
+            ]
+        );
+        let names = ass.styles().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Default", "Big"]);
+        let events = ass.events().collect::<Vec<_>>();
+        assert_eq!(events[0].style, "Default");
+        assert_eq!(events[0].text, r"A{\rDefault}B");
+        assert_eq!(events[1].style, "Big");
+        assert!(ass.dedup_styles().is_empty());
+    }
+
+    #[test]
+    fn test_remove_unused_styles() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\nStyle: Default,Arial,20\nStyle: Big,Arial,40\nStyle: Sign,Arial,30\nStyle: Unused,Arial,20\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,A{\\rSign}B{\\r}C\nComment: 0,0:00:02.00,0:00:03.00,Big,,0,0,0,,D\n";
+        let mut ass = buf.parse::<Ass>().unwrap();
+        assert_eq!(ass.remove_unused_styles(), ["Unused"]);
+        let names = ass.styles().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Default", "Big", "Sign"]);
+        assert!(ass.remove_unused_styles().is_empty());
+    }
+
+    #[test]
+    fn test_is_blank() {
+        let event = |text: &str| Event {
+            text: text.to_owned(),
+            ..Default::default()
+        };
+        assert!(event("").is_blank());
+        assert!(event(r"{\an8}\h\N ").is_blank());
+        assert!(!event(r"{\i1}Hi").is_blank());
+        assert!(!event(r"{\p1}m 0 0 l 10 10{\p0}").is_blank());
+
+        let events = vec![event(""), event("Hi"), event(" ")];
+        let mut ass = Ass::from_parts([("ScriptType", "v4.00+")], Vec::new(), events);
+        ass.retain_events(|e| !e.is_blank());
+        let texts = ass.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["Hi"]);
+    }
+
+    #[test]
+    fn test_event_duration() {
+        let mut event = Event {
+            start: Duration::from_millis(1500),
+            end: Duration::from_secs(4),
+            ..Default::default()
+        };
+        assert_eq!(event.duration(), Duration::from_millis(2500));
+        event.end = Duration::from_secs(1);
+        assert_eq!(event.duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_karaoke_syllables() {
+        let event = |text: &str| Event {
+            text: text.to_owned(),
+            ..Default::default()
+        };
+        assert!(event(r"{\b1}No karaoke").karaoke_syllables().is_empty());
+
+        let syllables =
+            event(r"{\an8}Sing: {\K50}la{\ko25\b1}la {\k100}{\kt10}la").karaoke_syllables();
+        let parts = syllables
+            .iter()
+            .map(|s| {
+                (
+                    s.offset.as_millis(),
+                    s.duration.as_millis(),
+                    s.effect,
+                    s.text.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            parts,
+            [
+                (0, 0, KaraokeEffect::Instant, r"{\an8}Sing: "),
+                (0, 500, KaraokeEffect::Sweep, "la"),
+                (500, 250, KaraokeEffect::Outline, r"{\b1}la "),
+                (750, 1000, KaraokeEffect::Instant, r"{\kt10}la"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_events() {
+        let event = |start: u64, text: &str| Event {
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(start + 1),
+            text: text.to_owned(),
+            ..Default::default()
+        };
+        let events = vec![event(3, "D"), event(1, "A"), event(3, "C"), event(2, "B")];
+        let mut ass = Ass::from_parts([("ScriptType", "v4.00+")], Vec::new(), events);
+        ass.sort_events();
+        let texts = ass.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn test_set_layer() {
+        let event = |layer: u8, style: &str| Event {
+            layer,
+            style: style.to_owned(),
+            ..Default::default()
+        };
+        let events = vec![event(0, "Default"), event(3, "Sign"), event(5, "Default")];
+        let mut ass = Ass::from_parts([("ScriptType", "v4.00+")], Vec::new(), events);
+        assert_eq!(ass.set_layer(2, Some("Sign")), 1);
+        assert_eq!(ass.set_layer(2, Some("Sign")), 0);
+        let layers = ass.events().map(|e| e.layer).collect::<Vec<_>>();
+        assert_eq!(layers, [0, 2, 5]);
+        assert_eq!(ass.set_layer(0, None), 2);
+        assert!(ass.events().all(|e| e.layer == 0));
+    }
+
+    #[test]
+    fn test_colour_blend() {
+        let grey = Colour::BLACK.blend(&Colour::WHITE, 0.5);
+        assert_eq!(grey, Colour::from_rgb(128, 128, 128));
+        assert_eq!(Colour::BLACK.blend(&Colour::RED, 0.0), Colour::BLACK);
+        assert_eq!(Colour::BLACK.blend(&Colour::RED, 2.0), Colour::RED);
+        let transparent = Colour::from_rgba(0, 0, 0, 255);
+        assert_eq!(Colour::WHITE.blend(&transparent, 0.5).alpha, 128);
+
+        // Opaque colours hide the background entirely
+        assert_eq!(Colour::RED.over(&Colour::WHITE), Colour::RED);
+        assert_eq!(transparent.over(&Colour::WHITE), Colour::WHITE);
+        let half = Colour::from_rgba(255, 255, 255, 128);
+        assert_eq!(half.over(&Colour::BLACK), Colour::from_rgb(127, 127, 127));
+        assert_eq!(transparent.over(&transparent), transparent);
+    }
+
+    #[test]
+    fn test_style_section_variants() {
+        for header in ["[V4 Styles]", "[V4++ Styles]", "[v4+ styles]"] {
+            let buf = format!("[Script Info]\nTitle: Test\n\n{header}\nFormat: Name, Fontname, Fontsize, MarginL, MarginR, MarginT, MarginB\nStyle: Main,Arial,20,1,2,30,4\n");
+            let ass = buf.parse::<Ass>().unwrap();
+            let style = ass.style("Main").expect(header);
+            assert_eq!(style.margin_v, 30);
+        }
+        assert!(!is_styles_header("[V4+ Stylesheet]"));
+        assert!(!is_styles_header("[Styles]"));
+    }
+
+    #[test]
+    fn test_ssa_styles() {
+        let buf = "[Script Info]\nScriptType: v4.00\n\n[V4 Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, TertiaryColour, BackColour, Bold, Italic, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, AlphaLevel, Encoding\nStyle: Default,Tahoma,24,16777215,65535,255,-2147483648,-1,0,1,1,2,6,30,30,30,0,0\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        let style = ass.style("Default").unwrap();
+        assert_eq!(style.alignment, 8);
+        assert_eq!(style.primary_colour, Colour::WHITE);
+        assert_eq!(style.outline_colour, Colour::RED);
+        assert_eq!(style.background_colour, Colour::from_rgba(0, 0, 0, 128));
+        assert!(style.bold);
+
+        // v4+ files keep their alignment as is
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Alignment\nStyle: Default,6\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        assert_eq!(ass.style("Default").unwrap().alignment, 6);
+    }
+
+    #[test]
+    fn test_unify_style() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\nStyle: Default,Arial,20\nStyle: Sign,Arial,40\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,A\nDialogue: 0,0:00:02.00,0:00:03.00,Sign,,0,0,0,,B\n";
+        let mut ass = buf.parse::<Ass>().unwrap();
+        ass.unify_style("Sign");
+        let styles = ass.styles().collect::<Vec<_>>();
+        assert_eq!(styles.len(), 1);
+        assert_eq!(styles[0].name, "Sign");
+        assert_eq!(styles[0].font_size, 40);
+        assert!(ass.events().all(|e| e.style == "Sign"));
+
+        ass.unify_style("Main");
+        let styles = ass.styles().collect::<Vec<_>>();
+        assert_eq!(styles.len(), 1);
+        assert_eq!(styles[0].name, "Main");
+        assert_eq!(styles[0].font_size, Style::program_default().font_size);
+        assert!(ass.events().all(|e| e.style == "Main"));
+    }
+
+    #[test]
+    fn test_timestamp_round_trip() {
+        let buf = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.50,00:00:02.5,Default,,0,0,0,,A\nDialogue: 0,0:00:03.500,0:00:04.00,Default,,0,0,0,,B\n";
+        let mut ass = buf.parse::<Ass>().unwrap();
+        let events = ass.events().collect::<Vec<_>>();
+        assert_eq!(events[0].start, Duration::from_millis(1500));
+        assert_eq!(events[0].end, Duration::from_millis(2500));
+        assert_eq!(events[1].start, Duration::from_millis(3500));
+        assert_eq!(
+            events[0].original_timestamps(),
+            Some(("0:00:01.50", "00:00:02.5"))
+        );
+        assert_eq!(Event::default().original_timestamps(), None);
+
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Dialogue: 0,0:00:01.50,00:00:02.5,Default,,0,0,0,,A\n"));
+        assert!(output.contains("Dialogue: 0,0:00:03.500,0:00:04.00,Default,,0,0,0,,B\n"));
+
+        ass.events_mut().nth(1).unwrap().shift_by(1.0);
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Dialogue: 0,0:00:01.50,00:00:02.5,Default,,0,0,0,,A\n"));
+        assert!(output.contains("Dialogue: 0,0:00:04.50,0:00:05.00,Default,,0,0,0,,B\n"));
+    }
+
+    #[test]
+    fn test_raw_lines() {
+        let buf = "[Script Info]\nTitle: Test\nNOTE\n\n[Fonts]\nfontname: font_0.ttf\nM)!\"#`M\n\n[Aegisub Extradata]\nDATA\n\n[Graphics]\nLOGO\nfilename: logo.png\nM)!\"#`M\nM)!\"#`M\n\nLOGO\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        let script_info = ass.sections[0].as_script_info().unwrap();
+        assert_eq!(script_info.lines[1], Line::Raw(String::from("NOTE")));
+        let fonts = ass.sections[1].as_generic().unwrap();
+        assert_eq!(fonts.lines[1], Line::Encoded(String::from("M)!\"#`M")));
+        let extradata = ass.sections[2].as_generic().unwrap();
+        assert_eq!(extradata.lines[0], Line::Raw(String::from("DATA")));
+        // Only lines after a file header are encoded data
+        let graphics = ass.sections[3].as_generic().unwrap();
+        assert_eq!(graphics.lines[0], Line::Raw(String::from("LOGO")));
+        assert!(graphics.lines[2].is_encoded());
+        assert!(graphics.lines[3].is_encoded());
+        assert_eq!(graphics.lines[5], Line::Raw(String::from("LOGO")));
+
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), buf);
+    }
+
+    #[test]
+    fn test_script_info_fields() {
+        let ass = "[Script Info]\nScriptType: v4.00+\nCollisions: Reverse\nTimer: 100.0000\nPlayDepth: 0\nWrapStyle: 2\nPlayResX: 640\nPlayResY: abc\nScaledBorderAndShadow: no\n"
+            .parse::<Ass>()
+            .unwrap();
+        let info = ass.sections[0].as_script_info().unwrap();
+        assert_eq!(info.get("Timer"), Some("100.0000"));
+        assert_eq!(info.get("Title"), None);
+        assert_eq!(info.collisions(), Some(Collisions::Reverse));
+        assert_eq!(info.timer(), Some(100.0));
+        assert_eq!(info.play_depth(), Some(0));
+        assert_eq!(info.wrap_style(), Some(WrapStyle::None));
+        assert_eq!(info.play_res_x(), Some(640));
+        assert_eq!(info.play_res_y(), None);
+        assert_eq!(info.scaled_border_and_shadow(), Some(false));
+
+        let info = ScriptInfo::default();
+        assert_eq!(info.wrap_style(), Some(WrapStyle::Smart));
+        assert_eq!(info.play_res_y(), Some(1080));
+        assert_eq!(info.scaled_border_and_shadow(), Some(true));
+        assert_eq!(info.collisions(), None);
+        assert_eq!(info.timer(), None);
+    }
+
+    #[test]
+    fn test_long_raw_lines() {
+        // Every character is in the range used by encoded data
+        let long = "ABCDEFGHIJ".repeat(12);
+        let buf = format!(
+            "[Script Info]\nTitle: Test\n{long}\n\n[Fonts]\nfontname: font_0.ttf\nM)!\"#`M\n{long}\n"
+        );
+        let ass = buf.parse::<Ass>().unwrap();
+        let script_info = ass.sections[0].as_script_info().unwrap();
+        assert_eq!(script_info.lines[1], Line::Raw(long.clone()));
+        let fonts = ass.sections[1].as_generic().unwrap();
+        assert!(fonts.lines[1].is_encoded());
+        assert_eq!(fonts.lines[2], Line::Raw(long.clone()));
+
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), buf);
+    }
+
+    #[test]
+    fn test_comments_in_sections() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\n; Main style\nStyle: Default,Arial,20\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n; Part A\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n;Part B\nDialogue: 0,0:00:02.00,0:00:03.00,Default,,0,0,0,,World\n; The end\n";
+        let mut ass = buf.parse::<Ass>().unwrap();
+        assert_eq!(ass.styles().count(), 1);
+        assert_eq!(ass.events().count(), 2);
+
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("; Main style\nStyle: Default,"));
+        assert!(output.contains("; Part A\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n;Part B\nDialogue: 0,0:00:02.00"));
+        assert!(output.contains("World\n; The end\n"));
+
+        // Comments stay where they were when the events around them are removed
+        ass.retain_events(|e| e.text != "Hello");
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("; Part A\n;Part B\nDialogue: 0,0:00:02.00"));
+
+        for section in &mut ass.sections {
+            section.remove_comments();
         }
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        assert!(!String::from_utf8(output).unwrap().contains(';'));
+    }
+
+    #[test]
+    fn test_comment_entries() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\n;Main\nStyle: Default,Arial,20\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n;TL note\n";
+        let ass = Ass::parse_with(buf, ParseOptions::strict()).unwrap();
+        let styles = ass.sections.iter().find_map(|s| s.as_styles()).unwrap();
+        assert_eq!(styles.entries[0], Entry::Comment(String::from("Main")));
+        assert_eq!(
+            styles.entries[1].item().map(|s| s.name.as_str()),
+            Some("Default")
+        );
+        assert_eq!(styles.styles().count(), 1);
+        let events = ass.sections.iter().find_map(|s| s.as_events()).unwrap();
+        assert_eq!(events.entries.len(), 2);
+        assert!(events.entries[1].is_comment());
+        assert_eq!(
+            events.events().map(|e| e.text.as_str()).collect::<Vec<_>>(),
+            ["Hello"]
+        );
+    }
+
+    #[test]
+    fn test_split_by_style() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\nStyle: JP,Arial,20\nStyle: EN,Arial,20\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,JP,,0,0,0,,こんにちは\nDialogue: 0,0:00:01.00,0:00:02.00,EN,,0,0,0,,Hello\nComment: 0,0:00:01.00,0:00:02.00,EN,,0,0,0,,TL note\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        let split = ass.split_by_style();
+        assert_eq!(split.len(), 2);
+
+        let en = &split["EN"];
+        let styles = en.sections.iter().find_map(|s| s.as_styles()).unwrap();
+        let names = styles.styles().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["EN"]);
+        assert_eq!(en.events().count(), 2);
+        assert!(en.events().all(|e| e.style == "EN"));
+
+        let jp = &split["JP"];
+        assert_eq!(jp.events().count(), 1);
+        assert_eq!(jp.sections[0].as_script_info().unwrap().title(), "Test");
+    }
+
+    #[test]
+    fn test_from_srt_alignment() {
+        let vtt = "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.000 line:10%\nTop\n\n2\n00:00:02.000 --> 00:00:03.000\nBottom\n";
+        let srt = crate::srt::save_to_string(&crate::vtt::load_from_string(vtt).unwrap());
+        let ass = Ass::from_srt(crate::srt::load_from_string(&srt).unwrap());
+        let texts = ass.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["{\\an8}Top", "Bottom"]);
+
+        assert_eq!(srt_to_ass("<i>{\\an7}Hi</i>"), "{\\an7}{\\i1}Hi{\\i0}");
+        assert_eq!(srt_to_ass("{\\a6}Top\n{\\an2}Line"), "{\\an8}Top\\NLine");
+        assert_eq!(
+            srt_to_ass("<font color=\"#ffff00\">Yellow</font>"),
+            "Yellow"
+        );
+        assert_eq!(srt_to_ass("{\\a4}Odd"), "Odd");
+    }
+
+    #[test]
+    fn test_clean_ass_text_line_breaks() {
+        assert_eq!(clean_ass_text(r"line1\N\Nline2"), "line1\nline2");
+        assert_eq!(clean_ass_text(r"\Nline1\N \Nline2\N"), "line1\nline2");
+        assert_eq!(clean_ass_text(r"{\an8}line1\Nline2"), "{\\an8}line1\nline2");
+    }
+
+    #[test]
+    fn test_ass_font_sizes() {
+        let convert = |text: &str| add_font_size(text, clean_ass_text(text));
+        assert_eq!(convert(r"{\fs30}Big"), r#"<font size="30">Big</font>"#);
+        assert_eq!(
+            convert(r"{\an8\fs20\b1\fs24.5}Top"),
+            r#"{\an8}<font size="24.5">Top</font>"#
+        );
+        assert_eq!(convert(r"Normal {\fs40}big"), "Normal big");
+        assert_eq!(convert(r"{\fsp5\fscx120}Wide"), "Wide");
+        assert_eq!(convert(r"{\fs30}"), "");
+    }
+
+    #[test]
+    fn test_events_only() {
+        let ass = Ass::from_srt(
+            crate::srt::load_from_string("1\n00:00:01,000 --> 00:00:02,000\nHello\n\n").unwrap(),
+        );
+        let mut output = Vec::new();
+        ass.events_only().save_to_writer(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\nFormat: "));
+        assert!(!output.contains("Style: "));
+
+        let parsed = output.parse::<Ass>().unwrap();
+        assert!(parsed.has_styles());
+        assert_eq!(parsed.styles().count(), 0);
+        let texts = parsed.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["Hello"]);
+    }
+
+    #[test]
+    fn test_events_table() {
+        let mut ass = Ass::from_srt(
+            crate::srt::load_from_string(
+                "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n01:00:03,000 --> 01:00:04,500\nBye\n\n",
+            )
+            .unwrap(),
+        );
+        ass.events_mut().nth(1).unwrap().name = String::from("Alice");
+        assert_eq!(
+            ass.events_table(),
+            "Kind      Layer  Start       End         Style    Name   Text\n\
+             Dialogue  0      0:00:01.00  0:00:02.00  Default         Hello\n\
+             Dialogue  0      1:00:03.00  1:00:04.50  Default  Alice  Bye\n"
+        );
+
+        // Wide characters take up two columns
+        ass.events_mut().nth(1).unwrap().name = String::from("字幕");
+        assert_eq!(
+            ass.events_table(),
+            "Kind      Layer  Start       End         Style    Name  Text\n\
+             Dialogue  0      0:00:01.00  0:00:02.00  Default        Hello\n\
+             Dialogue  0      1:00:03.00  1:00:04.50  Default  字幕  Bye\n"
+        );
+    }
+
+    #[test]
+    fn test_truncate_events() {
+        let dialogue = crate::srt::load_from_string(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n2\n00:00:03,000 --> 00:00:04,000\nTwo\n\n3\n00:00:05,000 --> 00:00:06,000\nThree\n\n",
+        )
+        .unwrap();
+        let mut ass = Ass::from_srt(dialogue);
+        ass.truncate_events(5);
+        assert_eq!(ass.events().count(), 3);
+        // Comment events don't count towards the limit
+        ass.events_mut().next().unwrap().kind = EventKind::Comment;
+        ass.truncate_events(2);
+        let texts = ass.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["One", "Two", "Three"]);
+        ass.truncate_events(1);
+        let texts = ass.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["One", "Two"]);
+        ass.truncate_events(0);
+        assert_eq!(ass.events().count(), 0);
+    }
+
+    #[test]
+    fn test_validate() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Alignment\nStyle: Default,2\nStyle: Broken,12\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+Dialogue: 0,0:00:01.00,0:00:03.00,Default,,0,0,0,,One\n\
+Dialogue: 0,0:00:02.00,0:00:04.00,Default,,0,0,0,,Two\n\
+Dialogue: 1,0:00:02.00,0:00:04.00,Default,,0,0,0,,{\\an10}Other layer\n\
+Dialogue: 0,0:00:06.00,0:00:05.00,Missing,,0,0,0,,Three\n\
+Dialogue: 0,0:00:08.00,0:00:08.00,Default,,0,0,0,,Four\n\
+Comment: 0,0:00:09.00,0:00:09.00,Default,,0,0,0,,Note\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        let issues = ass
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.kind, issue.event))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            issues,
+            vec![
+                (
+                    ValidationIssueKind::InvalidStyleAlignment {
+                        style: String::from("Broken"),
+                        alignment: 12
+                    },
+                    None
+                ),
+                (ValidationIssueKind::Overlap(0), Some(1)),
+                (
+                    ValidationIssueKind::InvalidAlignmentTag(String::from("\\an10")),
+                    Some(2)
+                ),
+                (
+                    ValidationIssueKind::UndefinedStyle(String::from("Missing")),
+                    Some(3)
+                ),
+                (ValidationIssueKind::InvertedTiming, Some(3)),
+                (ValidationIssueKind::ZeroDuration, Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_save_trailing_newline() {
+        let buf = "[Script Info]\nTitle: Test\n\n\n[Fonts]\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n\n\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[Script Info]\nTitle: Test\n\n[Fonts]\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n"
+        );
+    }
+
+    #[test]
+    fn test_compact_format() {
+        let buf = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer,Start,End,Style,Name,MarginL,MarginR,MarginV,Effect,Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n";
+        let ass = buf.parse::<Ass>().unwrap();
+        assert_eq!(ass.events().next().unwrap().text, "Hello");
+
+        let mut output = Vec::new();
+        let options = WriteOptions {
+            compact_format: true,
+            ..Default::default()
+        };
+        ass.save_to_writer_with(&mut output, &options).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), buf);
 
-        Ok(Self { sections })
+        let mut output = Vec::new();
+        let options = WriteOptions {
+            compact_format: true,
+            bom: true,
+            ..Default::default()
+        };
+        ass.save_to_writer_with(&mut output, &options).unwrap();
+        assert_eq!(output[..3], crate::UTF8_BOM);
+        assert_eq!(String::from_utf8(output[3..].to_vec()).unwrap(), buf);
+
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("Format: Layer, Start, End, Style,"));
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        assert!(close(Colour::WHITE.contrast_ratio(&Colour::BLACK), 21.0));
+        assert!(close(Colour::BLACK.contrast_ratio(&Colour::WHITE), 21.0));
+        assert!(close(Colour::RED.contrast_ratio(&Colour::RED), 1.0));
+        assert!(close(Colour::RED.contrast_ratio(&Colour::WHITE), 4.0));
+    }
+
+    #[test]
+    fn test_colour_parse() {
+        let blue = Colour::from_rgb(0, 0, 255);
+        assert_eq!(Colour::parse("#0000FF"), Some(blue));
+        assert_eq!(
+            Colour::parse("#0000FF80"),
+            Some(Colour::from_rgba(0, 0, 255, 0x80))
+        );
+        assert_eq!(Colour::parse("&H00FF0000"), Some(blue));
+        assert_eq!(Colour::parse("16711680"), Some(blue));
+        assert_eq!(Colour::parse("White"), Some(Colour::WHITE));
+        assert_eq!(Colour::parse("#FFF"), None);
+        assert_eq!(Colour::parse("Navy"), Some(Colour::from_rgb(0, 0, 128)));
+        assert_eq!(Colour::parse("orange"), None);
+        assert_eq!(Colour::from_ass("16711680"), None);
+    }
+
+    #[test]
+    fn test_overlay() {
+        let buf = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize\nStyle: Default,Arial,20\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n";
+        let mut primary = buf.parse::<Ass>().unwrap();
+        let overlay = buf.parse::<Ass>().unwrap();
+        let renamed = primary.overlay(overlay);
+        assert_eq!(
+            renamed,
+            vec![("Default".to_owned(), "Default-2".to_owned())]
+        );
+
+        let names = primary
+            .styles()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Default", "Default-2"]);
+        let styles = primary
+            .events()
+            .map(|e| e.style.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(styles, ["Default", "Default-2"]);
     }
 }
@@ -0,0 +1,1829 @@
+//! Cleaning up subtitles, the library side of `sub-tools cleanup`.
+//!
+//! The transforms are chosen with [`CleanupOptions`] and applied with
+//! [`cleanup_dialogue`] for .srt and .vtt dialogue or [`cleanup_ass`] for
+//! .ass files. Nothing is printed, what was changed is returned as a
+//! [`CleanupReport`] instead.
+//!
+//! ```
+//! use std::time::Duration;
+//! use sub_tools::cleanup::{cleanup_dialogue, CleanupOptions};
+//!
+//! let mut dialogue = sub_tools::srt::load_from_string(
+//!     "1\n00:00:01,000 --> 00:00:01,000\nHello   there\n",
+//! )
+//! .unwrap();
+//! let options = CleanupOptions {
+//!     collapse_spaces: true,
+//!     fix_zero_duration: Some(Duration::from_secs(1)),
+//!     ..Default::default()
+//! };
+//! let report = cleanup_dialogue(&mut dialogue, &options);
+//! assert_eq!(dialogue[0].text, "Hello there");
+//! assert_eq!(dialogue[0].end, Duration::from_secs(2));
+//! assert_eq!(report.zero_duration_fixed, 1);
+//! ```
+
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    sync::OnceLock,
+    time::Duration,
+};
+
+use crate::{
+    ass::{Ass, Entry, RenameStyleError, TextSpan},
+    japanese::{detect_script, Script},
+    srt,
+    utils::{windows_mut, LendingIterator},
+};
+
+/// The width to convert Japanese punctuation to, see [`CleanupOptions::punct`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PunctuationWidth {
+    /// `、。！？` become `,.!?`
+    Ascii,
+    /// `,.!?` become `、。！？` when they follow Japanese text
+    Fullwidth,
+}
+
+impl PunctuationWidth {
+    fn convert(self, text: &str) -> String {
+        match self {
+            PunctuationWidth::Ascii => crate::japanese::punctuation_to_ascii(text),
+            PunctuationWidth::Fullwidth => crate::japanese::punctuation_to_fullwidth(text),
+        }
+    }
+
+    fn convert_event(self, event: &mut crate::ass::Event) {
+        let spans = event
+            .spans()
+            .into_iter()
+            .map(|span| match span {
+                TextSpan::Text(text) => TextSpan::Text(self.convert(&text)),
+                TextSpan::Override(_) => span,
+            })
+            .collect::<Vec<_>>();
+        event.set_spans(&spans);
+    }
+}
+
+/// The case to convert the text to, see [`CleanupOptions::case`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextCase {
+    /// all lowercase
+    Lower,
+    /// ALL UPPERCASE
+    Upper,
+    /// Every Word Capitalised
+    Title,
+    /// Only the first letter of every sentence capitalised
+    Sentence,
+}
+
+/// How the lines of a split cue are timed, see [`CleanupOptions::split_lines`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SplitTiming {
+    /// Every line is shown for the whole cue
+    Share,
+    /// The cue's duration is divided evenly between the lines
+    Divide,
+}
+
+impl SplitTiming {
+    /// Returns the timing of each of the `count` lines a cue is split into.
+    fn split(self, start: Duration, end: Duration, count: usize) -> Vec<(Duration, Duration)> {
+        match self {
+            SplitTiming::Share => vec![(start, end); count],
+            SplitTiming::Divide => {
+                let length = end.saturating_sub(start);
+                let count = count as u32;
+                (0..count)
+                    .map(|i| (start + length * i / count, start + length * (i + 1) / count))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// A group of override tags that can be removed, see [`CleanupOptions::strip_tags`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TagCategory {
+    /// Every category below
+    All,
+    /// `\p` drawings, along with the drawing commands
+    Drawing,
+    /// `\k`, `\K`, `\kf`, `\ko` and `\kt`
+    Karaoke,
+    /// `\t` animated transforms
+    Transform,
+    /// `\move` and `\pos`
+    Movement,
+}
+
+impl TagCategory {
+    /// Returns the categories that are selected, with `All` expanded.
+    fn expand(categories: &[TagCategory]) -> Vec<TagCategory> {
+        [
+            Self::Drawing,
+            Self::Karaoke,
+            Self::Transform,
+            Self::Movement,
+        ]
+        .into_iter()
+        .filter(|c| categories.contains(c) || categories.contains(&Self::All))
+        .collect()
+    }
+
+    /// Returns the category of a single override tag, e.g. `\kf20`.
+    fn of(tag: &str) -> Option<Self> {
+        let name = tag.strip_prefix('\\')?;
+        if name.starts_with("move(") || name.starts_with("pos(") {
+            Some(Self::Movement)
+        } else if name.starts_with("t(") {
+            Some(Self::Transform)
+        } else if name.starts_with(['k', 'K']) {
+            Some(Self::Karaoke)
+        } else if name
+            .strip_prefix('p')
+            .is_some_and(|n| n.parse::<u32>().is_ok())
+        {
+            Some(Self::Drawing)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the name used on the command line, e.g. `karaoke`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TagCategory::All => "all",
+            TagCategory::Drawing => "drawing",
+            TagCategory::Karaoke => "karaoke",
+            TagCategory::Transform => "transform",
+            TagCategory::Movement => "movement",
+        }
+    }
+}
+
+/// The range of time that dialogue has to start in to be worked on.
+///
+/// Either end can be left out, in which case the range is open on that side.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DurationRange {
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+}
+
+impl DurationRange {
+    /// Returns `true` if the duration is inside the range, ends included.
+    pub fn contains(&self, duration: &Duration) -> bool {
+        match (&self.start, &self.end) {
+            (Some(start), Some(end)) => duration >= start && duration <= end,
+            (Some(start), None) => duration >= start,
+            (None, Some(end)) => duration <= end,
+            (None, None) => true,
+        }
+    }
+}
+
+/// The transforms done by [`cleanup_dialogue`] and [`cleanup_ass`].
+///
+/// Every transform is off by default. Unless noted otherwise, only the
+/// dialogue that starts inside [`range`](Self::range) is changed.
+#[derive(Debug, Default, Clone)]
+pub struct CleanupOptions {
+    /// The range of the dialogue to work on.
+    pub range: DurationRange,
+    /// Sorts the dialogue before anything else so the result doesn't depend
+    /// on the order of the input, see [`Ass::sort_events`].
+    pub stable: bool,
+    /// Removes the dialogue.
+    pub remove: bool,
+    /// Only keeps the first N lines, regardless of the range.
+    pub limit: Option<usize>,
+    /// Removes every comment event (.ass only).
+    pub comments: bool,
+    /// Removes the styles that no event uses once everything else is done,
+    /// see [`Ass::remove_unused_styles`] (.ass only).
+    pub unused_styles: bool,
+    /// Removes the events using any of these styles (.ass only).
+    pub dialogue_from: Vec<String>,
+    /// Fixes common issues with Japanese text, see [`crate::japanese::fix_broken_text`].
+    pub fix_japanese: bool,
+    /// Enables [`fix_japanese`](Self::fix_japanese) only if most of the dialogue is in Japanese.
+    pub auto_fix_japanese: bool,
+    /// Merges lines that have the same start and end time as the line before them.
+    pub merge_simultaneous: bool,
+    /// Joins lines that don't end a sentence with the next line if it starts
+    /// within the given gap.
+    pub join_sentences: Option<Duration>,
+    /// Splits multi-line dialogue into a line per line of text. Events
+    /// positioned with `\pos` or `\move` aren't split.
+    pub split_lines: Option<SplitTiming>,
+    /// Removes markup tags such as `<i>` (.srt and .vtt only).
+    pub strip_html: bool,
+    /// Replaces every match of the regex with the replacement, in order.
+    pub replacements: Vec<(Regex, String)>,
+    /// Lets the replacements change the text inside override tags (.ass only).
+    pub replace_in_tags: bool,
+    /// Converts the text to this case, leaving tags and escapes alone.
+    pub case: Option<TextCase>,
+    /// Converts the punctuation of Japanese text to this width.
+    pub punct: Option<PunctuationWidth>,
+    /// Removes the override tags of these categories (.ass only).
+    pub strip_tags: Vec<TagCategory>,
+    /// Collapses runs of spaces into a single space.
+    pub collapse_spaces: bool,
+    /// Removes leading and trailing whitespace from every line of text.
+    pub trim: bool,
+    /// Joins any lines of text past this many onto the last allowed line.
+    pub max_lines: Option<usize>,
+    /// Starts every line earlier by this much, without overlapping the line before.
+    pub pad_start: Option<Duration>,
+    /// Ends every line later by this much, without overlapping the line after.
+    pub pad_end: Option<Duration>,
+    /// Extends lines to the start of the next line if the gap is at most this long.
+    pub merge_gap: Option<Duration>,
+    /// Ends lines earlier so there's at least this long before the next line.
+    pub min_gap: Option<Duration>,
+    /// Shows lines with no duration for this long, see [`srt::Dialogue::duration`].
+    pub fix_zero_duration: Option<Duration>,
+    /// Extends lines shorter than this, without overlapping the next line in
+    /// .srt and .vtt files.
+    pub min_duration: Option<Duration>,
+    /// Shortens lines longer than this.
+    pub max_duration: Option<Duration>,
+    /// Snaps the start and end of every line to the closest frame at this frame rate.
+    pub snap_fps: Option<f64>,
+    /// Puts events on this layer, see [`Ass::set_layer`] (.ass only).
+    pub layer: Option<u8>,
+    /// Only puts the events using this style on [`layer`](Self::layer).
+    pub layer_style: Option<String>,
+    /// Removes duplicate styles, see [`Ass::dedup_styles`] (.ass only).
+    pub dedup_styles: bool,
+    /// Renames each style from the first name to the second (.ass only).
+    pub rename_styles: Vec<(String, String)>,
+    /// Makes every event use this style, see [`Ass::unify_style`] (.ass only).
+    pub unify_style: Option<String>,
+    /// Keeps the lines that have no visible text, which are removed otherwise.
+    pub keep_blank_cues: bool,
+}
+
+impl CleanupOptions {
+    /// Returns the start and end padding if either was given.
+    fn padding(&self) -> Option<(Duration, Duration)> {
+        if self.pad_start.is_none() && self.pad_end.is_none() {
+            return None;
+        }
+        Some((
+            self.pad_start.unwrap_or_default(),
+            self.pad_end.unwrap_or_default(),
+        ))
+    }
+
+    /// Applies the duration limits to a single dialogue line.
+    fn fix_timing(&self, start: Duration, end: &mut Duration, next_start: Option<Duration>) {
+        if let Some(length) = self
+            .fix_zero_duration
+            .filter(|_| end.saturating_sub(start).is_zero())
+        {
+            let target = start.saturating_add(length);
+            *end = match next_start {
+                Some(next) => target.min(next.max(*end)),
+                None => target,
+            };
+        }
+        if let Some(min) = self.min_duration {
+            if end.saturating_sub(start) < min {
+                let target = start.saturating_add(min);
+                *end = match next_start {
+                    Some(next) => target.min(next.max(*end)),
+                    None => target,
+                };
+            }
+        }
+        if let Some(max) = self.max_duration {
+            if end.saturating_sub(start) > max {
+                *end = start.saturating_add(max);
+            }
+        }
+    }
+
+    /// Snaps a single dialogue line to [`CleanupOptions::snap_fps`].
+    ///
+    /// `centiseconds` is for formats that can't store milliseconds, see [`snap_to_frame`].
+    fn snap_timing(&self, start: &mut Duration, end: &mut Duration, centiseconds: bool) {
+        if let Some(fps) = self.snap_fps {
+            *start = snap_to_frame(*start, fps, centiseconds);
+            *end = snap_to_frame(*end, fps, centiseconds);
+        }
+    }
+}
+
+/// What [`cleanup_dialogue`] or [`cleanup_ass`] changed.
+///
+/// The [`Display`] implementation summarises the changes in a sentence,
+/// e.g. `Removed 12 comments, renumbered 450 entries.`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// Lines removed with [`CleanupOptions::remove`] or [`CleanupOptions::dialogue_from`].
+    pub removed: usize,
+    pub comments: usize,
+    pub japanese: usize,
+    pub merged: usize,
+    pub joined: usize,
+    pub split: usize,
+    /// Lines whose text was changed by the text options.
+    pub edited: usize,
+    /// Lines whose timing was changed by the timing options.
+    pub retimed: usize,
+    pub blank: usize,
+    pub renumbered: usize,
+    /// Lines that were padded less than asked to avoid overlapping.
+    pub clamped: usize,
+    pub gaps_closed: usize,
+    /// Lines that were shortened to leave [`CleanupOptions::min_gap`].
+    pub gaps_opened: usize,
+    /// Lines with no duration, i.e. that ended when or before they started.
+    pub zero_duration: usize,
+    /// How many of the [`zero_duration`](Self::zero_duration) lines no longer do.
+    pub zero_duration_fixed: usize,
+    /// The override tags removed by [`CleanupOptions::strip_tags`], for every
+    /// category that was stripped.
+    pub tags_removed: BTreeMap<TagCategory, usize>,
+    /// The layer events were put on and how many of them changed.
+    pub layer: Option<(u8, usize)>,
+    /// The styles removed by [`CleanupOptions::dedup_styles`] along with the
+    /// style they were merged into, see [`Ass::dedup_styles`].
+    pub merged_styles: Vec<(String, String)>,
+    /// The styles removed by [`CleanupOptions::unused_styles`].
+    pub unused_styles: Vec<String>,
+    /// Events that still reset to another style after [`CleanupOptions::unify_style`].
+    pub style_resets: usize,
+}
+
+/// The start, end and text of every line before they're changed.
+type Snapshot = Vec<(Duration, Duration, String)>;
+
+impl CleanupReport {
+    /// Counts the lines that were edited or retimed since the snapshot.
+    ///
+    /// The lines must be in the same order as when the snapshot was taken.
+    fn count_changes<'a, I>(&mut self, before: &Snapshot, after: I)
+    where
+        I: Iterator<Item = (Duration, Duration, &'a str)>,
+    {
+        for ((start, end, text), (new_start, new_end, new_text)) in before.iter().zip(after) {
+            self.edited += usize::from(text != new_text);
+            self.retimed += usize::from((*start, *end) != (new_start, new_end));
+        }
+    }
+}
+
+impl Display for CleanupReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let actions = [
+            (self.removed, "removed", "line", "lines"),
+            (self.comments, "removed", "comment", "comments"),
+            (self.japanese, "fixed", "Japanese line", "Japanese lines"),
+            (
+                self.merged,
+                "merged",
+                "simultaneous line",
+                "simultaneous lines",
+            ),
+            (self.joined, "joined", "broken sentence", "broken sentences"),
+            (self.split, "split", "multi-line cue", "multi-line cues"),
+            (self.edited, "edited the text of", "line", "lines"),
+            (self.retimed, "retimed", "line", "lines"),
+            (self.blank, "removed", "blank line", "blank lines"),
+            (
+                self.merged_styles.len(),
+                "merged",
+                "duplicate style",
+                "duplicate styles",
+            ),
+            (
+                self.unused_styles.len(),
+                "pruned",
+                "unused style",
+                "unused styles",
+            ),
+            (self.renumbered, "renumbered", "entry", "entries"),
+        ];
+        let summary = actions
+            .iter()
+            .filter(|(count, ..)| *count > 0)
+            .map(|(count, verb, singular, plural)| {
+                let noun = if *count == 1 { singular } else { plural };
+                format!("{verb} {count} {noun}")
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut chars = summary.chars();
+        match chars.next() {
+            Some(first) => write!(f, "{}{}.", first.to_ascii_uppercase(), chars.as_str()),
+            None => f.write_str("Nothing was changed."),
+        }
+    }
+}
+
+/// Cleans up .srt or .vtt dialogue.
+///
+/// The dialogue is renumbered afterwards. Timing options that respect the
+/// neighbouring lines assume the dialogue is sorted by start, see
+/// [`CleanupOptions::stable`].
+pub fn cleanup_dialogue(
+    dialogue: &mut Vec<srt::Dialogue>,
+    options: &CleanupOptions,
+) -> CleanupReport {
+    let range = &options.range;
+    let mut report = CleanupReport::default();
+    if options.stable {
+        dialogue.sort_by(|a, b| (a.start, a.end, &a.text).cmp(&(b.start, b.end, &b.text)));
+    }
+    if options.remove {
+        let len = dialogue.len();
+        dialogue.retain(|d| !range.contains(&d.start));
+        report.removed += len - dialogue.len();
+    }
+    if let Some(limit) = options.limit {
+        dialogue.truncate(limit);
+    }
+    let fix_japanese = options.fix_japanese
+        || (options.auto_fix_japanese
+            && detect_script(dialogue.iter().map(|d| d.text.as_str())) == Some(Script::Japanese));
+    if options.merge_simultaneous {
+        let len = dialogue.len();
+        merge_simultaneous_dialogue(dialogue);
+        report.merged += len - dialogue.len();
+    }
+    if let Some(max_gap) = options.join_sentences {
+        let len = dialogue.len();
+        join_dialogue_sentences(dialogue, max_gap, range);
+        report.joined += len - dialogue.len();
+    }
+    if let Some(timing) = options.split_lines {
+        report.split += split_dialogue_lines(dialogue, timing, range);
+    }
+    if fix_japanese {
+        report.japanese += dialogue
+            .iter_mut()
+            .filter(|d| range.contains(&d.start))
+            .map(|d| usize::from(fix_japanese_text(&mut d.text)))
+            .sum::<usize>();
+    }
+
+    let before: Snapshot = dialogue
+        .iter()
+        .map(|d| (d.start, d.end, d.text.clone()))
+        .collect();
+    for d in dialogue.iter_mut().filter(|d| range.contains(&d.start)) {
+        if options.strip_html {
+            d.text = html_tag_regex().replace_all(&d.text, "").into_owned();
+        }
+        if !options.replacements.is_empty() {
+            d.text = replace_text(&options.replacements, &d.text);
+        }
+        if let Some(case) = options.case {
+            d.text = convert_case(&d.text, case);
+        }
+        if let Some(width) = options.punct {
+            d.text = width.convert(&d.text);
+        }
+        if options.collapse_spaces {
+            d.text = collapse_spaces(&d.text);
+        }
+        if options.trim {
+            d.text = trim_lines(&d.text);
+        }
+        if let Some(text) = options
+            .max_lines
+            .and_then(|max| limit_lines(&d.text, "\n", max))
+        {
+            d.text = text;
+        }
+    }
+    if let Some((lead_in, lead_out)) = options.padding() {
+        report.clamped += retime_dialogue(dialogue, |timings| {
+            pad_timings(timings, lead_in, lead_out, range)
+        });
+    }
+    if let Some(max_gap) = options.merge_gap {
+        report.gaps_closed +=
+            retime_dialogue(dialogue, |timings| close_gaps(timings, max_gap, range));
+    }
+    let next_starts = dialogue
+        .iter()
+        .skip(1)
+        .map(|d| Some(d.start))
+        .chain([None])
+        .collect::<Vec<_>>();
+    for (d, next_start) in dialogue.iter_mut().zip(next_starts) {
+        if range.contains(&d.start) {
+            let was_zero = d.duration().is_zero();
+            options.fix_timing(d.start, &mut d.end, next_start);
+            report.zero_duration += usize::from(was_zero);
+            report.zero_duration_fixed += usize::from(was_zero && !d.duration().is_zero());
+        }
+    }
+    if let Some(min_gap) = options.min_gap {
+        report.gaps_opened +=
+            retime_dialogue(dialogue, |timings| open_gaps(timings, min_gap, range));
+    }
+    // Snapping comes last so the ends that were moved above still land on a frame
+    for d in dialogue.iter_mut().filter(|d| range.contains(&d.start)) {
+        options.snap_timing(&mut d.start, &mut d.end, false);
+    }
+    report.count_changes(
+        &before,
+        dialogue.iter().map(|d| (d.start, d.end, d.text.as_str())),
+    );
+
+    if !options.keep_blank_cues {
+        let len = dialogue.len();
+        dialogue.retain(|d| !d.is_blank());
+        report.blank += len - dialogue.len();
+    }
+    for (index, d) in dialogue.iter_mut().enumerate() {
+        let position = (index + 1) as u32;
+        report.renumbered += usize::from(d.position != position);
+        d.position = position;
+    }
+    report
+}
+
+/// Cleans up the events and styles of an .ass file.
+///
+/// Timing options that respect the neighbouring lines only look at the
+/// dialogue events on the same layer and style, since events on different
+/// ones can overlap on purpose.
+///
+/// # Errors
+///
+/// Returns an error if one of [`CleanupOptions::rename_styles`] can't be
+/// renamed. The file is left partially cleaned up in that case.
+pub fn cleanup_ass(
+    subs: &mut Ass,
+    options: &CleanupOptions,
+) -> Result<CleanupReport, RenameStyleError> {
+    let range = &options.range;
+    let mut report = CleanupReport::default();
+    if options.stable {
+        subs.sort_events();
+    }
+    let fix_japanese = options.fix_japanese
+        || (options.auto_fix_japanese && {
+            let texts = subs
+                .events()
+                .filter(|e| e.kind.is_dialogue())
+                .map(|e| e.plain_text())
+                .collect::<Vec<_>>();
+            detect_script(texts.iter().map(String::as_str)) == Some(Script::Japanese)
+        });
+
+    // This removes *all* comments from the file
+    if options.comments {
+        let len = subs.events().count();
+        for section in &mut subs.sections {
+            section.remove_comments();
+        }
+        report.comments += len - subs.events().count();
+    }
+
+    if options.remove {
+        let len = subs.events().count();
+        subs.retain_events(|e| !range.contains(&e.start));
+        report.removed += len - subs.events().count();
+    }
+    if let Some(limit) = options.limit {
+        subs.truncate_events(limit);
+    }
+
+    if let Some(section) = subs.sections.iter_mut().find_map(|s| s.as_events_mut()) {
+        let removed_styles = options
+            .dialogue_from
+            .iter()
+            .map(String::as_str)
+            .collect::<HashSet<_>>();
+        // Events are only marked here and removed at the end so the passes line up.
+        // Comments in between are skipped so they stay where they are.
+        let mut events = section.events_mut().collect::<Vec<_>>();
+        let mut removed = vec![false; events.len()];
+        for (event, removed) in events.iter_mut().zip(&mut removed) {
+            if removed_styles.contains(event.style.as_str()) {
+                *removed = true;
+                report.removed += 1;
+            }
+
+            if fix_japanese && event.kind.is_dialogue() {
+                report.japanese += usize::from(fix_japanese_text(&mut event.text));
+            }
+        }
+
+        if options.merge_simultaneous {
+            // Every event of a group is merged into the first one
+            let mut first: Option<usize> = None;
+            for (index, is_removed) in removed.iter_mut().enumerate() {
+                if *is_removed {
+                    continue;
+                }
+                match first {
+                    Some(first)
+                        if events[first].start == events[index].start
+                            && events[first].end == events[index].end =>
+                    {
+                        let text = std::mem::take(&mut events[index].text);
+                        events[first].text.push_str("\\N");
+                        events[first].text.push_str(&text);
+                        *is_removed = true;
+                        report.merged += 1;
+                    }
+                    _ => first = Some(index),
+                }
+            }
+        }
+
+        if let Some(max_gap) = options.join_sentences {
+            let mut windows = windows_mut(&mut events);
+            let mut index = 0;
+            while let Some([left, right]) = windows.next() {
+                index += 1;
+                // Join forwards so that a sentence can span more than two lines
+                if !removed[index - 1]
+                    && !removed[index]
+                    && left.kind.is_dialogue()
+                    && right.kind.is_dialogue()
+                    && left.style == right.style
+                    && range.contains(&left.start)
+                    && !ends_sentence(&left.plain_text())
+                    && right.start.saturating_sub(left.end) <= max_gap
+                {
+                    right.text.insert_str(0, "\\N");
+                    right.text.insert_str(0, &left.text);
+                    right.start = left.start;
+                    removed[index - 1] = true;
+                    report.joined += 1;
+                }
+            }
+        }
+
+        // retain visits every event once in order so this lines up with the marks
+        let mut removed = removed.into_iter();
+        section.retain(|_| !removed.next().unwrap_or_default());
+    }
+
+    if let Some(timing) = options.split_lines {
+        report.split += split_event_lines(subs, timing, range);
+    }
+    let before: Snapshot = subs
+        .events()
+        .map(|e| (e.start, e.end, e.text.clone()))
+        .collect();
+    if let Some((lead_in, lead_out)) = options.padding() {
+        report.clamped += retime_tracks(subs, |timings| {
+            pad_timings(timings, lead_in, lead_out, range)
+        });
+    }
+    if let Some(max_gap) = options.merge_gap {
+        report.gaps_closed += retime_tracks(subs, |timings| close_gaps(timings, max_gap, range));
+    }
+    if !options.replacements.is_empty() {
+        subs.events_mut()
+            .filter(|e| e.kind.is_dialogue() && range.contains(&e.start))
+            .for_each(|e| replace_event_text(&options.replacements, e, options.replace_in_tags));
+    }
+    if let Some(case) = options.case {
+        subs.events_mut()
+            .filter(|e| e.kind.is_dialogue() && range.contains(&e.start))
+            .for_each(|e| e.text = convert_case(&e.text, case));
+    }
+    if let Some(width) = options.punct {
+        subs.events_mut()
+            .filter(|e| e.kind.is_dialogue() && range.contains(&e.start))
+            .for_each(|e| width.convert_event(e));
+    }
+    if !options.strip_tags.is_empty() {
+        let categories = TagCategory::expand(&options.strip_tags);
+        report.tags_removed = categories.iter().map(|&c| (c, 0)).collect();
+        subs.events_mut()
+            .filter(|e| e.kind.is_dialogue() && range.contains(&e.start))
+            .for_each(|e| e.text = strip_tags(&e.text, &categories, &mut report.tags_removed));
+    }
+    for event in subs
+        .events_mut()
+        .filter(|e| e.kind.is_dialogue() && range.contains(&e.start))
+    {
+        if options.collapse_spaces {
+            event.text = collapse_ass_spaces(&event.text);
+        }
+        if options.trim {
+            event.text = trim_ass_text(&event.text);
+        }
+        if let Some(text) = options
+            .max_lines
+            .and_then(|max| limit_lines(&event.text, "\\N", max))
+        {
+            event.text = text;
+        }
+        // Events can overlap on purpose so there's no next line to respect
+        let was_zero = event.duration().is_zero();
+        options.fix_timing(event.start, &mut event.end, None);
+        report.zero_duration += usize::from(was_zero);
+        report.zero_duration_fixed += usize::from(was_zero && !event.duration().is_zero());
+    }
+    if let Some(min_gap) = options.min_gap {
+        report.gaps_opened += retime_tracks(subs, |timings| open_gaps(timings, min_gap, range));
+    }
+    // Snapping comes last so the ends that were moved above still land on a frame
+    for event in subs
+        .events_mut()
+        .filter(|e| e.kind.is_dialogue() && range.contains(&e.start))
+    {
+        options.snap_timing(&mut event.start, &mut event.end, true);
+    }
+    report.count_changes(
+        &before,
+        subs.events().map(|e| (e.start, e.end, e.text.as_str())),
+    );
+
+    if let Some(layer) = options.layer {
+        let changed = subs.set_layer(layer, options.layer_style.as_deref());
+        report.layer = Some((layer, changed));
+    }
+    if options.dedup_styles {
+        report.merged_styles = subs.dedup_styles();
+    }
+    for (old, new) in &options.rename_styles {
+        subs.rename_style(old, new)?;
+    }
+    if let Some(name) = &options.unify_style {
+        subs.unify_style(name);
+        report.style_resets = subs
+            .events()
+            .filter(|e| {
+                e.spans().iter().flat_map(TextSpan::tags).any(|tag| {
+                    tag.strip_prefix("\\r")
+                        .is_some_and(|style| !style.is_empty() && style != name)
+                })
+            })
+            .count();
+    }
+
+    if !options.keep_blank_cues {
+        let len = subs.events().count();
+        subs.retain_events(|e| !e.kind.is_dialogue() || !e.is_blank());
+        report.blank += len - subs.events().count();
+    }
+    if options.unused_styles {
+        report.unused_styles = subs.remove_unused_styles();
+    }
+    Ok(report)
+}
+
+/// Applies the replacements to the text in order.
+fn replace_text(replacers: &[(Regex, String)], text: &str) -> String {
+    let mut result = text.to_owned();
+    for (regex, replacement) in replacers {
+        if let std::borrow::Cow::Owned(replaced) = regex.replace_all(&result, replacement.as_str())
+        {
+            result = replaced;
+        }
+    }
+    result
+}
+
+/// Applies the replacements to the event's text, leaving the override tags
+/// untouched unless `in_tags` is true.
+fn replace_event_text(replacers: &[(Regex, String)], event: &mut crate::ass::Event, in_tags: bool) {
+    if in_tags {
+        event.text = replace_text(replacers, &event.text);
+    } else {
+        let spans = event
+            .spans()
+            .into_iter()
+            .map(|span| match span {
+                TextSpan::Text(text) => TextSpan::Text(replace_text(replacers, &text)),
+                TextSpan::Override(_) => span,
+            })
+            .collect::<Vec<_>>();
+        event.set_spans(&spans);
+    }
+}
+
+/// Matches the .srt and .vtt markup tags, e.g. `<i>`, `<font color="red">`,
+/// `<c.yellow>` or `<v Bob>`.
+///
+/// Only known tag names with the attributes they take are matched so that
+/// dialogue such as `<b and c>` is left alone.
+fn html_tag_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(concat!(
+            r#"(?i)</(?:b|i|u|s|c|v|lang|ruby|rt|font)\s*>"#,
+            r#"|<(?:(?:b|i|u|s|c|ruby|rt)(?:\.[\w-]+)*"#,
+            r#"|font(?:\s+[\w-]+\s*=\s*(?:"[^"]*"|'[^']*'|[^\s<>"']+))*"#,
+            r#"|(?:v|lang)(?:\.[\w-]+)*\s[^<>]+"#,
+            r#"|br\s*/?|\d{2}:\d{2}(?::\d{2})?\.\d{3})\s*>"#,
+        ))
+        .unwrap()
+    })
+}
+
+fn space_run_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r#" {2,}"#).unwrap())
+}
+
+/// Removes the override tags of the given (expanded) categories from .ass event text.
+///
+/// Drawing commands are removed along with the `\p` tags that start them
+/// since they would be shown as text otherwise. Override blocks that end up
+/// empty are removed. The removed tags are counted by category.
+fn strip_tags(
+    text: &str,
+    categories: &[TagCategory],
+    removed: &mut BTreeMap<TagCategory, usize>,
+) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut drawing = false;
+    for span in TextSpan::parse(text) {
+        let tags = span.tags();
+        let TextSpan::Override(_) = span else {
+            if !(drawing && categories.contains(&TagCategory::Drawing)) {
+                output.push_str(&span.to_string());
+            }
+            continue;
+        };
+        let mut block = String::new();
+        for tag in &tags {
+            let category = TagCategory::of(tag);
+            if category == Some(TagCategory::Drawing) {
+                drawing = tag[2..].parse::<u32>().is_ok_and(|scale| scale > 0);
+            }
+            match category.filter(|c| categories.contains(c)) {
+                Some(category) => *removed.entry(category).or_default() += 1,
+                None => block.push_str(tag),
+            }
+        }
+        if !block.is_empty() || tags.is_empty() {
+            output.push('{');
+            output.push_str(&block);
+            output.push('}');
+        }
+    }
+    output
+}
+
+/// Rounds the duration to the closest frame boundary at the given frame rate.
+///
+/// With `centiseconds` the boundary is rounded up to the next centisecond
+/// instead, since .ass timestamps would otherwise cut it off and land on
+/// the frame before.
+fn snap_to_frame(d: Duration, fps: f64, centiseconds: bool) -> Duration {
+    let frame = (d.as_secs_f64() * fps).round();
+    if centiseconds {
+        // The epsilon keeps boundaries that are already whole from being pushed back
+        Duration::from_millis((frame / fps * 100.0 - 1e-9).ceil() as u64 * 10)
+    } else {
+        Duration::from_millis((frame / fps * 1000.0).round() as u64)
+    }
+}
+
+/// Moves the start of each timing earlier and the end later by the given pads.
+///
+/// The timings must be sorted by start. Pads are clamped so a timing doesn't
+/// overlap with its neighbours, and only timings that start inside the range
+/// are padded. Returns how many timings had a pad clamped.
+fn pad_timings(
+    timings: &mut [(Duration, Duration)],
+    lead_in: Duration,
+    lead_out: Duration,
+    range: &DurationRange,
+) -> usize {
+    let mut clamped = 0;
+    for index in 0..timings.len() {
+        let (start, end) = timings[index];
+        if !range.contains(&start) {
+            continue;
+        }
+        let mut was_clamped = false;
+        let mut new_start = start.saturating_sub(lead_in);
+        // The previous timing has already been padded at this point
+        if let Some(&(_, previous_end)) = index.checked_sub(1).map(|i| &timings[i]) {
+            let floor = previous_end.min(start);
+            if new_start < floor {
+                new_start = floor;
+                was_clamped = true;
+            }
+        }
+        let mut new_end = end.saturating_add(lead_out);
+        if let Some(&(next_start, _)) = timings.get(index + 1) {
+            let ceiling = next_start.max(end);
+            if new_end > ceiling {
+                new_end = ceiling;
+                was_clamped = true;
+            }
+        }
+        timings[index] = (new_start, new_end);
+        clamped += usize::from(was_clamped);
+    }
+    clamped
+}
+
+/// Extends the end of each timing to the start of the next one if the gap
+/// between them is at most the given length.
+///
+/// The timings must be sorted by start. Only timings that start inside the
+/// range are extended. Returns how many gaps were closed.
+fn close_gaps(
+    timings: &mut [(Duration, Duration)],
+    max_gap: Duration,
+    range: &DurationRange,
+) -> usize {
+    let mut closed = 0;
+    for index in 1..timings.len() {
+        let next_start = timings[index].0;
+        let (start, end) = &mut timings[index - 1];
+        if range.contains(start) && *end < next_start && next_start - *end <= max_gap {
+            *end = next_start;
+            closed += 1;
+        }
+    }
+    closed
+}
+
+/// Moves the end of each timing earlier so there is at least the given gap
+/// before the next one starts.
+///
+/// The timings must be sorted by start. Only timings that start inside the
+/// range are trimmed, and ends are never moved to or before the start, so
+/// timings that are too short to leave the gap are kept as is. Timings that
+/// overlap the next one are left to the overlap fixing. Returns how many
+/// timings were trimmed.
+fn open_gaps(
+    timings: &mut [(Duration, Duration)],
+    min_gap: Duration,
+    range: &DurationRange,
+) -> usize {
+    let mut trimmed = 0;
+    for index in 1..timings.len() {
+        let next_start = timings[index].0;
+        let (start, end) = &mut timings[index - 1];
+        if !range.contains(start) || *end > next_start || next_start - *end >= min_gap {
+            continue;
+        }
+        let new_end = next_start.saturating_sub(min_gap);
+        if new_end > *start {
+            *end = new_end;
+            trimmed += 1;
+        }
+    }
+    trimmed
+}
+
+/// Lets the given function change the timing of the dialogue.
+///
+/// Returns whatever the function returns.
+fn retime_dialogue<F>(dialogue: &mut [srt::Dialogue], f: F) -> usize
+where
+    F: FnOnce(&mut [(Duration, Duration)]) -> usize,
+{
+    let mut timings = dialogue
+        .iter()
+        .map(|d| (d.start, d.end))
+        .collect::<Vec<_>>();
+    let result = f(&mut timings);
+    for (d, (start, end)) in dialogue.iter_mut().zip(timings) {
+        d.start = start;
+        d.end = end;
+    }
+    result
+}
+
+/// Lets the given function change the timing of each track of dialogue events.
+///
+/// A track is every dialogue event on the same layer and style, sorted by
+/// start, since events on different tracks can overlap on purpose. Returns
+/// the sum of what the function returns.
+fn retime_tracks<F>(subs: &mut Ass, mut f: F) -> usize
+where
+    F: FnMut(&mut [(Duration, Duration)]) -> usize,
+{
+    let Some(section) = subs.sections.iter_mut().find_map(|s| s.as_events_mut()) else {
+        return 0;
+    };
+    let mut events = section.events_mut().collect::<Vec<_>>();
+    let mut tracks: HashMap<(u8, &str), Vec<usize>> = HashMap::new();
+    for (index, event) in events.iter().enumerate() {
+        if event.kind.is_dialogue() {
+            tracks
+                .entry((event.layer, event.style.as_str()))
+                .or_default()
+                .push(index);
+        }
+    }
+    let mut tracks = tracks.into_values().collect::<Vec<_>>();
+    let mut result = 0;
+    for indices in &mut tracks {
+        indices.sort_by_key(|&i| events[i].start);
+        let mut timings = indices
+            .iter()
+            .map(|&i| (events[i].start, events[i].end))
+            .collect::<Vec<_>>();
+        result += f(&mut timings);
+        for (&index, (start, end)) in indices.iter().zip(timings) {
+            let event = &mut events[index];
+            event.start = start;
+            event.end = end;
+        }
+    }
+    result
+}
+
+/// Collapses runs of ASCII spaces into a single space.
+///
+/// Hard spaces (`\h`) and full-width spaces are intentional so they're kept.
+fn collapse_spaces(text: &str) -> String {
+    space_run_regex().replace_all(text, " ").into_owned()
+}
+
+/// Collapses runs of spaces in the literal text, leaving override blocks untouched.
+fn collapse_ass_spaces(text: &str) -> String {
+    let spans = TextSpan::parse(text)
+        .into_iter()
+        .map(|span| match span {
+            TextSpan::Text(text) => TextSpan::Text(collapse_spaces(&text)),
+            TextSpan::Override(_) => span,
+        })
+        .collect::<Vec<_>>();
+    TextSpan::join(&spans)
+}
+
+/// Trims the whitespace around every line, dropping lines that end up empty.
+fn trim_lines(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Trims the whitespace around the visible text of every `\N` separated line.
+///
+/// Override blocks are kept in place, e.g. `{\i1}  hi  ` becomes `{\i1}hi`.
+fn trim_ass_text(text: &str) -> String {
+    text.split("\\N")
+        .map(|line| {
+            let mut spans = TextSpan::parse(line);
+            for span in spans.iter_mut() {
+                if let TextSpan::Text(text) = span {
+                    *text = text.trim_start().to_owned();
+                    if !text.is_empty() {
+                        break;
+                    }
+                }
+            }
+            for span in spans.iter_mut().rev() {
+                if let TextSpan::Text(text) = span {
+                    *text = text.trim_end().to_owned();
+                    if !text.is_empty() {
+                        break;
+                    }
+                }
+            }
+            TextSpan::join(&spans)
+        })
+        .collect::<Vec<_>>()
+        .join("\\N")
+}
+
+/// Joins any lines past `max` onto the last allowed line with a space.
+///
+/// Returns `None` if the text already has `max` lines or less.
+fn limit_lines(text: &str, separator: &str, max: usize) -> Option<String> {
+    let lines = text.split(separator).collect::<Vec<_>>();
+    if lines.len() <= max {
+        return None;
+    }
+    let (kept, rest) = lines.split_at(max - 1);
+    let mut result = kept.join(separator);
+    if !kept.is_empty() {
+        result.push_str(separator);
+    }
+    result.push_str(&rest.join(" "));
+    Some(result)
+}
+
+/// Converts the case of the text.
+///
+/// Override tags, HTML tags, HTML entities and escapes such as `\N` are left as-is.
+fn convert_case(text: &str, case: TextCase) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut sentence_start = true;
+    let mut word_start = true;
+    while let Some(ch) = rest.chars().next() {
+        let skip = match ch {
+            '{' => rest.find('}').map(|i| i + 1),
+            '<' => rest.find('>').map(|i| i + 1),
+            '&' => rest[1..]
+                .find(';')
+                .filter(|&i| {
+                    i > 0
+                        && rest[1..=i]
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '#')
+                })
+                .map(|i| i + 2),
+            '\\' => rest[1..].chars().next().map(|c| {
+                // Line breaks and hard spaces separate words
+                word_start = true;
+                1 + c.len_utf8()
+            }),
+            _ => None,
+        };
+
+        if let Some(len) = skip {
+            result.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        rest = &rest[ch.len_utf8()..];
+        if ch.is_alphanumeric() {
+            let upper = match case {
+                TextCase::Lower => false,
+                TextCase::Upper => true,
+                TextCase::Title => word_start,
+                TextCase::Sentence => {
+                    // The pronoun I is always capitalised
+                    sentence_start
+                        || (word_start
+                            && matches!(ch, 'i' | 'I')
+                            && !rest.starts_with(char::is_alphanumeric))
+                }
+            };
+            if upper {
+                result.extend(ch.to_uppercase());
+            } else {
+                result.extend(ch.to_lowercase());
+            }
+            sentence_start = false;
+            word_start = false;
+        } else {
+            result.push(ch);
+            if ch.is_whitespace() {
+                word_start = true;
+            } else if matches!(ch, '.' | '?' | '!' | '。' | '？' | '！') {
+                sentence_start = true;
+            }
+        }
+    }
+    result
+}
+
+/// Returns `true` if the text ends with sentence-final punctuation.
+fn ends_sentence(text: &str) -> bool {
+    let punctuation: &[char] = if crate::japanese::contains_japanese(text) {
+        &['。', '？', '！', '?', '!', '…']
+    } else {
+        &['.', '?', '!', '…']
+    };
+    text.trim_end()
+        .trim_end_matches(['"', '\'', '”', '’', ')', '」', '』', '）'])
+        .ends_with(punctuation)
+}
+
+/// Joins the text of two .srt or .vtt dialogue lines with a line break.
+///
+/// Positioning such as `{\an8}` only works at the start of the text,
+/// so the second line's positioning is dropped in favour of the first.
+fn join_dialogue_text(first: &str, second: &str) -> String {
+    let second = second.strip_prefix("{\\an8}").unwrap_or(second);
+    format!("{first}\n{second}")
+}
+
+/// Runs [`crate::japanese::fix_broken_text`] and returns whether the text changed.
+fn fix_japanese_text(text: &mut String) -> bool {
+    let original = text.clone();
+    crate::japanese::fix_broken_text(text);
+    *text != original
+}
+
+/// Removes the items that are marked as removed.
+fn retain_unmarked<T>(items: &mut Vec<T>, removed: Vec<bool>) {
+    // retain visits every item once in order so this lines up with the marks
+    let mut removed = removed.into_iter();
+    items.retain(|_| !removed.next().unwrap_or_default());
+}
+
+/// Merges .srt or .vtt dialogue that is shown at the same time as the line before it.
+fn merge_simultaneous_dialogue(dialogue: &mut Vec<srt::Dialogue>) {
+    let mut removed = vec![false; dialogue.len()];
+    // Every line of a group is merged into the first one
+    let mut first = 0;
+    for index in 1..dialogue.len() {
+        if dialogue[first].start == dialogue[index].start
+            && dialogue[first].end == dialogue[index].end
+        {
+            dialogue[first].text = join_dialogue_text(&dialogue[first].text, &dialogue[index].text);
+            removed[index] = true;
+        } else {
+            first = index;
+        }
+    }
+    retain_unmarked(dialogue, removed);
+}
+
+/// Joins .srt or .vtt dialogue that doesn't end a sentence with the line after it.
+fn join_dialogue_sentences(
+    dialogue: &mut Vec<srt::Dialogue>,
+    max_gap: Duration,
+    range: &DurationRange,
+) {
+    let mut removed = vec![false; dialogue.len()];
+    let mut windows = windows_mut(dialogue);
+    let mut index = 0;
+    while let Some([left, right]) = windows.next() {
+        index += 1;
+        // Join forwards so that a sentence can span more than two lines
+        if range.contains(&left.start)
+            && !ends_sentence(&left.text)
+            && right.start.saturating_sub(left.end) <= max_gap
+        {
+            right.text = join_dialogue_text(&left.text, &right.text);
+            right.start = left.start;
+            removed[index - 1] = true;
+        }
+    }
+    retain_unmarked(dialogue, removed);
+}
+
+/// Splits multi-line dialogue into a dialogue per line, see `cleanup --split-lines`.
+///
+/// Only dialogue that starts inside the range is split and blank lines are
+/// dropped. Returns how many dialogue lines were split.
+fn split_dialogue_lines(
+    dialogue: &mut Vec<srt::Dialogue>,
+    timing: SplitTiming,
+    range: &DurationRange,
+) -> usize {
+    let mut split = 0;
+    let mut result = Vec::with_capacity(dialogue.len());
+    for d in dialogue.drain(..) {
+        let lines = d
+            .text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        if lines.len() < 2 || !range.contains(&d.start) {
+            result.push(d);
+            continue;
+        }
+        split += 1;
+        for (line, (start, end)) in lines.iter().zip(timing.split(d.start, d.end, lines.len())) {
+            result.push(srt::Dialogue {
+                start,
+                end,
+                text: (*line).to_owned(),
+                ..d.clone()
+            });
+        }
+    }
+    *dialogue = result;
+    split
+}
+
+/// Splits multi-line dialogue events into an event per line, see `cleanup --split-lines`.
+///
+/// The override blocks at the start of the text are repeated on every line
+/// so that e.g. the alignment still applies. Returns how many events were split.
+fn split_event_lines(subs: &mut Ass, timing: SplitTiming, range: &DurationRange) -> usize {
+    let Some(section) = subs.sections.iter_mut().find_map(|s| s.as_events_mut()) else {
+        return 0;
+    };
+    let mut split = 0;
+    let mut result = Vec::with_capacity(section.entries.len());
+    for entry in section.entries.drain(..) {
+        let Entry::Item(event) = entry else {
+            result.push(entry);
+            continue;
+        };
+        let spans = TextSpan::parse(&event.text);
+        let leading = spans.iter().take_while(|s| s.is_override()).count();
+        let prefix = TextSpan::join(&spans[..leading]);
+        let lines = event.text[prefix.len()..]
+            .split("\\N")
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        // Every line would end up on the same spot, so positioned events are left alone
+        let positioned = spans
+            .iter()
+            .flat_map(TextSpan::tags)
+            .any(|tag| TagCategory::of(tag) == Some(TagCategory::Movement));
+        if !event.kind.is_dialogue()
+            || positioned
+            || lines.len() < 2
+            || !range.contains(&event.start)
+        {
+            result.push(Entry::Item(event));
+            continue;
+        }
+        split += 1;
+        for (line, (start, end)) in
+            lines
+                .iter()
+                .zip(timing.split(event.start, event.end, lines.len()))
+        {
+            let mut line_event = event.clone();
+            line_event.text = format!("{prefix}{line}");
+            line_event.start = start;
+            line_event.end = end;
+            result.push(Entry::Item(line_event));
+        }
+    }
+    section.entries = result;
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_case() {
+        let text = r"{\i1}HELLO THERE.\NI'M FINE, &AMP; YOU? <i>GOOD</i> こんにちは";
+        assert_eq!(
+            convert_case(text, TextCase::Sentence),
+            r"{\i1}Hello there.\NI'm fine, &AMP; you? <i>Good</i> こんにちは"
+        );
+        assert_eq!(
+            convert_case(text, TextCase::Title),
+            r"{\i1}Hello There.\NI'm Fine, &AMP; You? <i>Good</i> こんにちは"
+        );
+        assert_eq!(
+            convert_case(text, TextCase::Lower),
+            r"{\i1}hello there.\Ni'm fine, &AMP; you? <i>good</i> こんにちは"
+        );
+        assert_eq!(convert_case("{\\b1}abc", TextCase::Upper), "{\\b1}ABC");
+    }
+
+    #[test]
+    fn test_ends_sentence() {
+        assert!(ends_sentence("Hello."));
+        assert!(ends_sentence("Really?\" "));
+        assert!(!ends_sentence("and then"));
+        assert!(ends_sentence("そうですか？"));
+        assert!(ends_sentence("「行こう。」"));
+        assert!(!ends_sentence("今日は"));
+    }
+
+    #[test]
+    fn test_strip_html_tags() {
+        let strip = |s: &str| html_tag_regex().replace_all(s, "").into_owned();
+        assert_eq!(
+            strip("<i>Hello</i> <font color=\"red\">world</font>"),
+            "Hello world"
+        );
+        assert_eq!(strip("3 < 5 and 5 > 3"), "3 < 5 and 5 > 3");
+        assert_eq!(strip("a <b"), "a <b");
+        assert_eq!(strip("<b and c>"), "<b and c>");
+        assert_eq!(strip("<laughs> <B>Hi</B>"), "<laughs> Hi");
+        assert_eq!(
+            strip("<v Bob><c.yellow.bg_blue>Hi</c></v><00:00:01.500>there"),
+            "Hithere"
+        );
+    }
+
+    #[test]
+    fn test_fix_zero_duration() {
+        let ms = Duration::from_millis;
+        let options = CleanupOptions {
+            fix_zero_duration: Some(ms(1000)),
+            ..Default::default()
+        };
+        let mut end = ms(1000);
+        options.fix_timing(ms(1000), &mut end, None);
+        assert_eq!(end, ms(2000));
+
+        let mut end = ms(1000);
+        options.fix_timing(ms(1000), &mut end, Some(ms(1500)));
+        assert_eq!(end, ms(1500));
+
+        let mut end = ms(1200);
+        options.fix_timing(ms(1000), &mut end, None);
+        assert_eq!(end, ms(1200));
+
+        // Lines that end before they start aren't shown either
+        let mut end = ms(500);
+        options.fix_timing(ms(1000), &mut end, None);
+        assert_eq!(end, ms(2000));
+    }
+
+    #[test]
+    fn test_pad_cue_timings() {
+        let ms = Duration::from_millis;
+        let mut timings = [
+            (ms(1000), ms(2000)),
+            (ms(2100), ms(3000)),
+            (ms(5000), ms(6000)),
+        ];
+        let clamped = pad_timings(&mut timings, ms(50), ms(150), &DurationRange::default());
+        assert_eq!(clamped, 2);
+        assert_eq!(
+            timings,
+            [
+                (ms(950), ms(2100)),
+                (ms(2100), ms(3150)),
+                (ms(4950), ms(6150)),
+            ]
+        );
+
+        let mut timings = [(ms(0), ms(1000)), (ms(900), ms(2000))];
+        let range = DurationRange {
+            start: Some(ms(500)),
+            end: None,
+        };
+        assert_eq!(pad_timings(&mut timings, ms(100), ms(0), &range), 1);
+        assert_eq!(timings, [(ms(0), ms(1000)), (ms(900), ms(2000))]);
+    }
+
+    #[test]
+    fn test_merge_and_join_dialogue() {
+        let cue = |position, start, end, text: &str| srt::Dialogue {
+            position,
+            start: Duration::from_secs(start),
+            end: Duration::from_secs(end),
+            text: text.to_owned(),
+        };
+        // Positions that used to be used to mark removed lines are kept
+        let mut dialogue = vec![
+            cue(u32::MAX, 1, 2, "A"),
+            cue(u32::MAX, 1, 2, "B"),
+            cue(u32::MAX, 1, 2, "C"),
+            cue(u32::MAX, 3, 4, "D."),
+        ];
+        merge_simultaneous_dialogue(&mut dialogue);
+        let texts = dialogue.iter().map(|d| d.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["A\nB\nC", "D."]);
+
+        let buf = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,A\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,B\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,C\n";
+        let mut subs = buf.parse::<Ass>().unwrap();
+        let options = CleanupOptions {
+            merge_simultaneous: true,
+            ..Default::default()
+        };
+        assert_eq!(cleanup_ass(&mut subs, &options).unwrap().merged, 2);
+        let texts = subs.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, ["A\\NB\\NC"]);
+
+        let mut dialogue = vec![
+            cue(1, 1, 2, "and then"),
+            cue(u32::MAX, 2, 3, "it ended"),
+            cue(3, 3, 4, "here."),
+            cue(u32::MAX, 5, 6, "Bye."),
+        ];
+        join_dialogue_sentences(
+            &mut dialogue,
+            Duration::from_secs(1),
+            &DurationRange::default(),
+        );
+        assert_eq!(dialogue.len(), 2);
+        assert_eq!(dialogue[0].text, "and then\nit ended\nhere.");
+        assert_eq!(dialogue[0].start, Duration::from_secs(1));
+        assert_eq!(dialogue[1].text, "Bye.");
+    }
+
+    #[test]
+    fn test_close_small_gaps() {
+        let ms = Duration::from_millis;
+        let mut timings = [
+            (ms(1000), ms(2000)),
+            (ms(2080), ms(3000)),
+            (ms(3150), ms(4000)),
+            (ms(3500), ms(5000)),
+            (ms(6000), ms(7000)),
+        ];
+        let closed = close_gaps(&mut timings, ms(200), &DurationRange::default());
+        assert_eq!(closed, 2);
+        assert_eq!(
+            timings,
+            [
+                (ms(1000), ms(2080)),
+                (ms(2080), ms(3150)),
+                (ms(3150), ms(4000)),
+                (ms(3500), ms(5000)),
+                (ms(6000), ms(7000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_multi_line_cues() {
+        let s = Duration::from_secs;
+        let mut dialogue = vec![
+            srt::Dialogue {
+                position: 1,
+                start: s(0),
+                end: s(3),
+                text: String::from("One\nTwo\n\nThree"),
+            },
+            srt::Dialogue {
+                position: 2,
+                start: s(3),
+                end: s(4),
+                text: String::from("Single"),
+            },
+        ];
+        let split = split_dialogue_lines(
+            &mut dialogue,
+            SplitTiming::Divide,
+            &DurationRange::default(),
+        );
+        assert_eq!(split, 1);
+        assert_eq!(
+            dialogue
+                .iter()
+                .map(|d| (d.start, d.end, d.text.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (s(0), s(1), "One"),
+                (s(1), s(2), "Two"),
+                (s(2), s(3), "Three"),
+                (s(3), s(4), "Single"),
+            ]
+        );
+
+        let event = |text: &str| crate::ass::Event {
+            start: s(1),
+            end: s(2),
+            text: text.to_owned(),
+            ..Default::default()
+        };
+        let mut subs = Ass::from_parts(
+            [("ScriptType", "v4.00+")],
+            Vec::new(),
+            vec![
+                event(r"{\an8\i1}Top\N{\b1}Bottom"),
+                event(r"One line"),
+                event(r"{\pos(10,20)}Left\NRight"),
+            ],
+        );
+        let split = split_event_lines(&mut subs, SplitTiming::Share, &DurationRange::default());
+        assert_eq!(split, 1);
+        let texts = subs.events().map(|e| e.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                r"{\an8\i1}Top",
+                r"{\an8\i1}{\b1}Bottom",
+                "One line",
+                r"{\pos(10,20)}Left\NRight"
+            ]
+        );
+        assert!(subs.events().all(|e| (e.start, e.end) == (s(1), s(2))));
+    }
+
+    #[test]
+    fn test_strip_tag_categories() {
+        let strip = |text: &str, categories: &[TagCategory]| {
+            let mut removed = BTreeMap::new();
+            let text = strip_tags(text, &TagCategory::expand(categories), &mut removed);
+            (text, removed.into_iter().collect::<Vec<_>>())
+        };
+        assert_eq!(
+            strip(r"{\kf20}Ka{\k15\b1}ra{\K30}oke", &[TagCategory::Karaoke]),
+            (
+                String::from(r"Ka{\b1}raoke"),
+                vec![(TagCategory::Karaoke, 3)]
+            )
+        );
+        assert_eq!(
+            strip(
+                r"{\pos(10,20)\t(0,500,\fs40)\an8}Hi{\move(0,0,1,1)}",
+                &[TagCategory::Transform, TagCategory::Movement]
+            ),
+            (
+                String::from(r"{\an8}Hi"),
+                vec![(TagCategory::Transform, 1), (TagCategory::Movement, 2)]
+            )
+        );
+        assert_eq!(
+            strip(
+                r"{\p1}m 0 0 l 10 10{\p0}Text{\pbo2}",
+                &[TagCategory::Drawing]
+            ),
+            (
+                String::from(r"Text{\pbo2}"),
+                vec![(TagCategory::Drawing, 2)]
+            )
+        );
+        assert_eq!(
+            strip(r"{}{\k10\p1}m 0 0{\p0}", &[TagCategory::All]),
+            (
+                String::from("{}"),
+                vec![(TagCategory::Drawing, 2), (TagCategory::Karaoke, 1)]
+            )
+        );
+    }
+
+    #[test]
+    fn test_cleanup_summary() {
+        assert_eq!(CleanupReport::default().to_string(), "Nothing was changed.");
+        let summary = CleanupReport {
+            comments: 12,
+            japanese: 1,
+            merged: 8,
+            renumbered: 450,
+            ..Default::default()
+        };
+        assert_eq!(
+            summary.to_string(),
+            "Removed 12 comments, fixed 1 Japanese line, merged 8 simultaneous lines, \
+             renumbered 450 entries."
+        );
+        let summary = CleanupReport {
+            unused_styles: vec![String::from("Sign")],
+            ..Default::default()
+        };
+        assert_eq!(summary.to_string(), "Pruned 1 unused style.");
+
+        let ms = Duration::from_millis;
+        let before = vec![
+            (ms(0), ms(1000), String::from("a")),
+            (ms(1000), ms(2000), String::from("b")),
+            (ms(2000), ms(3000), String::from("c")),
+        ];
+        let mut summary = CleanupReport::default();
+        summary.count_changes(
+            &before,
+            [
+                (ms(0), ms(1000), "A"),
+                (ms(1000), ms(1500), "b"),
+                (ms(2000), ms(3000), "c"),
+            ]
+            .into_iter(),
+        );
+        assert_eq!((summary.edited, summary.retimed), (1, 1));
+    }
+
+    #[test]
+    fn test_open_small_gaps() {
+        let ms = Duration::from_millis;
+        let mut timings = [
+            (ms(1000), ms(2000)),
+            (ms(2000), ms(3000)),
+            (ms(3050), ms(3100)),
+            (ms(3120), ms(4000)),
+            (ms(3500), ms(5000)),
+            (ms(6000), ms(7000)),
+        ];
+        let trimmed = open_gaps(&mut timings, ms(80), &DurationRange::default());
+        assert_eq!(trimmed, 2);
+        assert_eq!(
+            timings,
+            [
+                (ms(1000), ms(1920)),
+                (ms(2000), ms(2970)),
+                (ms(3050), ms(3100)),
+                (ms(3120), ms(4000)),
+                (ms(3500), ms(5000)),
+                (ms(6000), ms(7000)),
+            ]
+        );
+
+        // The gap is opened before snapping so the end stays on a frame
+        let mut dialogue = srt::load_from_string(
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:02,010 --> 00:00:03,000\nWorld\n",
+        )
+        .unwrap();
+        let options = CleanupOptions {
+            min_gap: Some(ms(100)),
+            snap_fps: Some(25.0),
+            ..Default::default()
+        };
+        cleanup_dialogue(&mut dialogue, &options);
+        assert_eq!(dialogue[0].end, ms(1920));
+        assert_eq!(dialogue[1].start, ms(2000));
+    }
+
+    #[test]
+    fn test_collapse_space_runs() {
+        assert_eq!(collapse_spaces("hello   world"), "hello world");
+        assert_eq!(collapse_spaces("a\u{3000}\u{3000}b"), "a\u{3000}\u{3000}b");
+        assert_eq!(collapse_ass_spaces(r"a\h\h\hb  c"), r"a\h\h\hb c");
+        assert_eq!(
+            collapse_ass_spaces(r"{\fnNoto  Sans}hello  {\i1}  world"),
+            r"{\fnNoto  Sans}hello {\i1} world"
+        );
+    }
+
+    #[test]
+    fn test_trim_text() {
+        assert_eq!(trim_lines("  hello \n\tworld  "), "hello\nworld");
+        assert_eq!(trim_lines("hello\n   \nworld"), "hello\nworld");
+        assert_eq!(trim_ass_text(r"{\i1}  hi  "), r"{\i1}hi");
+        assert_eq!(
+            trim_ass_text(r" {\an8} {\b1} top {\b0} \N  bottom\h "),
+            r"{\an8}{\b1}top{\b0}\Nbottom\h"
+        );
+        assert_eq!(trim_ass_text("   "), "");
+    }
+
+    #[test]
+    fn test_limit_lines_and_snap() {
+        assert_eq!(limit_lines("a\nb", "\n", 2), None);
+        assert_eq!(limit_lines("a\nb\nc", "\n", 2).as_deref(), Some("a\nb c"));
+        assert_eq!(limit_lines("a\\Nb\\Nc", "\\N", 1).as_deref(), Some("a b c"));
+        assert_eq!(
+            snap_to_frame(Duration::from_millis(1010), 25.0, false),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            snap_to_frame(Duration::from_millis(1000), 23.976, false),
+            Duration::from_millis(1001)
+        );
+        assert_eq!(
+            snap_to_frame(Duration::from_millis(1000), 23.976, true),
+            Duration::from_millis(1010)
+        );
+        assert_eq!(
+            snap_to_frame(Duration::from_millis(1010), 25.0, true),
+            Duration::from_millis(1000)
+        );
+    }
+
+    #[test]
+    fn test_cleanup_events() {
+        let s = Duration::from_secs;
+        let event = |start, end, text: &str| crate::ass::Event {
+            start: s(start),
+            end: s(end),
+            text: text.to_owned(),
+            ..Default::default()
+        };
+        let mut comment = event(0, 1, "Note");
+        comment.kind = crate::ass::EventKind::Comment;
+        let mut subs = Ass::from_parts(
+            [("ScriptType", "v4.00+")],
+            Vec::new(),
+            vec![
+                comment,
+                event(1, 1, r"{\k10}Ka{\k20}ra"),
+                event(5, 6, r"{\k10}Kept"),
+            ],
+        );
+        let options = CleanupOptions {
+            range: DurationRange {
+                start: None,
+                end: Some(s(4)),
+            },
+            comments: true,
+            strip_tags: vec![TagCategory::Karaoke, TagCategory::Movement],
+            fix_zero_duration: Some(s(2)),
+            ..Default::default()
+        };
+        let report = cleanup_ass(&mut subs, &options).unwrap();
+        let events = subs
+            .events()
+            .map(|e| (e.start, e.end, e.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(events, [(s(1), s(3), "Kara"), (s(5), s(6), r"{\k10}Kept")]);
+        assert_eq!(report.comments, 1);
+        assert_eq!((report.zero_duration, report.zero_duration_fixed), (1, 1));
+        assert_eq!(
+            report.tags_removed.into_iter().collect::<Vec<_>>(),
+            [(TagCategory::Karaoke, 2), (TagCategory::Movement, 0)]
+        );
+        assert_eq!((report.edited, report.retimed), (1, 1));
+    }
+
+    #[test]
+    fn test_cleanup_keeps_section_comments() {
+        let buf = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n; Scene 1\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,A\n; Simultaneous\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,B\n; Scene 2\nDialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,C\\ND\n";
+        let mut subs = buf.parse::<Ass>().unwrap();
+        let options = CleanupOptions {
+            merge_simultaneous: true,
+            split_lines: Some(SplitTiming::Share),
+            ..Default::default()
+        };
+        let report = cleanup_ass(&mut subs, &options).unwrap();
+        assert_eq!((report.merged, report.split), (1, 2));
+        let section = subs.sections.iter().find_map(|s| s.as_events()).unwrap();
+        let entries = section
+            .entries
+            .iter()
+            .map(|e| match e {
+                Entry::Comment(comment) => comment.as_str(),
+                Entry::Item(event) => event.text.as_str(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [" Scene 1", "A", "B", " Simultaneous", " Scene 2", "C", "D"]
+        );
+    }
+}
@@ -1,85 +1,58 @@
+use anyhow::Context;
 use clap::{
-    builder::styling::{Effects, Reset, RgbColor, Style as AnsiStyle},
+    builder::styling::{Ansi256Color, Color, Effects, RgbColor, Style as AnsiStyle},
     Args, CommandFactory, Parser, Subcommand, ValueEnum,
 };
 use regex::Regex;
+use serde::Deserialize;
 use std::{
     collections::{HashMap, HashSet},
-    io::{stdin, stdout, IsTerminal, Read, Write},
+    fmt::Display,
+    io::{stderr, stdin, stdout, IsTerminal, Read, Write},
     path::{Path, PathBuf},
-    sync::OnceLock,
     time::Duration,
 };
 
 use crate::{
-    ass::{Ass, Colour},
-    srt,
-    utils::{windows_mut, LendingIterator},
-    vtt, SubtitleFormat,
+    ass::{Ass, Colour, ParseOptions, WriteOptions},
+    ass_to_dialogue,
+    cleanup::{
+        cleanup_ass, cleanup_dialogue, CleanupOptions, CleanupReport, DurationRange,
+        PunctuationWidth, SplitTiming, TagCategory, TextCase,
+    },
+    config::{CleanupConfig, ConvertConfig, ShiftConfig},
+    japanese::{detect_script, Script},
+    srt::{self, DialogueChange},
+    utils::{
+        duration_from_secs, frame_alignment, scale_duration, SignedDuration, COMMON_FRAME_RATES,
+    },
+    vtt, FormatError, SubtitleFormat,
 };
 
+#[cfg(feature = "serde")]
+use crate::json::Subtitles;
+
+/// Returns `true` if the seconds can be used as a duration option.
+fn is_valid_duration(time: f32) -> bool {
+    time.is_finite() && time != -0.0
+}
+
 fn valid_duration(s: &str) -> Result<f32, String> {
     let time: f32 = s
         .parse()
         .map_err(|_| format!("`{s}` isn't a valid duration"))?;
-    if !time.is_finite() || time == -0.0 {
-        Err(format!("`{s}` isn't a valid duration"))
-    } else {
+    if is_valid_duration(time) {
         Ok(time)
+    } else {
+        Err(format!("`{s}` isn't a valid duration"))
     }
 }
 
-fn ass_tag_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| Regex::new(r#"\{(.+)\}"#).unwrap())
-}
-
-fn allowed_ass_tags_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| Regex::new(r#"(\\an\d)"#).unwrap())
-}
-
-fn drawing_events_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| Regex::new(r#"\{\\p\d\}(.+)\{\\p\d\}"#).unwrap())
-}
-
-fn special_ass_character_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| Regex::new(r#"\\(n|N|h)"#).unwrap())
-}
-
-fn clean_ass_text(s: &str) -> String {
-    // Remove drawing events
-    let result = drawing_events_regex().replace_all(s, "");
-    // Remove all other ass tags
-    let result = ass_tag_regex().replace_all(&result, |captures: &regex::Captures| {
-        match allowed_ass_tags_regex().find(&captures[1]) {
-            Some(m) => {
-                let mut buffer = String::with_capacity(2 + m.len());
-                buffer.push('{');
-                buffer.push_str(m.as_str());
-                buffer.push('}');
-                buffer
-            }
-            None => String::new(),
-        }
-    });
-    // Replace special characters
-    special_ass_character_regex()
-        .replace_all(&result, |captures: &regex::Captures| {
-            match captures.get(1) {
-                Some(m) => {
-                    if m.as_str() == "N" {
-                        "\n"
-                    } else {
-                        " "
-                    }
-                }
-                None => " ", // This should technically be "unreachable"
-            }
-        })
-        .into_owned()
+fn valid_offset(s: &str) -> Result<SignedDuration, String> {
+    match s.parse::<SignedDuration>() {
+        Ok(offset) if !offset.is_zero() => Ok(offset),
+        _ => Err(format!("`{s}` isn't a valid duration")),
+    }
 }
 
 /// A duration that can be parsed from the command line or as a string input.
@@ -132,6 +105,58 @@ fn parse_duration(s: &str) -> Result<Duration, InvalidDuration> {
     parse_duration_fractional_helper(s).ok_or(InvalidDuration)
 }
 
+/// Detects the subtitle format of the given contents.
+fn detect_format(contents: &str) -> Result<SubtitleFormat, FormatError> {
+    SubtitleFormat::detect(contents).ok_or(FormatError::Unrecognized)
+}
+
+/// Returns an error saying that the operation does not support the format.
+fn unsupported_format(format: SubtitleFormat, operation: &'static str) -> anyhow::Error {
+    FormatError::Unsupported(format, operation).into()
+}
+
+/// Exit code for errors that don't fit in any other category.
+pub const EXIT_FAILURE: u8 = 1;
+/// Exit code for invalid command line arguments.
+///
+/// This is the exit code used by the argument parser.
+pub const EXIT_USAGE: u8 = 2;
+/// Exit code for subtitle files that could not be parsed.
+pub const EXIT_PARSE: u8 = 3;
+/// Exit code for I/O errors, e.g. a file that could not be opened.
+pub const EXIT_IO: u8 = 4;
+/// Exit code for subtitle formats that are unrecognised or unsupported by the operation.
+pub const EXIT_UNSUPPORTED_FORMAT: u8 = 5;
+
+/// Returns the exit code that should be used for the given error.
+///
+/// The first error in the chain that has a dedicated exit code is used.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    for cause in error.chain() {
+        #[cfg(feature = "serde")]
+        if cause.is::<crate::json::JsonError>() {
+            return EXIT_PARSE;
+        }
+
+        if cause.is::<FormatError>() {
+            return EXIT_UNSUPPORTED_FORMAT;
+        } else if let Some(e) = cause.downcast_ref::<crate::ass::Error>() {
+            return match e.kind() {
+                crate::ass::ErrorKind::Io(_) => EXIT_IO,
+                _ => EXIT_PARSE,
+            };
+        } else if cause.is::<srt::ParseDialogueError>() {
+            return EXIT_PARSE;
+        } else if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            return match e.kind() {
+                std::io::ErrorKind::InvalidData => EXIT_PARSE,
+                _ => EXIT_IO,
+            };
+        }
+    }
+    EXIT_FAILURE
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum InputOutputLocation {
     Path(PathBuf),
@@ -147,48 +172,103 @@ impl InputOutputLocation {
         }
     }
 
-    fn read_as_string(&self) -> std::io::Result<String> {
+    fn read_bytes(&self) -> std::io::Result<Vec<u8>> {
         match self {
-            InputOutputLocation::Path(path) => crate::load_file(path),
+            InputOutputLocation::Path(path) => std::fs::read(path),
             InputOutputLocation::Stdio => {
-                let mut stdin = stdin();
-                let mut buffer = String::new();
-                stdin.read_to_string(&mut buffer)?;
+                let mut buffer = Vec::new();
+                stdin().read_to_end(&mut buffer)?;
+                Ok(buffer)
+            }
+        }
+    }
 
-                if buffer.starts_with('\u{feff}') {
-                    // This is pretty inefficient but oh well
-                    // U+FEFF is 3 bytes
-                    buffer.drain(..3);
-                }
+    fn read_as_string(&self) -> std::io::Result<String> {
+        crate::decode_file(self.read_bytes()?)
+    }
 
-                Ok(buffer)
+    fn save_ass(&self, ass: &Ass, options: &WriteOptions) -> anyhow::Result<()> {
+        match self {
+            InputOutputLocation::Path(path) => ass.save_with(path, options)?,
+            InputOutputLocation::Stdio => ass.save_to_writer_with(stdout().lock(), options)?,
+        }
+        Ok(())
+    }
+
+    fn save_vtt(
+        &self,
+        dialogue: &[srt::Dialogue],
+        options: &vtt::WriteOptions,
+    ) -> anyhow::Result<()> {
+        match self {
+            InputOutputLocation::Path(path) => vtt::save_with(path, dialogue, options)?,
+            InputOutputLocation::Stdio => {
+                let buf = vtt::save_to_string(dialogue);
+                let mut stdout = stdout().lock();
+                if options.bom {
+                    stdout.write_all(&crate::UTF8_BOM)?;
+                }
+                stdout.write_all(buf.as_bytes())?;
             }
         }
+        Ok(())
     }
 
-    fn save_ass(&self, ass: &Ass) -> anyhow::Result<()> {
+    fn save_string(&self, buf: &str) -> anyhow::Result<()> {
         match self {
-            InputOutputLocation::Path(path) => ass.save(path)?,
-            InputOutputLocation::Stdio => ass.save_to_writer(stdout().lock())?,
+            InputOutputLocation::Path(path) => std::fs::write(path, buf)
+                .with_context(|| format!("could not write {}", path.display()))?,
+            InputOutputLocation::Stdio => stdout().write_all(buf.as_bytes())?,
         }
         Ok(())
     }
 
-    fn save_srt(&self, dialogue: &[srt::Dialogue]) -> anyhow::Result<()> {
+    #[cfg(feature = "serde")]
+    fn save_json(&self, subtitles: &Subtitles) -> anyhow::Result<()> {
+        self.save_string(&subtitles.to_json()?)
+    }
+
+    fn save_srt(
+        &self,
+        dialogue: &[srt::Dialogue],
+        options: &srt::WriteOptions,
+    ) -> anyhow::Result<()> {
         match self {
-            InputOutputLocation::Path(path) => srt::save(path, dialogue),
+            InputOutputLocation::Path(path) => srt::save_with(path, dialogue, options),
             InputOutputLocation::Stdio => {
-                let buf = srt::save_to_string(dialogue);
-                stdout().write_all(buf.as_bytes())?;
+                let buf = srt::save_to_string_with(dialogue, options);
+                let mut stdout = stdout().lock();
+                if options.bom {
+                    stdout.write_all(&crate::UTF8_BOM)?;
+                }
+                stdout.write_all(buf.as_bytes())?;
                 Ok(())
             }
         }
     }
 }
 
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Success
+  1  Other error
+  2  Invalid command line arguments
+  3  Subtitle file could not be parsed
+  4  I/O error
+  5  Unrecognized or unsupported subtitle format";
+
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = EXIT_CODES_HELP)]
 pub struct Cli {
+    /// The configuration file to read default options from.
+    ///
+    /// Defaults to `.sub-tools.toml` in the working directory
+    /// if it exists. Options given on the command line take
+    /// precedence over the ones in the configuration file.
+    /// Flags that the configuration file turns on can be turned
+    /// off with `--no-` in front of their name, e.g. `--no-trim`.
+    #[arg(long, global = true, verbatim_doc_comment)]
+    pub config: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Subcommands,
 }
@@ -199,22 +279,222 @@ pub enum Subcommands {
     Convert(ConvertArgs),
     /// Shifts a subtitle's dialogue by a given time
     Shift(ShiftArgs),
+    /// Checks whether a subtitle's dialogue fits within a video
+    Fit(FitArgs),
     /// Cleans up a subtitle file
     Cleanup(CleanupArgs),
     /// Shows some high level information about a subtitle file
     Info(InfoArgs),
+    /// Merges an .ass subtitle file on top of another one
+    Merge(MergeArgs),
+    /// Checks an .ass subtitle file for structural issues
+    Check(CheckArgs),
+    /// Shows the dialogue that changed between two subtitle files
+    Diff(DiffArgs),
 }
 
-#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ConvertFormat {
     Auto,
     Srt,
     Ass,
+    /// A table of the events with aligned columns for reviewing by hand.
+    ///
+    /// This can't be converted back into subtitles.
+    Table,
+    /// The structured subtitle model, see the `json` module
+    #[cfg(feature = "serde")]
+    Json,
 }
 
-#[derive(Args, Default, Debug)]
+impl ConvertFormat {
+    /// Returns the format that uses the extension of the path.
+    fn from_path(path: &Path) -> Option<Self> {
+        match SubtitleFormat::from_path(path) {
+            Some(SubtitleFormat::Srt) => Some(Self::Srt),
+            Some(SubtitleFormat::Ass) => Some(Self::Ass),
+            Some(_) => None,
+            None => {
+                let extension = path.extension()?.to_str()?;
+                [
+                    Self::Table,
+                    #[cfg(feature = "serde")]
+                    Self::Json,
+                ]
+                .into_iter()
+                .find(|format| format.extension().eq_ignore_ascii_case(extension))
+            }
+        }
+    }
+
+    /// Returns the file extension used by the format, without the leading dot.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Srt => SubtitleFormat::Srt.extension(),
+            Self::Ass => SubtitleFormat::Ass.extension(),
+            Self::Table => "txt",
+            #[cfg(feature = "serde")]
+            Self::Json => "json",
+            Self::Auto => unreachable!("auto has no extension"),
+        }
+    }
+}
+
+/// An input format that can't be detected from the file contents.
+#[cfg(feature = "serde")]
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq)]
+pub enum InputFormat {
+    /// The structured subtitle model written by `--to json`
+    Json,
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SrtSeparator {
+    /// `00:00:01,000`
+    Comma,
+    /// `00:00:01.000`
+    Dot,
+}
+
+/// The frame rate that [`CleanupProfile::Netflix`] snaps to.
+///
+/// This is only a default, `--snap-fps` or `snap-fps` in the configuration
+/// file take precedence for videos with a different frame rate.
+pub const NETFLIX_SNAP_FPS: f64 = 24000.0 / 1001.0;
+
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupProfile {
+    /// Netflix timed text style guide: snaps to 23.976 fps unless
+    /// --snap-fps is given, keeps durations between 5/6 and 7
+    /// seconds, allows at most 2 lines, and collapses runs of spaces
+    #[value(verbatim_doc_comment)]
+    Netflix,
+}
+
+/// The command line and configuration values of [`PunctuationWidth`].
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PunctuationWidthArg {
+    /// `、。！？` become `,.!?`
+    Ascii,
+    /// `,.!?` become `、。！？` when they follow Japanese text
+    Fullwidth,
+}
+
+impl From<PunctuationWidthArg> for PunctuationWidth {
+    fn from(width: PunctuationWidthArg) -> Self {
+        match width {
+            PunctuationWidthArg::Ascii => Self::Ascii,
+            PunctuationWidthArg::Fullwidth => Self::Fullwidth,
+        }
+    }
+}
+
+/// The command line and configuration values of [`TextCase`].
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextCaseArg {
+    /// all lowercase
+    Lower,
+    /// ALL UPPERCASE
+    Upper,
+    /// Every Word Capitalised
+    Title,
+    /// Only the first letter of every sentence capitalised
+    Sentence,
+}
+
+impl From<TextCaseArg> for TextCase {
+    fn from(case: TextCaseArg) -> Self {
+        match case {
+            TextCaseArg::Lower => Self::Lower,
+            TextCaseArg::Upper => Self::Upper,
+            TextCaseArg::Title => Self::Title,
+            TextCaseArg::Sentence => Self::Sentence,
+        }
+    }
+}
+
+/// The command line and configuration values of [`SplitTiming`].
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitTimingArg {
+    /// Every line is shown for the whole cue
+    Share,
+    /// The cue's duration is divided evenly between the lines
+    Divide,
+}
+
+impl From<SplitTimingArg> for SplitTiming {
+    fn from(timing: SplitTimingArg) -> Self {
+        match timing {
+            SplitTimingArg::Share => Self::Share,
+            SplitTimingArg::Divide => Self::Divide,
+        }
+    }
+}
+
+/// The command line and configuration values of [`TagCategory`].
+#[derive(Debug, Copy, Clone, ValueEnum, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagCategoryArg {
+    /// Every category below
+    All,
+    /// `\p` drawings, along with the drawing commands
+    Drawing,
+    /// `\k`, `\K`, `\kf`, `\ko` and `\kt`
+    Karaoke,
+    /// `\t` animated transforms
+    Transform,
+    /// `\move` and `\pos`
+    Movement,
+}
+
+impl From<TagCategoryArg> for TagCategory {
+    fn from(category: TagCategoryArg) -> Self {
+        match category {
+            TagCategoryArg::All => Self::All,
+            TagCategoryArg::Drawing => Self::Drawing,
+            TagCategoryArg::Karaoke => Self::Karaoke,
+            TagCategoryArg::Transform => Self::Transform,
+            TagCategoryArg::Movement => Self::Movement,
+        }
+    }
+}
+
+/// Returns `true` if the frame rate can be used as an option.
+fn is_valid_fps(fps: f64) -> bool {
+    fps.is_finite() && fps > 0.0
+}
+
+fn valid_fps(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(fps) if is_valid_fps(fps) => Ok(fps),
+        _ => Err(format!("`{s}` isn't a valid frame rate")),
+    }
+}
+
+fn valid_frames(s: &str) -> Result<i64, String> {
+    match s.parse::<i64>() {
+        Ok(frames) if frames != 0 => Ok(frames),
+        _ => Err(format!("`{s}` isn't a valid frame count")),
+    }
+}
+
+/// Converts a number of frames at the given frame rate into an offset.
+fn frames_to_offset(frames: i64, fps: f64) -> SignedDuration {
+    SignedDuration {
+        duration: duration_from_secs(frames.unsigned_abs() as f64 / fps),
+        negative: frames < 0,
+    }
+}
+
+#[derive(Args, Default, Debug, Clone)]
 #[group(required = false, multiple = true)]
-pub struct DurationRange {
+pub struct DurationRangeArgs {
     /// The duration to start working from.
     ///
     /// When shifting or doing any type of editing work, start working
@@ -232,13 +512,11 @@ pub struct DurationRange {
     pub end: Option<Duration>,
 }
 
-impl DurationRange {
-    pub fn contains(&self, duration: &Duration) -> bool {
-        match (&self.start, &self.end) {
-            (Some(start), Some(end)) => duration >= start && duration <= end,
-            (Some(start), None) => duration >= start,
-            (None, Some(end)) => duration <= end,
-            (None, None) => true,
+impl From<DurationRangeArgs> for DurationRange {
+    fn from(args: DurationRangeArgs) -> Self {
+        Self {
+            start: args.start,
+            end: args.end,
         }
     }
 }
@@ -289,10 +567,300 @@ impl InPlaceOutputArgs {
     }
 }
 
+#[derive(Args, Default, Debug)]
+pub struct ParseArgs {
+    /// Reject subtitle files that are malformed instead of being lenient.
+    ///
+    /// This rejects dialogue that ends before it starts and .ass events
+    /// with fewer fields than their format, which are otherwise only
+    /// warnings.
+    ///
+    /// For .ass files this also rejects files with duplicate major
+    /// sections and warns when the `[V4+ Styles]` section is missing.
+    #[arg(long, verbatim_doc_comment)]
+    pub strict: bool,
+    /// Turns off `--strict` when the configuration file turns it on.
+    #[arg(long, overrides_with = "strict", hide = true)]
+    pub no_strict: bool,
+}
+
+impl ParseArgs {
+    fn options(&self) -> ParseOptions {
+        ParseOptions {
+            strict: self.strict,
+            ..Default::default()
+        }
+    }
+
+    /// Parses an .ass file without its events, for when only the styles are needed.
+    fn parse_ass_styles(&self, contents: &str) -> anyhow::Result<Ass> {
+        let options = ParseOptions {
+            skip_events: true,
+            ..self.options()
+        };
+        let ass = Ass::parse_with(contents, options)?;
+        if self.strict && !ass.has_styles() {
+            eprintln!("warning: missing [V4+ Styles] section");
+        }
+        Ok(ass)
+    }
+
+    fn parse_ass(&self, contents: &str) -> anyhow::Result<Ass> {
+        let (ass, warnings) = Ass::parse_with_warnings(contents, self.options())?;
+        if self.strict && !ass.has_styles() {
+            eprintln!("warning: missing [V4+ Styles] section");
+        }
+        // Strict parsing already rejects these
+        let invalid = ass.events().filter(|e| !e.is_valid()).count();
+        if invalid > 0 {
+            eprintln!("warning: {invalid} event(s) end before they start");
+        }
+        // Missing fields are the only thing lenient parsing warns about for now
+        if !warnings.is_empty() {
+            eprintln!("warning: {} event(s) are missing fields", warnings.len());
+        }
+        Ok(ass)
+    }
+
+    fn check_dialogue(&self, dialogue: &[srt::Dialogue]) -> anyhow::Result<()> {
+        for d in dialogue.iter().filter(|d| !d.is_valid()) {
+            if self.strict {
+                anyhow::bail!("dialogue {} ends before it starts", d.position);
+            }
+            eprintln!("warning: dialogue {} ends before it starts", d.position);
+        }
+        Ok(())
+    }
+
+    fn parse_srt(&self, contents: &str) -> anyhow::Result<Vec<srt::Dialogue>> {
+        let dialogue = srt::load_from_string(contents)?;
+        self.check_dialogue(&dialogue)?;
+        Ok(dialogue)
+    }
+
+    fn parse_vtt(&self, contents: &str) -> anyhow::Result<Vec<srt::Dialogue>> {
+        let dialogue = vtt::load_from_string(contents)?;
+        self.check_dialogue(&dialogue)?;
+        Ok(dialogue)
+    }
+
+    /// Parses any supported subtitle format into dialogue, see [`crate::parse_dialogue`].
+    fn parse_dialogue(&self, contents: &str) -> anyhow::Result<Vec<srt::Dialogue>> {
+        let dialogue = crate::parse_dialogue(contents, self.options())?;
+        // Strict parsing already rejects these
+        self.check_dialogue(&dialogue)?;
+        Ok(dialogue)
+    }
+}
+
+#[derive(Args, Default, Debug)]
+pub struct WriteArgs {
+    /// Separate the fields of .ass `Format:` lines with `,` instead of `, `.
+    ///
+    /// Some tools only accept one or the other.
+    #[arg(long, verbatim_doc_comment)]
+    pub compact_format: bool,
+    /// Turns off `--compact-format` when the configuration file turns it on.
+    #[arg(long, overrides_with = "compact_format", hide = true)]
+    pub no_compact_format: bool,
+    /// Start the output with a UTF-8 byte order mark.
+    ///
+    /// Some players on Windows need it to show non-ASCII text.
+    #[arg(long, verbatim_doc_comment)]
+    pub bom: bool,
+    /// Turns off `--bom` when the configuration file turns it on.
+    #[arg(long, overrides_with = "bom", hide = true)]
+    pub no_bom: bool,
+    /// The separator before the milliseconds of .srt timestamps [default: comma]
+    ///
+    /// The standard is a comma but some players and tools
+    /// only accept a dot.
+    #[arg(long, value_enum, value_name = "SEP", verbatim_doc_comment)]
+    pub srt_sep: Option<SrtSeparator>,
+}
+
+impl WriteArgs {
+    fn options(&self) -> WriteOptions {
+        WriteOptions {
+            compact_format: self.compact_format,
+            bom: self.bom,
+        }
+    }
+
+    fn srt_options(&self) -> srt::WriteOptions {
+        srt::WriteOptions {
+            bom: self.bom,
+            dot_separator: self.srt_sep == Some(SrtSeparator::Dot),
+        }
+    }
+
+    fn vtt_options(&self) -> vtt::WriteOptions {
+        vtt::WriteOptions { bom: self.bom }
+    }
+}
+
+/// Returns the plain text of the dialogue events, e.g. for [`detect_script`].
+fn dialogue_texts(ass: &Ass) -> Vec<String> {
+    ass.events()
+        .filter(|e| e.kind.is_dialogue())
+        .map(|e| e.plain_text())
+        .collect()
+}
+
+/// Formats a duration as `HH:MM:SS.mmm` for display purposes.
+fn format_timestamp(d: &Duration) -> String {
+    let seconds = d.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60,
+        d.subsec_millis()
+    )
+}
+
+/// Inputs at least this big show their progress while being processed.
+const LARGE_INPUT: usize = 1024 * 1024;
+
+/// Reports the progress of long operations on stderr, e.g. `[2/3] cleaning up`.
+///
+/// Nothing is shown unless stderr is a terminal. The line is cleared when
+/// this is dropped so that it doesn't get mixed up with other output.
+struct Progress {
+    current: usize,
+    total: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(total: usize, enabled: bool) -> Self {
+        Self {
+            current: 0,
+            total,
+            enabled: enabled && stderr().is_terminal(),
+        }
+    }
+
+    /// Returns a progress that's only shown if the input is large enough to take a while.
+    fn for_input(contents: &str, total: usize) -> Self {
+        Self::new(total, contents.len() >= LARGE_INPUT)
+    }
+
+    /// Moves on to the next step with the given description.
+    fn step(&mut self, message: impl Display) {
+        self.current += 1;
+        if self.enabled {
+            eprint!("\r\x1b[K[{}/{}] {message}", self.current, self.total);
+            let _ = stderr().flush();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.enabled {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+#[derive(Args, Default, Debug)]
+pub struct PreviewArgs {
+    /// Print the first N dialogue lines after modification to stderr.
+    ///
+    /// This does not affect the saved output.
+    #[arg(long, value_name = "N", verbatim_doc_comment)]
+    pub preview: Option<usize>,
+    /// Only process and output the first N dialogue lines.
+    ///
+    /// This is useful for quickly trying out options on large files.
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+}
+
+impl PreviewArgs {
+    fn print_cue(start: &Duration, end: &Duration, text: &str) {
+        eprintln!("{} --> {}", format_timestamp(start), format_timestamp(end));
+        for line in text.lines() {
+            eprintln!("  {line}");
+        }
+    }
+
+    fn limit_ass(&self, ass: &mut Ass) {
+        if let Some(limit) = self.limit {
+            ass.truncate_events(limit);
+        }
+    }
+
+    fn limit_srt(&self, dialogue: &mut Vec<srt::Dialogue>) {
+        if let Some(limit) = self.limit {
+            dialogue.truncate(limit);
+        }
+    }
+
+    fn preview_ass(&self, ass: &Ass) {
+        if let Some(count) = self.preview {
+            for event in ass.events().filter(|e| e.kind.is_dialogue()).take(count) {
+                Self::print_cue(&event.start, &event.end, &event.plain_text());
+            }
+        }
+    }
+
+    fn preview_srt(&self, dialogue: &[srt::Dialogue]) {
+        if let Some(count) = self.preview {
+            for d in dialogue.iter().take(count) {
+                Self::print_cue(&d.start, &d.end, &d.text);
+            }
+        }
+    }
+}
+
+#[derive(Args, Default, Debug)]
+pub struct BlankCueArgs {
+    /// Keep dialogue lines that have no visible text.
+    ///
+    /// By default, dialogue lines that are empty or only have
+    /// whitespace and tags are removed from the output since
+    /// players show nothing for them and .srt files can't
+    /// represent them.
+    #[arg(long, verbatim_doc_comment)]
+    pub keep_blank_cues: bool,
+    /// Turns off `--keep-blank-cues` when the configuration file turns it on.
+    #[arg(long, overrides_with = "keep_blank_cues", hide = true)]
+    pub no_keep_blank_cues: bool,
+}
+
+impl BlankCueArgs {
+    fn apply_ass(&self, ass: &mut Ass) {
+        if !self.keep_blank_cues {
+            ass.retain_events(|e| !e.kind.is_dialogue() || !e.is_blank());
+        }
+    }
+
+    fn apply_srt(&self, dialogue: &mut Vec<srt::Dialogue>) {
+        if self.keep_blank_cues {
+            return;
+        }
+        let len = dialogue.len();
+        dialogue.retain(|d| !d.is_blank());
+        if dialogue.len() != len {
+            for (index, d) in dialogue.iter_mut().enumerate() {
+                d.position = (index + 1) as u32;
+            }
+        }
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct ConvertArgs {
     #[arg(long, default_value_t = ConvertFormat::Auto, value_enum)]
     pub to: ConvertFormat,
+    /// The format of the input file if it can't be detected.
+    ///
+    /// Files with a .json extension are always read as JSON.
+    #[cfg(feature = "serde")]
+    #[arg(long, value_enum)]
+    pub from: Option<InputFormat>,
     /// The subtitle file to convert to.
     ///
     /// If `-` is given, then it's interpreted as stdin.
@@ -308,9 +876,44 @@ pub struct ConvertArgs {
     /// the file to stdout.
     #[arg(short, long, verbatim_doc_comment)]
     pub output: Option<PathBuf>,
+    /// Keep `\fs` font sizes as `<font size>` tags when converting .ass to .srt.
+    ///
+    /// Only sizes set at the start of a line are kept since
+    /// changes partway through a line can't be represented.
+    #[arg(long, verbatim_doc_comment)]
+    pub font_size: bool,
+    /// Only keep the events when converting .ass to .ass.
+    ///
+    /// The script info is replaced with a minimal stub and
+    /// the styles are removed. This is useful for comparing
+    /// the dialogue between versions of a file.
+    #[arg(long, verbatim_doc_comment)]
+    pub events_only: bool,
+    #[command(flatten)]
+    pub blank: BlankCueArgs,
+    #[command(flatten)]
+    pub parse: ParseArgs,
+    #[command(flatten)]
+    pub write: WriteArgs,
 }
 
 impl ConvertArgs {
+    /// Fills in the options from the configuration file that weren't given explicitly.
+    pub fn apply_config(&mut self, config: &ConvertConfig) {
+        if self.to == ConvertFormat::Auto && self.output.is_none() {
+            if let Some(to) = config.to {
+                self.to = to;
+            }
+        }
+        self.parse.strict |= !self.parse.no_strict && config.strict.unwrap_or_default();
+        self.write.compact_format |=
+            !self.write.no_compact_format && config.compact_format.unwrap_or_default();
+        self.write.bom |= !self.write.no_bom && config.bom.unwrap_or_default();
+        self.write.srt_sep = self.write.srt_sep.or(config.srt_sep);
+        self.blank.keep_blank_cues |=
+            !self.blank.no_keep_blank_cues && config.keep_blank_cues.unwrap_or_default();
+    }
+
     /// Returns the output filename.
     ///
     /// If the command line arguments are invalid then this exits.
@@ -326,10 +929,16 @@ impl ConvertArgs {
             .exit();
         }
 
-        if !matches!(
-            self.file.extension().and_then(|s| s.to_str()),
-            Some("ass" | "srt" | "vtt")
-        ) {
+        #[cfg(feature = "serde")]
+        if self.file.extension().is_some_and(|ext| ext == "json") {
+            self.from = Some(InputFormat::Json);
+        }
+        #[cfg(feature = "serde")]
+        let json_input = self.from.is_some();
+        #[cfg(not(feature = "serde"))]
+        let json_input = false;
+
+        if !json_input && SubtitleFormat::from_path(&self.file).is_none() {
             Cli::command()
                 .error(
                     clap::error::ErrorKind::ValueValidation,
@@ -341,10 +950,9 @@ impl ConvertArgs {
         match self.output.take() {
             Some(path) => {
                 if self.to == ConvertFormat::Auto {
-                    self.to = match path.extension().and_then(|s| s.to_str()) {
-                        Some("ass") => ConvertFormat::Ass,
-                        Some("srt") => ConvertFormat::Srt,
-                        _ => Cli::command()
+                    self.to = match ConvertFormat::from_path(&path) {
+                        Some(format) => format,
+                        None => Cli::command()
                             .error(
                                 clap::error::ErrorKind::ValueValidation,
                                 "could not determine output format for subtitle, use --to",
@@ -360,11 +968,7 @@ impl ConvertArgs {
                     return InputOutputLocation::Stdio;
                 }
 
-                let extension = match self.to {
-                    ConvertFormat::Ass => "ass",
-                    ConvertFormat::Srt => "srt",
-                    ConvertFormat::Auto => unreachable!(),
-                };
+                let extension = self.to.extension();
                 let mut output = PathBuf::new();
                 if let Some(filename) = self.file.file_stem() {
                     let mut filename = filename.to_os_string();
@@ -384,111 +988,493 @@ impl ConvertArgs {
         }
     }
 
+    /// Converts the JSON model into the output format.
+    #[cfg(feature = "serde")]
+    fn convert_json(&self, output: &InputOutputLocation, contents: &str) -> anyhow::Result<()> {
+        let subtitles = Subtitles::from_json(contents)?;
+        let into_ass = |subtitles: Subtitles| {
+            let mut ass = subtitles.into_ass();
+            self.blank.apply_ass(&mut ass);
+            ass
+        };
+        match self.to {
+            ConvertFormat::Srt => {
+                let mut dialogue = match subtitles {
+                    Subtitles::Srt { dialogue } => dialogue,
+                    subtitles => ass_to_dialogue(subtitles.into_ass(), self.font_size),
+                };
+                self.blank.apply_srt(&mut dialogue);
+                output.save_srt(&dialogue, &self.write.srt_options())
+            }
+            ConvertFormat::Ass => output.save_ass(&into_ass(subtitles), &self.write.options()),
+            ConvertFormat::Table => output.save_string(&into_ass(subtitles).events_table()),
+            ConvertFormat::Json => output.save_json(&subtitles),
+            ConvertFormat::Auto => unreachable!(),
+        }
+    }
+
     /// Runs the conversion utility.
     pub fn run(mut self) -> anyhow::Result<()> {
         let output = self.validate_output();
-        let input = InputOutputLocation::new(self.file);
+        let input = InputOutputLocation::new(std::mem::take(&mut self.file));
         let contents = input.read_as_string()?;
-        match SubtitleFormat::detect(&contents) {
-            Some(SubtitleFormat::Ass) => {
-                let ass = contents.parse::<Ass>()?;
+        let mut progress = Progress::for_input(&contents, 1);
+        progress.step("converting");
+        #[cfg(feature = "serde")]
+        if self.from.is_some() {
+            return self.convert_json(&output, &contents);
+        }
+
+        match detect_format(&contents)? {
+            SubtitleFormat::Ass => {
+                let mut ass = self.parse.parse_ass(&contents)?;
+                self.blank.apply_ass(&mut ass);
                 match self.to {
                     ConvertFormat::Srt => {
-                        let dialogue = ass
-                            .sections
-                            .into_iter()
-                            .filter_map(|s| s.try_into_events().ok())
-                            .flat_map(|e| {
-                                e.events
-                                    .into_iter()
-                                    .filter(|e| e.kind.is_dialogue())
-                                    .enumerate()
-                            })
-                            .map(|(idx, e)| srt::Dialogue {
-                                position: idx as u32 + 1,
-                                start: e.start,
-                                end: e.end,
-                                text: clean_ass_text(&e.text),
-                            })
-                            .collect::<Vec<_>>();
-
-                        output.save_srt(&dialogue)
+                        // Removing the tags can leave lines without any text
+                        let mut dialogue = ass_to_dialogue(ass, self.font_size);
+                        self.blank.apply_srt(&mut dialogue);
+                        output.save_srt(&dialogue, &self.write.srt_options())
                     }
                     ConvertFormat::Ass => {
                         // .ass -> .ass is a bit weird, but I guess
                         // just run it through the parser to clean it up
-                        output.save_ass(&ass)
+                        let ass = if self.events_only {
+                            ass.events_only()
+                        } else {
+                            ass
+                        };
+                        output.save_ass(&ass, &self.write.options())
                     }
-                    _ => Ok(()),
+                    ConvertFormat::Table => output.save_string(&ass.events_table()),
+                    #[cfg(feature = "serde")]
+                    ConvertFormat::Json => output.save_json(&Subtitles::from_ass(&ass)),
+                    ConvertFormat::Auto => unreachable!(),
                 }
             }
-            Some(SubtitleFormat::Srt) => {
-                let dialogue = srt::load_from_string(&contents)?;
+            SubtitleFormat::Srt => {
+                let mut dialogue = self.parse.parse_srt(&contents)?;
+                self.blank.apply_srt(&mut dialogue);
                 match self.to {
-                    ConvertFormat::Srt => output.save_srt(&dialogue),
+                    ConvertFormat::Srt => output.save_srt(&dialogue, &self.write.srt_options()),
                     ConvertFormat::Ass => {
                         let ass = Ass::from_srt(dialogue);
-                        output.save_ass(&ass)
+                        output.save_ass(&ass, &self.write.options())
                     }
-                    _ => Ok(()),
+                    ConvertFormat::Table => {
+                        output.save_string(&Ass::from_srt(dialogue).events_table())
+                    }
+                    #[cfg(feature = "serde")]
+                    ConvertFormat::Json => output.save_json(&Subtitles::from_dialogue(dialogue)),
+                    ConvertFormat::Auto => unreachable!(),
                 }
             }
-            Some(SubtitleFormat::Vtt) => {
-                let dialogue = vtt::load_from_string(&contents)?;
+            SubtitleFormat::Vtt => {
+                let mut dialogue = self.parse.parse_vtt(&contents)?;
+                self.blank.apply_srt(&mut dialogue);
                 match self.to {
-                    ConvertFormat::Srt => output.save_srt(&dialogue),
+                    ConvertFormat::Srt => output.save_srt(&dialogue, &self.write.srt_options()),
                     ConvertFormat::Ass => {
                         let ass = Ass::from_srt(dialogue);
-                        output.save_ass(&ass)
+                        output.save_ass(&ass, &self.write.options())
                     }
-                    _ => Ok(()),
+                    ConvertFormat::Table => {
+                        output.save_string(&Ass::from_srt(dialogue).events_table())
+                    }
+                    #[cfg(feature = "serde")]
+                    ConvertFormat::Json => output.save_json(&Subtitles::from_dialogue(dialogue)),
+                    ConvertFormat::Auto => unreachable!(),
                 }
             }
-            _ => anyhow::bail!("Somehow got an invalid input file"),
         }
     }
 }
 
 #[derive(Args, Debug)]
 pub struct InfoArgs {
-    /// The subtitle file to get information for.
+    /// The subtitle files to get information for.
+    ///
+    /// If more than one file is given then the information for each
+    /// file is printed followed by a summary of all of them.
     ///
     /// If `-` is given, then it's interpreted as stdin.
-    pub file: PathBuf,
-}
-
+    #[arg(required_unless_present = "files_from", verbatim_doc_comment)]
+    pub files: Vec<PathBuf>,
+    /// Also read the files to get information for from LIST, one path per line.
+    ///
+    /// This is meant for shell pipelines, e.g. with `find` or `fd`.
+    /// Blank lines and lines starting with `#` are skipped. If `-`
+    /// is given, then the list is read from stdin.
+    #[arg(long, value_name = "LIST", verbatim_doc_comment)]
+    pub files_from: Option<PathBuf>,
+    #[command(flatten)]
+    pub parse: ParseArgs,
+    /// Only print the style names, one per line.
+    ///
+    /// The names of every file are printed without any headers.
+    /// Subtitle formats without styles print nothing.
+    #[arg(long, verbatim_doc_comment)]
+    pub list_styles: bool,
+    /// Only print the styles, skipping everything about the dialogue.
+    ///
+    /// The dialogue isn't parsed at all, which is much faster
+    /// for large files. Subtitle formats without styles print
+    /// nothing.
+    #[arg(long, conflicts_with = "list_styles", verbatim_doc_comment)]
+    pub styles_only: bool,
+    /// Also print a histogram of how long the dialogue is shown for.
+    #[arg(long, conflicts_with_all = ["list_styles", "styles_only"])]
+    pub stats: bool,
+    /// Also guess the frame rate of the video the dialogue was timed to.
+    ///
+    /// This looks at how well the timings line up with the
+    /// frames of common frame rates, which is useful to know
+    /// before scaling the timings to another frame rate.
+    #[arg(long, conflicts_with_all = ["list_styles", "styles_only"], verbatim_doc_comment)]
+    pub detect_fps: bool,
+    /// Describe the file in a few sentences instead of printing the details.
+    #[arg(long, conflicts_with_all = ["list_styles", "styles_only"])]
+    pub explain: bool,
+    /// Also print the text encoding of the file and whether it has a BOM.
+    ///
+    /// This is printed before the file is parsed, so it's shown
+    /// even for files that can't be read because of the encoding.
+    #[arg(long, verbatim_doc_comment)]
+    pub report_encoding: bool,
+    /// Print colours and text effects without terminal escape codes.
+    ///
+    /// This is the default when the output isn't a terminal.
+    #[arg(long, verbatim_doc_comment)]
+    pub no_color: bool,
+}
+
+/// Returns how many groups of dialogue are shown at the exact same time with
+/// different text, e.g. a top and bottom line stacked on purpose.
+///
+/// These are the lines that `cleanup --merge-simultaneous` would combine.
+fn stacked_groups<'a>(dialogue: impl Iterator<Item = (Duration, Duration, &'a str)>) -> usize {
+    let mut groups: HashMap<(Duration, Duration), HashSet<&str>> = HashMap::new();
+    for (start, end, text) in dialogue {
+        groups.entry((start, end)).or_default().insert(text);
+    }
+    groups.values().filter(|texts| texts.len() > 1).count()
+}
+
+/// Returns how many dialogue events use each style, in the order the styles are first used.
+fn dialogue_by_style(subs: &Ass) -> Vec<(&str, usize)> {
+    let mut counter: Vec<(&str, usize)> = Vec::new();
+    for event in subs.events().filter(|e| e.kind.is_dialogue()) {
+        match counter.iter_mut().find(|(name, _)| *name == event.style) {
+            Some((_, count)) => *count += 1,
+            None => counter.push((&event.style, 1)),
+        }
+    }
+    counter
+}
+
+/// Returns the paths of a file list, see `info --files-from`.
+///
+/// Every line is a path, apart from blank lines and lines starting with `#`.
+fn parse_file_list(contents: &str) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// A plain English description of a file, see `info --explain`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Description {
+    format: SubtitleFormat,
+    /// The `ScriptType` of .ass files.
+    version: Option<String>,
+    /// The number of styles of .ass files.
+    styles: Option<usize>,
+    dialogue: usize,
+    /// When the first dialogue starts and the last one ends.
+    span: Option<(Duration, Duration)>,
+    script: Option<Script>,
+}
+
+impl Description {
+    fn new(
+        format: SubtitleFormat,
+        timings: &[(Duration, Duration)],
+        script: Option<Script>,
+    ) -> Self {
+        let span = timings
+            .iter()
+            .map(|(start, _)| *start)
+            .min()
+            .zip(timings.iter().map(|(_, end)| *end).max());
+        Self {
+            format,
+            version: None,
+            styles: None,
+            dialogue: timings.len(),
+            span,
+            script,
+        }
+    }
+}
+
+impl Display for Description {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn plural(count: usize, singular: &str, plural: &str) -> String {
+            match count {
+                0 => format!("no {plural}"),
+                1 => format!("1 {singular}"),
+                _ => format!("{count} {plural}"),
+            }
+        }
+        fn time(d: Duration) -> String {
+            let seconds = d.as_secs();
+            format!(
+                "{}:{:02}:{:02}",
+                seconds / 3600,
+                (seconds / 60) % 60,
+                seconds % 60
+            )
+        }
+
+        write!(f, "This is a {}", self.format.name())?;
+        match self.version.as_deref() {
+            Some(version) if !version.is_empty() => write!(f, " {version} file")?,
+            _ => f.write_str(" file")?,
+        }
+        match self.styles {
+            Some(styles) => write!(
+                f,
+                " with {} and {}",
+                plural(styles, "style", "styles"),
+                plural(self.dialogue, "dialogue line", "dialogue lines")
+            )?,
+            None => write!(
+                f,
+                " with {}",
+                plural(self.dialogue, "dialogue line", "dialogue lines")
+            )?,
+        }
+        if let Some((start, end)) = self.span {
+            write!(f, " spanning {} to {}", time(start), time(end))?;
+        }
+        f.write_str(".")?;
+        match self.script {
+            Some(Script::Other) | None => Ok(()),
+            Some(script @ (Script::Latin | Script::Cyrillic | Script::Arabic)) => {
+                write!(f, " Most of the text is written in {script} script.")
+            }
+            Some(script) => write!(f, " Most of the text is written in {script}."),
+        }
+    }
+}
+
+/// The text encoding details of a file, see `info --report-encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EncodingReport {
+    /// The name of the encoding.
+    encoding: &'static str,
+    /// Whether the encoding is supported.
+    supported: bool,
+    /// Whether the file starts with a BOM.
+    bom: bool,
+    /// How many BOMs there are after the start of the file.
+    stray_boms: usize,
+    /// Whether the file has CRLF line endings.
+    crlf: bool,
+}
+
+impl EncodingReport {
+    fn detect(bytes: &[u8]) -> Self {
+        let mut report = Self {
+            encoding: "UTF-8",
+            supported: true,
+            bom: false,
+            stray_boms: 0,
+            crlf: false,
+        };
+        if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+            report.encoding = if bytes[0] == 0xFF {
+                "UTF-16LE"
+            } else {
+                "UTF-16BE"
+            };
+            report.supported = false;
+            report.bom = true;
+            return report;
+        }
+
+        report.bom = bytes.starts_with(&crate::UTF8_BOM);
+        match std::str::from_utf8(bytes) {
+            Ok(text) => {
+                if text.is_ascii() {
+                    report.encoding = "ASCII";
+                }
+                report.stray_boms = text.matches('\u{feff}').count() - usize::from(report.bom);
+                report.crlf = text.contains("\r\n");
+            }
+            Err(_) => {
+                report.encoding = "unknown";
+                report.supported = false;
+            }
+        }
+        report
+    }
+
+    fn print(&self) {
+        println!("Encoding:");
+        if self.supported {
+            println!("  Detected: {}", self.encoding);
+        } else {
+            println!("  Detected: {} (only UTF-8 is supported)", self.encoding);
+        }
+        println!("  BOM: {}", if self.bom { "yes" } else { "no" });
+        if self.stray_boms > 0 {
+            println!("  Stray BOMs: {} (removed)", self.stray_boms);
+        }
+        if self.supported {
+            let endings = if self.crlf { "CRLF" } else { "LF" };
+            println!("  Line Endings: {endings}");
+        }
+        println!();
+    }
+}
+
+/// How many colours the terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorSupport {
+    /// No escape codes at all.
+    None,
+    /// The 16 basic ANSI colours.
+    Ansi16,
+    /// The 256 colour xterm palette.
+    Ansi256,
+    /// 24-bit colour.
+    TrueColor,
+}
+
+/// The default xterm values of the 16 basic ANSI colours, in palette order.
+const ANSI_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// The channel values used by the 6x6x6 colour cube of the 256 colour palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn colour_distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).unsigned_abs().pow(2);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Returns the index of the closest colour in the 16 colour palette.
+fn nearest_ansi16(rgb: RgbColor) -> u8 {
+    let target = (rgb.0, rgb.1, rgb.2);
+    (0..16)
+        .min_by_key(|&i| colour_distance(ANSI_PALETTE[i], target))
+        .unwrap_or_default() as u8
+}
+
+/// Returns the index of the closest colour in the 256 colour palette.
+///
+/// Only the colour cube and the grayscale ramp are considered since the
+/// first 16 colours vary between terminals.
+fn nearest_ansi256(rgb: RgbColor) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(c))
+            .unwrap_or_default()
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+    let cube_index = 16 + 36 * r + 6 * g + b;
+
+    // The grayscale ramp goes from 8 to 238 in steps of 10
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + gray_step * 10;
+
+    let target = (rgb.0, rgb.1, rgb.2);
+    if colour_distance((gray, gray, gray), target) < colour_distance(cube, target) {
+        232 + gray_step
+    } else {
+        cube_index as u8
+    }
+}
+
+impl ColorSupport {
+    /// Detects the colour support of the terminal from the environment.
+    fn detect() -> Self {
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        let term = std::env::var("TERM").unwrap_or_default();
+        if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// Returns the closest colour that the terminal can display.
+    fn color(self, rgb: RgbColor) -> Option<Color> {
+        match self {
+            Self::None => None,
+            // The first 16 colours of the 256 colour palette are the basic ANSI colours
+            Self::Ansi16 => Ansi256Color(nearest_ansi16(rgb))
+                .into_ansi()
+                .map(Color::from),
+            Self::Ansi256 => Some(Ansi256Color(nearest_ansi256(rgb)).into()),
+            Self::TrueColor => Some(rgb.into()),
+        }
+    }
+}
+
 struct ColourDisplay {
     name: &'static str,
     colour: Colour,
     spaced: bool,
+    support: ColorSupport,
 }
 
 impl ColourDisplay {
-    fn new(name: &'static str, colour: Colour) -> Self {
+    fn new(name: &'static str, colour: Colour, support: ColorSupport) -> Self {
         Self {
             name,
             colour,
             spaced: false,
+            support,
         }
     }
 
-    fn spaced(name: &'static str, colour: Colour) -> Self {
+    fn spaced(name: &'static str, colour: Colour, support: ColorSupport) -> Self {
         Self {
             name,
             colour,
             spaced: true,
+            support,
         }
     }
 
     fn proper_background(&self) -> RgbColor {
-        // Contrast ratio is defined as L1 + 0.05 / L2 + 0.05
-        // L2 is the relative luminance of the background colour
-        // L1 is the relative luminance of the actual text colour
         // For simplicity the background colour can only be white/black
-        let l1 = self.colour.relative_luminance();
-        let l2 = Colour::BLACK.relative_luminance();
-        let cr = (l1 + 0.05) / (l2 + 0.05);
-        if cr >= 3.0 {
+        if self.colour.contrast_ratio(&Colour::BLACK) >= 3.0 {
             RgbColor(0, 0, 0)
         } else {
             RgbColor(255, 255, 255)
@@ -502,13 +1488,13 @@ impl ColourDisplay {
 
 impl std::fmt::Display for ColourDisplay {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let style = AnsiStyle::new()
+            .fg_color(self.support.color(self.as_rgb()))
+            .bg_color(self.support.color(self.proper_background()));
+        write!(f, "{style}{}{style:#}", self.name)?;
         write!(
             f,
-            "{}{}{}{}{}({})",
-            self.as_rgb().render_fg(),
-            self.proper_background().render_bg(),
-            self.name,
-            Reset,
+            "{}({})",
             if self.spaced { "  " } else { " " },
             self.colour.to_hex()
         )
@@ -516,36 +1502,61 @@ impl std::fmt::Display for ColourDisplay {
 }
 
 impl InfoArgs {
-    fn info_for_ass(&self, subs: Ass) {
+    /// Returns the colour support to use for the output.
+    fn color_support(&self) -> ColorSupport {
+        if self.no_color || !stdout().is_terminal() {
+            ColorSupport::None
+        } else {
+            ColorSupport::detect()
+        }
+    }
+
+    fn print_styles(&self, subs: &Ass) {
+        let color = self.color_support();
         // Maybe at some point PlayResX/Y can be there too but
         // there's no point since like.. you can just see it in the file easily
         if let Some(section) = subs.sections.iter().find_map(|s| s.as_styles()) {
             println!("Styles:");
-            for style in &section.styles {
+            if section.styles().next().is_none() {
+                println!("  No styles defined");
+            }
+            for style in section.styles() {
                 println!("  {}:", style.name);
                 println!("    Font: {} at {}px", style.font_name, style.font_size);
                 // Colors: #RRGGBBAA
                 //   Primary (#aabbccdd)  Secondary  (#aabbccdd)
                 //   Outline (#aabbccdd)  Background (#aabbccdd)
                 println!("    Colors: #RRGGBBAA");
-                let primary = ColourDisplay::new("Primary", style.primary_colour);
+                let primary = ColourDisplay::new("Primary", style.primary_colour, color);
+                let outline = ColourDisplay::new("Outline", style.outline_colour, color);
                 println!(
                     "      {}  {}\n      {}  {}",
                     primary,
-                    ColourDisplay::spaced("Secondary", style.secondary_colour),
-                    ColourDisplay::new("Outline", style.outline_colour),
-                    ColourDisplay::new("Background", style.background_colour)
+                    ColourDisplay::spaced("Secondary", style.secondary_colour, color),
+                    outline,
+                    ColourDisplay::new("Background", style.background_colour, color)
                 );
-                let sample = AnsiStyle::new()
-                    .effects(
-                        Effects::new()
-                            .set(Effects::BOLD, style.bold)
-                            .set(Effects::ITALIC, style.italic)
-                            .set(Effects::UNDERLINE, style.underline)
-                            .set(Effects::STRIKETHROUGH, style.striked),
-                    )
-                    .fg_color(Some(primary.as_rgb().into()));
+                let sample = if color == ColorSupport::None {
+                    AnsiStyle::new()
+                } else {
+                    AnsiStyle::new()
+                        .effects(
+                            Effects::new()
+                                .set(Effects::BOLD, style.bold)
+                                .set(Effects::ITALIC, style.italic)
+                                .set(Effects::UNDERLINE, style.underline)
+                                .set(Effects::STRIKETHROUGH, style.striked),
+                        )
+                        .fg_color(color.color(primary.as_rgb()))
+                        // The outline is drawn around the text, so the closest a
+                        // terminal can get is using it as the background
+                        .bg_color(color.color(outline.as_rgb()))
+                };
                 println!("    Sample Text: {sample}Hello, こんにちは{sample:#}");
+                println!(
+                    "    Contrast: {:.1}:1 against the outline",
+                    style.primary_colour.contrast_ratio(&style.outline_colour)
+                );
                 //  Text Properties:
                 //    Scale: (100%, 100%)  Spacing: 0px  Angle: 0.0
                 //  Border Style: 4px Outline [with 4px drop shadow]
@@ -591,255 +1602,1936 @@ impl InfoArgs {
             }
 
             println!();
+        } else {
+            println!("Styles:\n  No styles defined\n");
         }
+    }
 
-        let mut counter = HashMap::new();
-        for event in subs.events().filter(|s| s.kind.is_dialogue()) {
-            counter
-                .entry(event.style.as_str())
-                .and_modify(|x| *x += 1)
-                .or_insert(1);
-        }
+    fn info_for_ass(&self, subs: Ass) {
+        self.print_styles(&subs);
+        let counter = dialogue_by_style(&subs);
         println!("Dialogue:");
-        for (style, count) in counter.iter() {
-            println!("  {style}: {count}");
+        for (style, count) in &counter {
+            if subs.style(style).is_some() {
+                println!("  {style}: {count}");
+            } else {
+                println!("  {style}: {count} (undefined)");
+            }
         }
-        let sum = counter.values().sum::<i32>();
+        let sum = counter.iter().map(|(_, count)| count).sum::<usize>();
         println!("  Total: {sum}");
+        let texts = dialogue_texts(&subs);
+        if let Some(script) = detect_script(texts.iter().map(String::as_str)) {
+            println!("  Script: {script}");
+        }
+        let stacked = stacked_groups(
+            subs.events()
+                .filter(|e| e.kind.is_dialogue())
+                .map(|e| (e.start, e.end, e.text.as_str())),
+        );
+        Self::print_stacked(stacked);
+
+        let undefined = subs.undefined_styles();
+        if !undefined.is_empty() {
+            println!("\nUndefined Styles:");
+            for (style, count) in undefined {
+                println!("  {style}: used by {count} event(s)");
+            }
+        }
     }
 
     fn simple_info(&self, dialogue: &[srt::Dialogue]) {
-        println!("Dialogue:\n  Total: {}", dialogue.len())
+        if !self.list_styles {
+            println!("Dialogue:\n  Total: {}", dialogue.len());
+            if let Some(script) = detect_script(dialogue.iter().map(|d| d.text.as_str())) {
+                println!("  Script: {script}");
+            }
+            let stacked =
+                stacked_groups(dialogue.iter().map(|d| (d.start, d.end, d.text.as_str())));
+            Self::print_stacked(stacked);
+        }
     }
 
-    pub fn run(self) -> anyhow::Result<()> {
-        let input = InputOutputLocation::new(self.file.clone());
-        let contents = input.read_as_string()?;
-        let format = SubtitleFormat::detect(&contents);
-        match format {
-            Some(SubtitleFormat::Ass) => {
-                let subs = contents.parse()?;
-                self.info_for_ass(subs);
-                Ok(())
+    fn print_stacked(stacked: usize) {
+        if stacked > 0 {
+            println!("  Stacked: {stacked} group(s) share their timing, see --merge-simultaneous");
+        }
+    }
+
+    fn print_stats(mut durations: Vec<Duration>) {
+        const BUCKETS: [(&str, Duration); 4] = [
+            ("< 1s", Duration::from_secs(1)),
+            ("1-2s", Duration::from_secs(2)),
+            ("2-4s", Duration::from_secs(4)),
+            (">= 4s", Duration::MAX),
+        ];
+        const BAR_WIDTH: usize = 40;
+
+        println!("\nDurations:");
+        if durations.is_empty() {
+            println!("  No dialogue");
+            return;
+        }
+
+        let mut counts = [0usize; BUCKETS.len()];
+        for duration in &durations {
+            let index = BUCKETS.iter().position(|(_, upper)| duration < upper);
+            counts[index.unwrap_or(BUCKETS.len() - 1)] += 1;
+        }
+        let max = counts.iter().copied().max().unwrap_or_default();
+        for ((label, _), count) in BUCKETS.iter().zip(counts) {
+            let bar = "#".repeat(count * BAR_WIDTH / max);
+            println!("  {label:>5} {bar:<BAR_WIDTH$} {count}");
+        }
+
+        durations.sort_unstable();
+        let total = durations.iter().sum::<Duration>();
+        let mean = total / durations.len() as u32;
+        let middle = durations.len() / 2;
+        let median = if durations.len().is_multiple_of(2) {
+            (durations[middle - 1] + durations[middle]) / 2
+        } else {
+            durations[middle]
+        };
+        println!("  Mean: {:.3}s", mean.as_secs_f64());
+        println!("  Median: {:.3}s", median.as_secs_f64());
+    }
+
+    fn print_frame_rate(timings: &[Duration]) {
+        // Too few timings line up with every frame rate by chance
+        const MIN_TIMINGS: usize = 20;
+
+        println!("\nFrame rate:");
+        if timings.iter().filter(|t| !t.is_zero()).count() < MIN_TIMINGS {
+            println!("  Not enough dialogue to tell");
+            return;
+        }
+
+        let fps = |name: &str| {
+            COMMON_FRAME_RATES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map_or(0.0, |(_, fps)| *fps)
+        };
+        let result = frame_alignment(timings);
+        let (best, score) = result[0];
+        // Multiples of the best frame rate always line up at least as well
+        let runner_up = result.iter().skip(1).find(|(name, _)| {
+            let ratio = fps(name) / fps(best);
+            ratio < 1.0 || (ratio - ratio.round()).abs() > 1e-6
+        });
+        let margin = score - runner_up.map_or(0.0, |(_, s)| *s);
+        let confidence = if score >= 0.9 && margin >= 0.25 {
+            "high"
+        } else if score >= 0.6 && margin >= 0.1 {
+            "medium"
+        } else {
+            "low"
+        };
+        println!(
+            "  Best match: {best} ({:.0}% of timings, {confidence} confidence)",
+            score * 100.0
+        );
+        if let Some((name, score)) = runner_up {
+            println!("  Runner-up: {name} ({:.0}% of timings)", score * 100.0);
+        }
+    }
+
+    /// Prints the information for a single file.
+    ///
+    /// Returns the number of dialogue lines and when the last one ends.
+    fn info_for_file(&self, path: &Path) -> anyhow::Result<(usize, Duration)> {
+        let input = InputOutputLocation::new(path.to_path_buf());
+        let contents = if self.report_encoding {
+            let bytes = input.read_bytes()?;
+            EncodingReport::detect(&bytes).print();
+            crate::decode_file(bytes)?
+        } else {
+            input.read_as_string()?
+        };
+        let format = detect_format(&contents)?;
+        let (dialogue, durations) = match format {
+            SubtitleFormat::Ass => {
+                if self.styles_only {
+                    let subs = self.parse.parse_ass_styles(&contents)?;
+                    self.print_styles(&subs);
+                    return Ok((0, Duration::ZERO));
+                }
+                let subs = self.parse.parse_ass(&contents)?;
+                let (dialogue, durations): (Vec<_>, Vec<_>) = subs
+                    .events()
+                    .filter(|e| e.kind.is_dialogue())
+                    .map(|e| ((e.start, e.end), e.duration()))
+                    .unzip();
+                if self.explain {
+                    let texts = dialogue_texts(&subs);
+                    let script = detect_script(texts.iter().map(String::as_str));
+                    let mut description = Description::new(format, &dialogue, script);
+                    description.version = subs
+                        .sections
+                        .iter()
+                        .find_map(|s| s.as_script_info())
+                        .map(|s| s.version().to_owned());
+                    description.styles = Some(subs.styles().count());
+                    println!("{description}");
+                } else if self.list_styles {
+                    for style in subs.styles() {
+                        println!("{}", style.name);
+                    }
+                } else {
+                    self.info_for_ass(subs);
+                }
+                (dialogue, durations)
             }
-            Some(SubtitleFormat::Vtt) => {
-                let dialogue = vtt::load_from_string(&contents)?;
-                self.simple_info(&dialogue);
-                Ok(())
+            // There are no styles so there's nothing to print
+            _ if self.styles_only => return Ok((0, Duration::ZERO)),
+            SubtitleFormat::Vtt | SubtitleFormat::Srt => {
+                let dialogue = if format == SubtitleFormat::Vtt {
+                    self.parse.parse_vtt(&contents)?
+                } else {
+                    self.parse.parse_srt(&contents)?
+                };
+                let (timings, durations): (Vec<_>, Vec<_>) = dialogue
+                    .iter()
+                    .map(|d| ((d.start, d.end), d.duration()))
+                    .unzip();
+                if self.explain {
+                    let script = detect_script(dialogue.iter().map(|d| d.text.as_str()));
+                    println!("{}", Description::new(format, &timings, script));
+                } else {
+                    self.simple_info(&dialogue);
+                }
+                (timings, durations)
             }
-            Some(SubtitleFormat::Srt) => {
-                let dialogue = srt::load_from_string(&contents)?;
-                self.simple_info(&dialogue);
-                Ok(())
+        };
+
+        if self.stats {
+            Self::print_stats(durations);
+        }
+        if self.detect_fps {
+            let timings = dialogue
+                .iter()
+                .flat_map(|&(start, end)| [start, end])
+                .collect::<Vec<_>>();
+            Self::print_frame_rate(&timings);
+        }
+        let last_end = dialogue
+            .iter()
+            .map(|(_, end)| *end)
+            .max()
+            .unwrap_or_default();
+        Ok((dialogue.len(), last_end))
+    }
+
+    /// Adds the files from `--files-from` to the files given on the command line.
+    fn read_files_from(&mut self) -> anyhow::Result<()> {
+        let Some(list) = &self.files_from else {
+            return Ok(());
+        };
+        let contents = InputOutputLocation::new(list.clone())
+            .read_as_string()
+            .with_context(|| format!("could not read file list {}", list.display()))?;
+        self.files.extend(parse_file_list(&contents));
+        if list.as_os_str() == "-" && self.files.iter().any(|p| p.as_os_str() == "-") {
+            anyhow::bail!("stdin can't be used for both the file list and a subtitle file");
+        }
+        if self.files.is_empty() {
+            anyhow::bail!("no files were given in {}", list.display());
+        }
+        Ok(())
+    }
+
+    pub fn run(mut self) -> anyhow::Result<()> {
+        self.read_files_from()?;
+        let multiple = self.files.len() > 1;
+        let (mut total, mut length) = (0, Duration::ZERO);
+        // The output already shows how far along it is when it's on a terminal
+        let mut progress = Progress::new(self.files.len(), multiple && !stdout().is_terminal());
+        for (index, path) in self.files.iter().enumerate() {
+            progress.step(path.display());
+            // The style names are meant to be piped so they're printed on their own
+            if multiple && !self.list_styles {
+                if index != 0 {
+                    println!();
+                }
+                println!("==> {} <==", path.display());
             }
-            _ => Cli::command()
-                .error(
-                    clap::error::ErrorKind::ValueValidation,
-                    "could not recognize subtitle type",
-                )
-                .exit(),
+            let (count, last_end) = self
+                .info_for_file(path)
+                .with_context(|| format!("could not get information for {}", path.display()))?;
+            total += count;
+            length += last_end;
+        }
+
+        if multiple && !self.list_styles && !self.styles_only {
+            println!("\n==> Total <==");
+            println!("Files: {}", self.files.len());
+            println!("Dialogue: {total}");
+            println!("Duration: {}", format_timestamp(&length));
         }
+        Ok(())
     }
 }
 
 #[derive(Args, Debug)]
-pub struct ShiftArgs {
-    /// The subtitle file to shift
+pub struct CheckArgs {
+    /// The subtitle file to check.
     ///
     /// If `-` is given, then it's interpreted as stdin.
-    file: PathBuf,
-    #[command(flatten)]
-    output: InPlaceOutputArgs,
+    pub file: PathBuf,
     #[command(flatten)]
-    range: DurationRange,
-    /// Shift the timing of the subtitles by the given seconds
-    #[arg(long, required = true, value_parser = valid_duration, allow_negative_numbers = true)]
-    by: f32,
+    pub parse: ParseArgs,
 }
 
-impl ShiftArgs {
+impl CheckArgs {
     pub fn run(self) -> anyhow::Result<()> {
-        let output = self.output.resolve(&self.file)?;
-        let input = InputOutputLocation::new(self.file);
+        let input = InputOutputLocation::new(self.file.clone());
         let contents = input.read_as_string()?;
-        match SubtitleFormat::detect(&contents) {
-            Some(SubtitleFormat::Ass) => {
-                let mut subs = contents.parse::<Ass>()?;
-                subs.events_mut()
-                    .filter(|e| self.range.contains(&e.start))
-                    .for_each(|e| e.shift_by(self.by));
-                output.save_ass(&subs)
-            }
-            Some(SubtitleFormat::Srt) => {
-                let mut dialogue = srt::load_from_string(&contents)?;
-                dialogue
-                    .iter_mut()
-                    .filter(|d| self.range.contains(&d.start))
-                    .for_each(|d| d.shift_by(self.by));
-                output.save_srt(&dialogue)
-            }
-            Some(_) => Cli::command()
-                .error(
-                    clap::error::ErrorKind::ValueValidation,
-                    "unsupported subtitle format for this operation",
-                )
-                .exit(),
-            None => Cli::command()
-                .error(
-                    clap::error::ErrorKind::ValueValidation,
-                    "could not recognize subtitle type",
-                )
-                .exit(),
+        match detect_format(&contents)? {
+            SubtitleFormat::Ass => {
+                let subs = Ass::parse_with(&contents, self.parse.options())?;
+                let issues = subs.validate();
+                for issue in &issues {
+                    match (issue.event, issue.start) {
+                        (Some(index), Some(start)) => println!(
+                            "event {index} ({}): {}",
+                            format_timestamp(&start),
+                            issue.kind
+                        ),
+                        _ => println!("{}", issue.kind),
+                    }
+                }
+                if !issues.is_empty() {
+                    anyhow::bail!("found {} issue(s)", issues.len());
+                }
+                Ok(())
+            }
+            format => Err(unsupported_format(format, "check")),
         }
     }
 }
 
 #[derive(Args, Debug)]
-pub struct CleanupArgs {
-    /// The subtitle file to cleanup
+pub struct DiffArgs {
+    /// The original subtitle file.
     ///
     /// If `-` is given, then it's interpreted as stdin.
-    file: PathBuf,
-    #[command(flatten)]
-    output: InPlaceOutputArgs,
-    #[command(flatten)]
-    range: DurationRange,
-    /// Remove comment lines from the file (.ass only).
-    #[arg(long)]
-    comments: bool,
-    /// Remove unused styles from the file (.ass only).
-    #[arg(long)]
-    unused_styles: bool,
-    /// Fixes common issues with Japanese subtitle files.
-    ///
-    /// The things removed are as follows:
+    pub old: PathBuf,
+    /// The modified subtitle file.
     ///
-    /// * [外:37F6ECF37A0A3EF8DFF083CCC8754F81]-like instances of text
-    /// * Half-width kana is converted into full width kana
-    /// * Removal of &lrm;, U+202A, and U+202C characters
-    #[arg(long = "fix-jp", verbatim_doc_comment)]
-    fix_japanese: bool,
-    /// Remove all dialogue lines from the file.
-    ///
-    /// This is only useful if specified with a start and
-    /// end range.
+    /// If `-` is given, then it's interpreted as stdin.
+    pub new: PathBuf,
+    #[command(flatten)]
+    pub parse: ParseArgs,
+    /// How far apart in seconds two start times can be to be the same dialogue.
+    #[arg(long, default_value_t = 0.5, value_parser = valid_duration)]
+    pub tolerance: f32,
+    /// Print the changes in a format similar to a unified diff.
     #[arg(long)]
-    remove: bool,
-    /// Removes dialogue using the given style (.ass only)
-    ///
-    /// Can be specified multiple times to remove multiple
-    /// dialogues from different styles.
-    #[arg(long, verbatim_doc_comment)]
-    dialogue_from: Vec<String>,
-    /// Merges simultaneous dialogue lines that have the same start and end time.
-    ///
-    /// This is a common trick used in some .ass files. Merging is done by
-    /// combining the dialogue top to bottom with a new line between each.
-    #[arg(long, verbatim_doc_comment)]
-    merge_simultaneous: bool,
+    pub unified: bool,
 }
 
-impl CleanupArgs {
-    pub fn run(self) -> anyhow::Result<()> {
-        let output = self.output.resolve(&self.file)?;
-        let input = InputOutputLocation::new(self.file);
-        let contents = input.read_as_string()?;
-        match SubtitleFormat::detect(&contents) {
-            Some(SubtitleFormat::Srt) => {
-                let mut dialogue = srt::load_from_string(&contents)?;
-                if self.remove {
-                    dialogue.retain(|d| !self.range.contains(&d.start));
-                }
-                if self.merge_simultaneous {
-                    let mut windows = windows_mut(&mut dialogue);
-                    while let Some([left, right]) = windows.next() {
-                        if left.start == right.start && left.end == right.end {
-                            left.text.push('\n');
-                            left.text.push_str(&right.text);
-                            right.position = u32::MAX; // sentinel to mark for deletion
-                        }
-                    }
-                    dialogue.retain(|d| d.position != u32::MAX);
+impl DiffArgs {
+    fn load(&self, path: &Path) -> anyhow::Result<Vec<srt::Dialogue>> {
+        let contents = InputOutputLocation::new(path.to_path_buf()).read_as_string()?;
+        self.parse
+            .parse_dialogue(&contents)
+            .with_context(|| format!("could not load {}", path.display()))
+    }
+
+    fn print_summary(changes: &[DialogueChange]) {
+        fn cue(d: &srt::Dialogue) -> String {
+            format!(
+                "{} --> {}: {}",
+                format_timestamp(&d.start),
+                format_timestamp(&d.end),
+                d.text.trim_end().replace('\n', " / ")
+            )
+        }
+
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+        for change in changes {
+            match change {
+                DialogueChange::Added(d) => {
+                    added += 1;
+                    println!("added   {}", cue(d));
                 }
-                if self.fix_japanese {
-                    dialogue
-                        .iter_mut()
-                        .filter(|d| self.range.contains(&d.start))
-                        .for_each(|d| crate::japanese::fix_broken_text(&mut d.text));
+                DialogueChange::Removed(d) => {
+                    removed += 1;
+                    println!("removed {}", cue(d));
                 }
-
-                // Fix up the SRT position markers
-                for (index, d) in dialogue.iter_mut().enumerate() {
-                    d.position = (index + 1) as u32;
+                DialogueChange::Changed(old, new) => {
+                    changed += 1;
+                    println!("changed {}", cue(old));
+                    println!("     to {}", cue(new));
                 }
+            }
+        }
+        if !changes.is_empty() {
+            println!();
+        }
+        println!("{added} added, {removed} removed, {changed} changed");
+    }
 
-                output.save_srt(&dialogue)
+    fn print_unified(&self, changes: &[DialogueChange]) {
+        fn lines(prefix: char, d: &srt::Dialogue) {
+            println!(
+                "{prefix}{} --> {}",
+                format_timestamp(&d.start),
+                format_timestamp(&d.end)
+            );
+            for line in d.text.lines() {
+                println!("{prefix}{line}");
             }
-            Some(SubtitleFormat::Ass) => {
-                let mut subs = contents.parse::<Ass>()?;
+        }
 
-                // This removes *all* comments from the file
-                if self.comments {
-                    for section in &mut subs.sections {
-                        section.remove_comments();
-                    }
+        println!("--- {}", self.old.display());
+        println!("+++ {}", self.new.display());
+        for change in changes {
+            match change {
+                DialogueChange::Added(d) => {
+                    println!("@@ {} @@", format_timestamp(&d.start));
+                    lines('+', d);
                 }
-
-                if let Some(section) = subs.sections.iter_mut().find_map(|s| s.as_events_mut()) {
-                    let mut used_styles = HashSet::new();
-                    if self.remove {
-                        section.events.retain(|e| !self.range.contains(&e.start));
-                    }
-
-                    // Do this in two passes to keep track of used styles
-                    let removed_styles = self.dialogue_from.into_iter().collect::<HashSet<_>>();
-                    for event in &mut section.events {
-                        if !used_styles.contains(event.style.as_str()) {
-                            used_styles.insert(event.style.clone());
-                        }
-                        if removed_styles.contains(&event.style) {
-                            event.start = Duration::MAX; // sentinel
-                        }
-
-                        if self.fix_japanese && event.kind.is_dialogue() {
-                            crate::japanese::fix_broken_text(&mut event.text);
-                        }
-                    }
-
-                    if self.merge_simultaneous {
-                        let mut windows = windows_mut(&mut section.events);
-                        while let Some([left, right]) = windows.next() {
-                            if left.start != Duration::MAX
-                                && left.start == right.start
-                                && left.end == right.end
-                            {
-                                left.text.push_str("\\N");
-                                left.text.push_str(&right.text);
-                                right.start = Duration::MAX; // sentinel to mark for deletion
-                            }
-                        }
-                    }
-
-                    #[allow(clippy::nonminimal_bool)]
-                    section.events.retain(|d| {
-                        d.start != Duration::MAX
-                            && !(self.unused_styles && !used_styles.contains(d.style.as_str()))
-                    });
+                DialogueChange::Removed(d) => {
+                    println!("@@ {} @@", format_timestamp(&d.start));
+                    lines('-', d);
+                }
+                DialogueChange::Changed(old, new) => {
+                    println!("@@ {} @@", format_timestamp(&old.start));
+                    lines('-', old);
+                    lines('+', new);
                 }
-                output.save_ass(&subs)
             }
-            Some(_) => Cli::command()
-                .error(
-                    clap::error::ErrorKind::ValueValidation,
-                    "unsupported subtitle format for this operation",
-                )
-                .exit(),
-            None => Cli::command()
-                .error(
-                    clap::error::ErrorKind::ValueValidation,
-                    "could not recognize subtitle type",
-                )
-                .exit(),
         }
     }
+
+    pub fn run(self) -> anyhow::Result<()> {
+        let old = self.load(&self.old)?;
+        let new = self.load(&self.new)?;
+        let tolerance = duration_from_secs(self.tolerance.abs().into());
+        let changes = srt::diff(&old, &new, tolerance);
+        if self.unified {
+            self.print_unified(&changes);
+        } else {
+            Self::print_summary(&changes);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ShiftArgs {
+    /// The subtitle file to shift
+    ///
+    /// If `-` is given, then it's interpreted as stdin.
+    file: PathBuf,
+    #[command(flatten)]
+    output: InPlaceOutputArgs,
+    #[command(flatten)]
+    range: DurationRangeArgs,
+    #[command(flatten)]
+    parse: ParseArgs,
+    #[command(flatten)]
+    write: WriteArgs,
+    #[command(flatten)]
+    preview: PreviewArgs,
+    /// Shift the timing of the subtitles by the given seconds
+    #[arg(
+        long,
+        required_unless_present_any = ["from_file", "by_frames", "from_timing"],
+        value_parser = valid_offset,
+        allow_negative_numbers = true
+    )]
+    by: Option<SignedDuration>,
+    /// Shift the timing of the subtitles by the given number of frames.
+    ///
+    /// Negative frame counts shift backward. Requires `--fps`.
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        conflicts_with = "by",
+        requires = "fps",
+        value_parser = valid_frames,
+        allow_negative_numbers = true
+    )]
+    by_frames: Option<i64>,
+    /// The frame rate used to convert `--by-frames` into seconds
+    #[arg(long, requires = "by_frames", value_parser = valid_fps)]
+    fps: Option<f64>,
+    /// Shift multiple segments of the subtitles using a file.
+    ///
+    /// Each line of the file is a `start end offset` triple. The start
+    /// and end use the same format as `--start` and `--end` and the
+    /// offset is in seconds, the same as `--by`. A segment includes
+    /// its start but not its end, so segments can follow each other,
+    /// but they can't overlap. Ranges always refer to the original
+    /// timings, so a line is never shifted twice because an earlier
+    /// segment moved it into a later one. Blank lines and lines
+    /// starting with `#` are ignored.
+    ///
+    /// For example:
+    ///
+    ///   # Ad break at 10 minutes
+    ///   10:00 20:00 2.5
+    ///   20:00 01:00:00 4.1
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["by", "by_frames", "start", "end"],
+        verbatim_doc_comment
+    )]
+    from_file: Option<PathBuf>,
+    /// Replace the timing of every dialogue line with the ones in a file.
+    ///
+    /// This is for timings from an external tool, e.g. forced
+    /// alignment from speech recognition. Each line of the file is
+    /// an `index start end` triple separated by tabs or spaces,
+    /// where the index is the position of the dialogue line
+    /// starting from 1. The times are either in seconds or in the
+    /// same format as .srt timestamps. The text is kept as is and
+    /// the file must have exactly one timing for every dialogue
+    /// line. Blank lines and lines starting with `#` are ignored.
+    ///
+    /// For example:
+    ///
+    ///   # index start end
+    ///   1  12.480  14.920
+    ///   2  00:00:15,100  00:00:17,000
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["by", "by_frames", "from_file", "start", "end"],
+        verbatim_doc_comment
+    )]
+    from_timing: Option<PathBuf>,
+}
+
+/// A segment of the subtitles to shift.
+struct ShiftSegment {
+    range: DurationRange,
+    by: SignedDuration,
+    /// Segments from `--from-file` don't include their end so that a line
+    /// starting where one segment ends and the next starts is shifted once.
+    half_open: bool,
+}
+
+impl ShiftSegment {
+    /// Returns `true` if a line starting at the given time is shifted by this segment.
+    fn contains(&self, start: &Duration) -> bool {
+        self.range.contains(start) && !(self.half_open && self.range.end == Some(*start))
+    }
+}
+
+/// Returns the index of the segment that shifts each line, given their start times.
+///
+/// Segments aren't meant to overlap, but if they do only the first one applies.
+fn assign_segments(segments: &[ShiftSegment], starts: &[Duration]) -> Vec<Option<usize>> {
+    starts
+        .iter()
+        .map(|start| segments.iter().position(|s| s.contains(start)))
+        .collect()
+}
+
+impl std::fmt::Display for ShiftSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.range.start, &self.range.end) {
+            (Some(start), Some(end)) => {
+                write!(f, "{} - {}", format_timestamp(start), format_timestamp(end))?
+            }
+            _ => f.write_str("all")?,
+        }
+        write!(f, " by {}s", self.by)
+    }
+}
+
+fn parse_shift_segments(contents: &str) -> anyhow::Result<Vec<ShiftSegment>> {
+    let mut segments = Vec::new();
+    let mut lines = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parse_line = || -> anyhow::Result<ShiftSegment> {
+            let mut fields = line.split_whitespace();
+            let (Some(start), Some(end), Some(by), None) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                anyhow::bail!("expected `start end offset`");
+            };
+            let (start, end) = (parse_duration(start)?, parse_duration(end)?);
+            if end <= start {
+                anyhow::bail!("the segment ends before it starts");
+            }
+            Ok(ShiftSegment {
+                range: DurationRange {
+                    start: Some(start),
+                    end: Some(end),
+                },
+                by: valid_offset(by).map_err(anyhow::Error::msg)?,
+                half_open: true,
+            })
+        };
+        segments.push(parse_line().with_context(|| format!("line {}", index + 1))?);
+        lines.push(index + 1);
+    }
+
+    let mut order = (0..segments.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| segments[i].range.start);
+    for pair in order.windows(2) {
+        let (first, second) = (&segments[pair[0]], &segments[pair[1]]);
+        if second.range.start < first.range.end {
+            anyhow::bail!(
+                "the segments on lines {} and {} overlap",
+                lines[pair[0]].min(lines[pair[1]]),
+                lines[pair[0]].max(lines[pair[1]])
+            );
+        }
+    }
+    Ok(segments)
+}
+
+/// Parses a time of a `--from-timing` file, either in seconds or as a timestamp.
+fn parse_timing(s: &str) -> anyhow::Result<Duration> {
+    if let Some(time) = srt::parse_srt_time(s) {
+        return Ok(time);
+    }
+    match s.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(duration_from_secs(seconds)),
+        _ => Ok(parse_duration(s)?),
+    }
+}
+
+/// Parses a `--from-timing` file into the timings ordered by their index.
+///
+/// Every index from 1 up to the number of timings has to be given exactly once.
+fn parse_timing_list(contents: &str) -> anyhow::Result<Vec<(Duration, Duration)>> {
+    let mut timings = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parse_line = || -> anyhow::Result<(usize, Duration, Duration)> {
+            let mut fields = line.split_whitespace();
+            let (Some(position), Some(start), Some(end), None) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                anyhow::bail!("expected `index start end`");
+            };
+            let position = position
+                .parse::<usize>()
+                .ok()
+                .filter(|&p| p > 0)
+                .context("the index must be a number starting from 1")?;
+            let (start, end) = (parse_timing(start)?, parse_timing(end)?);
+            if end < start {
+                anyhow::bail!("the timing ends before it starts");
+            }
+            Ok((position, start, end))
+        };
+        timings.push(parse_line().with_context(|| format!("line {}", index + 1))?);
+    }
+
+    timings.sort_by_key(|(position, ..)| *position);
+    for (expected, (position, ..)) in (1..).zip(&timings) {
+        if *position != expected {
+            anyhow::bail!("expected a timing for index {expected} but found {position}");
+        }
+    }
+    Ok(timings
+        .into_iter()
+        .map(|(_, start, end)| (start, end))
+        .collect())
+}
+
+/// Returns an error if the number of timings doesn't match the number of dialogue lines.
+fn check_timing_count(timings: &[(Duration, Duration)], dialogue: usize) -> anyhow::Result<()> {
+    if timings.len() != dialogue {
+        anyhow::bail!(
+            "the timing file has {} timing(s) but there are {dialogue} dialogue line(s)",
+            timings.len()
+        );
+    }
+    Ok(())
+}
+
+impl ShiftArgs {
+    /// Fills in the options from the configuration file that weren't given explicitly.
+    pub fn apply_config(&mut self, config: &ShiftConfig) {
+        self.parse.strict |= !self.parse.no_strict && config.strict.unwrap_or_default();
+        self.write.compact_format |=
+            !self.write.no_compact_format && config.compact_format.unwrap_or_default();
+        self.write.bom |= !self.write.no_bom && config.bom.unwrap_or_default();
+        self.write.srt_sep = self.write.srt_sep.or(config.srt_sep);
+    }
+
+    fn segments(&mut self) -> anyhow::Result<Vec<ShiftSegment>> {
+        if self.from_timing.is_some() {
+            return Ok(Vec::new());
+        }
+        match &self.from_file {
+            Some(path) => {
+                let contents = crate::load_file(path)?;
+                parse_shift_segments(&contents)
+                    .with_context(|| format!("invalid shift file {}", path.display()))
+            }
+            None => Ok(vec![ShiftSegment {
+                range: std::mem::take(&mut self.range).into(),
+                by: match (self.by_frames, self.fps) {
+                    (Some(frames), Some(fps)) => frames_to_offset(frames, fps),
+                    _ => self.by.unwrap_or_default(),
+                },
+                half_open: false,
+            }]),
+        }
+    }
+
+    /// Reads the timings from `--from-timing`, if given.
+    fn timings(&self) -> anyhow::Result<Option<Vec<(Duration, Duration)>>> {
+        let Some(path) = &self.from_timing else {
+            return Ok(None);
+        };
+        let contents = crate::load_file(path)?;
+        parse_timing_list(&contents)
+            .with_context(|| format!("invalid timing file {}", path.display()))
+            .map(Some)
+    }
+
+    pub fn run(mut self) -> anyhow::Result<()> {
+        let segments = self.segments()?;
+        let timings = self.timings()?;
+        let report = self.from_file.is_some();
+        let output = self.output.resolve(&self.file)?;
+        let input = InputOutputLocation::new(self.file);
+        let contents = input.read_as_string()?;
+        match detect_format(&contents)? {
+            SubtitleFormat::Ass => {
+                let mut subs = self.parse.parse_ass(&contents)?;
+                if let Some(timings) = &timings {
+                    let dialogue = subs.events().filter(|e| e.kind.is_dialogue()).count();
+                    check_timing_count(timings, dialogue)?;
+                    let events = subs.events_mut().filter(|e| e.kind.is_dialogue());
+                    for (event, &(start, end)) in events.zip(timings) {
+                        event.start = start;
+                        event.end = end;
+                    }
+                }
+                self.preview.limit_ass(&mut subs);
+                let starts = subs.events().map(|e| e.start).collect::<Vec<_>>();
+                let assigned = assign_segments(&segments, &starts);
+                for (event, index) in subs.events_mut().zip(&assigned) {
+                    if let Some(index) = index {
+                        event.shift(segments[*index].by);
+                    }
+                }
+                if report {
+                    for (index, segment) in segments.iter().enumerate() {
+                        let count = assigned.iter().filter(|i| **i == Some(index)).count();
+                        eprintln!("{segment}: shifted {count} event(s)");
+                    }
+                }
+                self.preview.preview_ass(&subs);
+                output.save_ass(&subs, &self.write.options())
+            }
+            format @ (SubtitleFormat::Srt | SubtitleFormat::Vtt) => {
+                let mut dialogue = if format == SubtitleFormat::Vtt {
+                    self.parse.parse_vtt(&contents)?
+                } else {
+                    self.parse.parse_srt(&contents)?
+                };
+                if let Some(timings) = &timings {
+                    check_timing_count(timings, dialogue.len())?;
+                    for (d, &(start, end)) in dialogue.iter_mut().zip(timings) {
+                        d.start = start;
+                        d.end = end;
+                    }
+                }
+                self.preview.limit_srt(&mut dialogue);
+                let starts = dialogue.iter().map(|d| d.start).collect::<Vec<_>>();
+                let assigned = assign_segments(&segments, &starts);
+                for (d, index) in dialogue.iter_mut().zip(&assigned) {
+                    if let Some(index) = index {
+                        d.shift(segments[*index].by);
+                    }
+                }
+                if report {
+                    for (index, segment) in segments.iter().enumerate() {
+                        let count = assigned.iter().filter(|i| **i == Some(index)).count();
+                        eprintln!("{segment}: shifted {count} dialogue line(s)");
+                    }
+                }
+                self.preview.preview_srt(&dialogue);
+                if format == SubtitleFormat::Vtt {
+                    output.save_vtt(&dialogue, &self.write.vtt_options())
+                } else {
+                    output.save_srt(&dialogue, &self.write.srt_options())
+                }
+            }
+        }
+    }
+}
+
+/// Scaling by more than this usually means the frame rate is wrong.
+const SUSPICIOUS_SCALE: f64 = 0.05;
+
+#[derive(Args, Debug)]
+pub struct FitArgs {
+    /// The subtitle file to check
+    ///
+    /// If `-` is given, then it's interpreted as stdin.
+    file: PathBuf,
+    /// The duration of the video.
+    ///
+    /// This uses the same format as `--start` and `--end`
+    /// in other commands, e.g. `23:40` or `01:23:40.5`.
+    #[arg(long, value_parser = parse_duration, verbatim_doc_comment)]
+    video_duration: Duration,
+    /// Scales the timings so the last dialogue line ends with the video.
+    ///
+    /// Nothing is scaled if the dialogue already fits.
+    #[arg(long, verbatim_doc_comment)]
+    scale: bool,
+    #[command(flatten)]
+    output: InPlaceOutputArgs,
+    #[command(flatten)]
+    parse: ParseArgs,
+    #[command(flatten)]
+    write: WriteArgs,
+}
+
+impl FitArgs {
+    /// Returns the factor to scale the timings by so the dialogue fits.
+    ///
+    /// Returns `None` if the dialogue already fits.
+    fn scale_factor(&self, last_end: Duration) -> Option<f64> {
+        if last_end <= self.video_duration {
+            return None;
+        }
+        Some(self.video_duration.as_secs_f64() / last_end.as_secs_f64())
+    }
+
+    pub fn run(self) -> anyhow::Result<()> {
+        let input = InputOutputLocation::new(self.file.clone());
+        let contents = input.read_as_string()?;
+        let format = detect_format(&contents)?;
+        let (mut subs, mut dialogue) = match format {
+            SubtitleFormat::Ass => (Some(self.parse.parse_ass(&contents)?), Vec::new()),
+            _ => (None, self.parse.parse_dialogue(&contents)?),
+        };
+        let last_end = match &subs {
+            Some(subs) => subs
+                .events()
+                .filter(|e| e.kind.is_dialogue())
+                .map(|e| e.end)
+                .max(),
+            None => dialogue.iter().map(|d| d.end).max(),
+        }
+        .unwrap_or_default();
+
+        if !self.scale {
+            println!("Video: {}", format_timestamp(&self.video_duration));
+            println!("Last line ends: {}", format_timestamp(&last_end));
+            if last_end > self.video_duration {
+                anyhow::bail!(
+                    "dialogue runs past the end of the video by {}",
+                    format_timestamp(&(last_end - self.video_duration))
+                );
+            }
+            return Ok(());
+        }
+
+        let factor = self.scale_factor(last_end);
+        let scale = |start: &mut Duration, end: &mut Duration| {
+            if let Some(factor) = factor {
+                *start = scale_duration(*start, factor);
+                *end = scale_duration(*end, factor);
+            }
+        };
+        match factor {
+            Some(factor) => {
+                if (1.0 - factor).abs() > SUSPICIOUS_SCALE {
+                    eprintln!(
+                        "warning: scaling by {:.1}%, the frame rate is probably wrong",
+                        (factor - 1.0) * 100.0
+                    );
+                }
+                eprintln!("scaled timings by {factor:.5}")
+            }
+            None => eprintln!("dialogue already fits, nothing to scale"),
+        }
+
+        let output = self.output.resolve(&self.file)?;
+        match subs.as_mut() {
+            Some(subs) => {
+                subs.retime(scale);
+                output.save_ass(subs, &self.write.options())
+            }
+            None => {
+                srt::retime(&mut dialogue, scale);
+                if format == SubtitleFormat::Vtt {
+                    output.save_vtt(&dialogue, &self.write.vtt_options())
+                } else {
+                    output.save_srt(&dialogue, &self.write.srt_options())
+                }
+            }
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CleanupArgs {
+    /// The subtitle file to cleanup
+    ///
+    /// If `-` is given, then it's interpreted as stdin.
+    file: PathBuf,
+    #[command(flatten)]
+    output: InPlaceOutputArgs,
+    #[command(flatten)]
+    range: DurationRangeArgs,
+    #[command(flatten)]
+    parse: ParseArgs,
+    #[command(flatten)]
+    write: WriteArgs,
+    #[command(flatten)]
+    preview: PreviewArgs,
+    #[command(flatten)]
+    blank: BlankCueArgs,
+    /// Remove comment lines from the file (.ass only).
+    #[arg(long)]
+    comments: bool,
+    /// Turns off `--comments` when the configuration file turns it on.
+    #[arg(long, overrides_with = "comments", hide = true)]
+    no_comments: bool,
+    /// Remove unused styles from the file (.ass only).
+    #[arg(long)]
+    unused_styles: bool,
+    /// Turns off `--unused-styles` when the configuration file turns it on.
+    #[arg(long, overrides_with = "unused_styles", hide = true)]
+    no_unused_styles: bool,
+    /// Fixes common issues with Japanese subtitle files.
+    ///
+    /// The things removed are as follows:
+    ///
+    /// * [外:37F6ECF37A0A3EF8DFF083CCC8754F81]-like instances of text
+    /// * Half-width kana is converted into full width kana
+    /// * Removal of &lrm;, U+202A, and U+202C characters
+    #[arg(long = "fix-jp", verbatim_doc_comment)]
+    fix_japanese: bool,
+    /// Turns off `--fix-jp` when the configuration file turns it on.
+    #[arg(long = "no-fix-jp", overrides_with = "fix_japanese", hide = true)]
+    no_fix_japanese: bool,
+    /// Enables `--fix-jp` only if most of the dialogue is in Japanese.
+    #[arg(long = "auto-fix-jp", conflicts_with = "fix_japanese")]
+    auto_fix_japanese: bool,
+    /// Turns off `--auto-fix-jp` when the configuration file turns it on.
+    #[arg(
+        long = "no-auto-fix-jp",
+        overrides_with = "auto_fix_japanese",
+        hide = true
+    )]
+    no_auto_fix_japanese: bool,
+    /// Remove all dialogue lines from the file.
+    ///
+    /// This is only useful if specified with a start and
+    /// end range.
+    #[arg(long)]
+    remove: bool,
+    /// Removes dialogue using the given style (.ass only)
+    ///
+    /// Can be specified multiple times to remove multiple
+    /// dialogues from different styles.
+    #[arg(long, verbatim_doc_comment)]
+    dialogue_from: Vec<String>,
+    /// Merges simultaneous dialogue lines that have the same start and end time.
+    ///
+    /// This is a common trick used in some .ass files. Merging is done by
+    /// combining the dialogue top to bottom with a new line between each.
+    #[arg(long, verbatim_doc_comment)]
+    merge_simultaneous: bool,
+    /// Turns off `--merge-simultaneous` when the configuration file turns it on.
+    #[arg(long, overrides_with = "merge_simultaneous", hide = true)]
+    no_merge_simultaneous: bool,
+    /// Splits every line of multi-line dialogue into its own dialogue.
+    ///
+    /// This is the opposite of --merge-simultaneous. By default
+    /// every line keeps the timing of the original dialogue, with
+    /// `divide` the duration is divided evenly between the lines
+    /// instead. For .ass files, override tags at the start of the
+    /// dialogue are repeated on every line, and events positioned
+    /// with \pos or \move are left as is.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TIMING",
+        num_args = 0..=1,
+        default_missing_value = "share",
+        require_equals = true,
+        conflicts_with = "merge_simultaneous",
+        verbatim_doc_comment
+    )]
+    split_lines: Option<SplitTimingArg>,
+    /// Sorts the dialogue by start time before cleaning up.
+    ///
+    /// Merging and joining lines depends on the order of the
+    /// dialogue, so this makes the output the same however the
+    /// input was ordered. Lines that start at the same time are
+    /// sorted by their end, then layer and style for .ass files,
+    /// then text. Note that this may reorder events.
+    #[arg(long, verbatim_doc_comment)]
+    stable: bool,
+    /// Never reorder the dialogue, even if --stable is set in the config.
+    ///
+    /// Sorting with --stable is the only option that reorders
+    /// the dialogue. Merging, joining and splitting lines work on
+    /// neighbouring lines in their original order, and the timing
+    /// options only look at the start times without moving lines.
+    /// This is for files where the order is intentional, e.g.
+    /// stacked lines or effects.
+    #[arg(long, conflicts_with = "stable", verbatim_doc_comment)]
+    keep_order: bool,
+    /// Renames a style from OLD to NEW (.ass only)
+    ///
+    /// Events and `\r` override tags that reference the style are
+    /// updated as well. Can be specified multiple times.
+    #[arg(long, value_name = "OLD=NEW", value_parser = parse_rename, verbatim_doc_comment)]
+    rename_style: Vec<(String, String)>,
+    /// Removes styles that are identical to an earlier style apart from the name (.ass only)
+    ///
+    /// Events that used a removed style use the kept style instead.
+    #[arg(long, verbatim_doc_comment)]
+    dedup_styles: bool,
+    /// Turns off `--dedup-styles` when the configuration file turns it on.
+    #[arg(long, overrides_with = "dedup_styles", hide = true)]
+    no_dedup_styles: bool,
+    /// Makes every event use the given style and removes every other style (.ass only)
+    ///
+    /// The style is created with the default settings if it
+    /// doesn't exist. Override tags such as `\rSign` that reset
+    /// to another style still apply.
+    #[arg(long, value_name = "NAME", verbatim_doc_comment)]
+    unify_style: Option<String>,
+    /// Puts every event on layer 0 (.ass only)
+    ///
+    /// This makes further processing simpler, but events that
+    /// overlap on purpose may be drawn in a different order.
+    #[arg(long, conflicts_with = "set_layer", verbatim_doc_comment)]
+    flatten_layers: bool,
+    /// Turns off `--flatten-layers` when the configuration file turns it on.
+    #[arg(long, overrides_with = "flatten_layers", hide = true)]
+    no_flatten_layers: bool,
+    /// Puts events on the given layer (.ass only)
+    ///
+    /// Only events using the style given by --style are changed
+    /// if it's given, otherwise every event is.
+    #[arg(long, value_name = "LAYER", verbatim_doc_comment)]
+    set_layer: Option<u8>,
+    /// The style of the events whose layer is set by --set-layer.
+    #[arg(long, value_name = "NAME", requires = "set_layer")]
+    style: Option<String>,
+    /// Replaces text in every dialogue line using a regex.
+    ///
+    /// The format is `/pattern/replacement/` where the first
+    /// character is the delimiter, similar to `sed`. The delimiter
+    /// can be escaped with a backslash. Capture groups can be
+    /// referenced in the replacement using `$1` or `${name}`.
+    ///
+    /// Can be specified multiple times, in which case they are
+    /// applied in order.
+    #[arg(long, value_name = "/PATTERN/REPLACEMENT/", value_parser = parse_replacement, verbatim_doc_comment)]
+    replace: Vec<TextReplacement>,
+    /// Treat the `--replace` pattern and replacement as literal text instead of a regex.
+    #[arg(long, requires = "replace")]
+    literal: bool,
+    /// Allow `--replace` to modify text inside override tags (.ass only).
+    ///
+    /// By default, only the text outside of `{...}` blocks is replaced.
+    #[arg(long, requires = "replace", verbatim_doc_comment)]
+    replace_in_tags: bool,
+    /// Converts the case of every dialogue line.
+    ///
+    /// Override tags, HTML tags, and text without casing
+    /// (e.g. Japanese) are left untouched.
+    #[arg(long, value_enum, verbatim_doc_comment)]
+    case: Option<TextCaseArg>,
+    /// Converts Japanese punctuation between full-width and ASCII.
+    ///
+    /// Override tags are left untouched.
+    #[arg(long, value_enum, value_name = "WIDTH", verbatim_doc_comment)]
+    punct: Option<PunctuationWidthArg>,
+    /// Removes the override tags of the given categories (.ass only)
+    ///
+    /// Multiple categories can be separated by commas, e.g.
+    /// `--strip-tags karaoke,transform`. The rest of the tags
+    /// and the text are kept.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CATEGORY",
+        value_delimiter = ',',
+        verbatim_doc_comment
+    )]
+    strip_tags: Vec<TagCategoryArg>,
+    /// Joins dialogue lines that don't end a sentence with the next line.
+    ///
+    /// A line is joined when it doesn't end with sentence-final
+    /// punctuation and the next line starts within MAX_GAP seconds
+    /// (0.5 by default) of it ending. This is mainly useful for
+    /// automatically generated subtitles.
+    #[arg(
+        long,
+        value_name = "MAX_GAP",
+        num_args = 0..=1,
+        default_missing_value = "0.5",
+        require_equals = true,
+        value_parser = valid_duration,
+        verbatim_doc_comment
+    )]
+    join_sentences: Option<f32>,
+    /// Removes HTML tags such as `<i>` from every dialogue line (.srt and .vtt only).
+    ///
+    /// This is useful for players that don't support markup.
+    /// Only the .srt and .vtt markup tags are removed, so text
+    /// such as `3 < 5` or `<laughs>` is kept.
+    #[arg(long, verbatim_doc_comment)]
+    strip_html: bool,
+    /// Turns off `--strip-html` when the configuration file turns it on.
+    #[arg(long, overrides_with = "strip_html", hide = true)]
+    no_strip_html: bool,
+    /// Collapses runs of spaces in every dialogue line into a single space.
+    ///
+    /// Override tags, hard spaces (`\h`), and full-width
+    /// spaces are left untouched.
+    #[arg(long, verbatim_doc_comment)]
+    collapse_spaces: bool,
+    /// Turns off `--collapse-spaces` when the configuration file turns it on.
+    #[arg(long, overrides_with = "collapse_spaces", hide = true)]
+    no_collapse_spaces: bool,
+    /// Removes leading and trailing whitespace from every line of dialogue.
+    ///
+    /// Override tags at the start or end of the line are kept (.ass only).
+    #[arg(long, verbatim_doc_comment)]
+    trim: bool,
+    /// Turns off `--trim` when the configuration file turns it on.
+    #[arg(long, overrides_with = "trim", hide = true)]
+    no_trim: bool,
+    /// Limits every dialogue line to at most N lines of text.
+    ///
+    /// Any extra lines are joined onto the last allowed line.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), verbatim_doc_comment)]
+    max_lines: Option<u16>,
+    /// Extends dialogue lines that are shown for less than the given seconds.
+    ///
+    /// For .srt and .vtt files, lines are not extended past
+    /// the start of the next line.
+    #[arg(long, value_name = "SECONDS", value_parser = valid_duration, verbatim_doc_comment)]
+    min_duration: Option<f32>,
+    /// Shortens dialogue lines that are shown for more than the given seconds.
+    #[arg(long, value_name = "SECONDS", value_parser = valid_duration)]
+    max_duration: Option<f32>,
+    /// Shows dialogue lines with no duration for SECONDS (1 by default).
+    ///
+    /// These start and end at the same time, or end before they start.
+    ///
+    /// For .srt and .vtt files, lines are not extended past
+    /// the start of the next line.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        require_equals = true,
+        value_parser = valid_duration,
+        verbatim_doc_comment
+    )]
+    fix_zero_duration: Option<f32>,
+    /// Extends dialogue lines to the start of the next line if the gap is at most MAX_GAP seconds.
+    ///
+    /// This avoids the subtitles flickering off for a moment
+    /// between lines. For .ass files, only lines with the same
+    /// layer and style count.
+    #[arg(long, value_name = "MAX_GAP", value_parser = valid_duration, verbatim_doc_comment)]
+    merge_gap: Option<f32>,
+    /// Ends dialogue lines earlier so there are at least MIN_GAP seconds before the next line.
+    ///
+    /// This is the opposite of --merge-gap, e.g. for delivery
+    /// specs that require a gap of two frames between lines.
+    /// Lines are never shortened to nothing and overlapping
+    /// lines are left alone. For .ass files, only lines with
+    /// the same layer and style count.
+    #[arg(
+        long,
+        value_name = "MIN_GAP",
+        value_parser = valid_duration,
+        conflicts_with = "merge_gap",
+        verbatim_doc_comment
+    )]
+    min_gap: Option<f32>,
+    /// Starts every dialogue line earlier by the given seconds.
+    ///
+    /// Lines don't start before the previous line ends. For .ass
+    /// files, only lines with the same layer and style count.
+    #[arg(long, value_name = "SECONDS", value_parser = valid_duration, verbatim_doc_comment)]
+    pad_start: Option<f32>,
+    /// Ends every dialogue line later by the given seconds.
+    ///
+    /// Lines don't end after the next line starts. For .ass
+    /// files, only lines with the same layer and style count.
+    #[arg(long, value_name = "SECONDS", value_parser = valid_duration, verbatim_doc_comment)]
+    pad_end: Option<f32>,
+    /// Snaps the start and end of every dialogue line to the closest frame.
+    #[arg(long, value_name = "FPS", value_parser = valid_fps)]
+    snap_fps: Option<f64>,
+    /// Applies a preset of cleanup options.
+    ///
+    /// Options that are given explicitly take precedence
+    /// over the ones set by the preset.
+    #[arg(long, value_enum, verbatim_doc_comment)]
+    profile: Option<CleanupProfile>,
+    /// Prints what was changed once the cleanup is done.
+    #[arg(long)]
+    summary: bool,
+}
+
+/// A `/pattern/replacement/` given to `--replace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TextReplacement {
+    pattern: String,
+    replacement: String,
+}
+
+fn parse_replacement(s: &str) -> Result<TextReplacement, String> {
+    let error = || format!("`{s}` must be in /pattern/replacement/ format");
+    let mut chars = s.chars();
+    let delimiter = chars.next().ok_or_else(error)?;
+    if delimiter.is_alphanumeric() || delimiter == '\\' {
+        return Err(error());
+    }
+
+    let mut parts = vec![String::new()];
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push(ch);
+                    part.push(next);
+                }
+                None => return Err(error()),
+            },
+            _ if ch == delimiter => parts.push(String::new()),
+            _ => parts.last_mut().unwrap().push(ch),
+        }
+    }
+
+    // The trailing delimiter leaves an empty part at the end
+    match <[String; 3]>::try_from(parts) {
+        Ok([pattern, replacement, rest]) if !pattern.is_empty() && rest.is_empty() => {
+            Ok(TextReplacement {
+                pattern,
+                replacement,
+            })
+        }
+        _ => Err(error()),
+    }
+}
+
+fn parse_rename(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+            Ok((old.to_owned(), new.to_owned()))
+        }
+        _ => Err(format!("`{s}` must be in OLD=NEW format")),
+    }
+}
+
+impl CleanupArgs {
+    fn replacers(&self) -> anyhow::Result<Vec<(Regex, String)>> {
+        self.replace
+            .iter()
+            .map(|r| {
+                if self.literal {
+                    let regex = Regex::new(&regex::escape(&r.pattern))?;
+                    // `$` has a special meaning in the replacement so it has to be escaped
+                    Ok((regex, r.replacement.replace('$', "$$")))
+                } else {
+                    let regex = Regex::new(&r.pattern)
+                        .with_context(|| format!("invalid --replace pattern `{}`", r.pattern))?;
+                    Ok((regex, r.replacement.clone()))
+                }
+            })
+            .collect()
+    }
+
+    /// Fills in the options from the configuration file that weren't given explicitly.
+    pub fn apply_config(&mut self, config: &CleanupConfig) -> anyhow::Result<()> {
+        if let Some(fps) = config.snap_fps.filter(|&fps| !is_valid_fps(fps)) {
+            anyhow::bail!("`{fps}` isn't a valid frame rate for snap-fps");
+        }
+        let durations = [
+            ("join-sentences", config.join_sentences),
+            ("min-duration", config.min_duration),
+            ("max-duration", config.max_duration),
+            ("fix-zero-duration", config.fix_zero_duration),
+            ("merge-gap", config.merge_gap),
+            ("min-gap", config.min_gap),
+            ("pad-start", config.pad_start),
+            ("pad-end", config.pad_end),
+        ];
+        for (name, seconds) in durations {
+            if let Some(seconds) = seconds.filter(|&s| !is_valid_duration(s)) {
+                anyhow::bail!("`{seconds}` isn't a valid duration for {name}");
+            }
+        }
+        if config.max_lines == Some(0) {
+            anyhow::bail!("max-lines must be at least 1");
+        }
+
+        self.parse.strict |= !self.parse.no_strict && config.strict.unwrap_or_default();
+        self.write.compact_format |=
+            !self.write.no_compact_format && config.compact_format.unwrap_or_default();
+        self.write.bom |= !self.write.no_bom && config.bom.unwrap_or_default();
+        self.write.srt_sep = self.write.srt_sep.or(config.srt_sep);
+        self.comments |= !self.no_comments && config.comments.unwrap_or_default();
+        self.unused_styles |= !self.no_unused_styles && config.unused_styles.unwrap_or_default();
+        self.dedup_styles |= !self.no_dedup_styles && config.dedup_styles.unwrap_or_default();
+        self.fix_japanese |= !self.no_fix_japanese && config.fix_jp.unwrap_or_default();
+        self.auto_fix_japanese |=
+            !self.no_auto_fix_japanese && config.auto_fix_jp.unwrap_or_default();
+        self.merge_simultaneous |=
+            !self.no_merge_simultaneous && config.merge_simultaneous.unwrap_or_default();
+        self.keep_order |= config.keep_order.unwrap_or_default();
+        self.stable |= config.stable.unwrap_or_default() && !self.keep_order;
+        self.split_lines = self.split_lines.or(config.split_lines);
+        self.flatten_layers |= !self.no_flatten_layers && config.flatten_layers.unwrap_or_default();
+        self.strip_html |= !self.no_strip_html && config.strip_html.unwrap_or_default();
+        self.collapse_spaces |=
+            !self.no_collapse_spaces && config.collapse_spaces.unwrap_or_default();
+        self.trim |= !self.no_trim && config.trim.unwrap_or_default();
+        self.blank.keep_blank_cues |=
+            !self.blank.no_keep_blank_cues && config.keep_blank_cues.unwrap_or_default();
+        self.case = self.case.or(config.case);
+        self.punct = self.punct.or(config.punct);
+        if self.strip_tags.is_empty() {
+            self.strip_tags = config.strip_tags.clone().unwrap_or_default();
+        }
+        self.join_sentences = self.join_sentences.or(config.join_sentences);
+        self.max_lines = self.max_lines.or(config.max_lines);
+        self.min_duration = self.min_duration.or(config.min_duration);
+        self.max_duration = self.max_duration.or(config.max_duration);
+        self.fix_zero_duration = self.fix_zero_duration.or(config.fix_zero_duration);
+        self.merge_gap = self.merge_gap.or(config.merge_gap);
+        self.min_gap = self.min_gap.or(config.min_gap);
+        // clap only sees the command line so the conflict is checked again here
+        if self.merge_gap.is_some() && self.min_gap.is_some() {
+            anyhow::bail!("merge-gap and min-gap can't be used together");
+        }
+        self.pad_start = self.pad_start.or(config.pad_start);
+        self.pad_end = self.pad_end.or(config.pad_end);
+        self.snap_fps = self.snap_fps.or(config.snap_fps);
+        self.profile = self.profile.or(config.profile);
+        Ok(())
+    }
+
+    /// Fills in the options set by the profile that weren't given explicitly.
+    fn apply_profile(&mut self) {
+        match self.profile {
+            Some(CleanupProfile::Netflix) => {
+                self.snap_fps.get_or_insert(NETFLIX_SNAP_FPS);
+                self.min_duration.get_or_insert(5.0 / 6.0);
+                self.max_duration.get_or_insert(7.0);
+                self.max_lines.get_or_insert(2);
+                self.collapse_spaces |= !self.no_collapse_spaces;
+            }
+            None => {}
+        }
+    }
+
+    /// Returns the library options for the transforms, see [`crate::cleanup`].
+    fn options(&self) -> anyhow::Result<CleanupOptions> {
+        // Rounded to milliseconds since e.g. 0.05 isn't exact as an f32
+        let seconds = |seconds: Option<f32>| {
+            seconds.map(|s| Duration::from_millis((s.abs() * 1000.0).round() as u64))
+        };
+        Ok(CleanupOptions {
+            range: self.range.clone().into(),
+            stable: self.stable,
+            remove: self.remove,
+            limit: self.preview.limit,
+            comments: self.comments,
+            unused_styles: self.unused_styles,
+            dialogue_from: self.dialogue_from.clone(),
+            fix_japanese: self.fix_japanese,
+            auto_fix_japanese: self.auto_fix_japanese,
+            merge_simultaneous: self.merge_simultaneous,
+            join_sentences: seconds(self.join_sentences),
+            split_lines: self.split_lines.map(Into::into),
+            strip_html: self.strip_html,
+            replacements: self.replacers()?,
+            replace_in_tags: self.replace_in_tags,
+            case: self.case.map(Into::into),
+            punct: self.punct.map(Into::into),
+            strip_tags: self.strip_tags.iter().copied().map(Into::into).collect(),
+            collapse_spaces: self.collapse_spaces,
+            trim: self.trim,
+            max_lines: self.max_lines.map(usize::from),
+            pad_start: seconds(self.pad_start),
+            pad_end: seconds(self.pad_end),
+            merge_gap: seconds(self.merge_gap),
+            min_gap: seconds(self.min_gap),
+            fix_zero_duration: seconds(self.fix_zero_duration),
+            min_duration: seconds(self.min_duration),
+            max_duration: seconds(self.max_duration),
+            snap_fps: self.snap_fps,
+            layer: self.set_layer.or(self.flatten_layers.then_some(0)),
+            layer_style: self.style.clone(),
+            dedup_styles: self.dedup_styles,
+            rename_styles: self.rename_style.clone(),
+            unify_style: self.unify_style.clone(),
+            keep_blank_cues: self.blank.keep_blank_cues,
+        })
+    }
+
+    /// Prints what the options that were given did.
+    fn print_report(&self, report: &CleanupReport) {
+        if self.split_lines.is_some() {
+            eprintln!("split {} line(s)", report.split);
+        }
+        if report.clamped > 0 {
+            eprintln!(
+                "warning: {} line(s) were padded less to avoid overlapping",
+                report.clamped
+            );
+        }
+        if self.merge_gap.is_some() {
+            eprintln!("closed {} gap(s)", report.gaps_closed);
+        }
+        for (category, count) in &report.tags_removed {
+            eprintln!("removed {count} {} tag(s)", category.name());
+        }
+        // The other options that change durations can leave these too
+        let retimed =
+            self.min_duration.is_some() || self.max_duration.is_some() || self.snap_fps.is_some();
+        if self.fix_zero_duration.is_some() {
+            if report.zero_duration > 0 {
+                eprintln!(
+                    "fixed {} of {} zero duration line(s)",
+                    report.zero_duration_fixed, report.zero_duration
+                );
+            }
+        } else if retimed && report.zero_duration > report.zero_duration_fixed {
+            let remaining = report.zero_duration - report.zero_duration_fixed;
+            eprintln!(
+                "warning: {remaining} line(s) have no duration, \
+                 use --fix-zero-duration to show them"
+            );
+        }
+        if self.min_gap.is_some() {
+            eprintln!("trimmed {} line(s) to leave a gap", report.gaps_opened);
+        }
+        if let Some((layer, changed)) = report.layer {
+            eprintln!("moved {changed} event(s) to layer {layer}");
+        }
+        for (old, new) in &report.merged_styles {
+            if old == new {
+                eprintln!("removed duplicate style {old}");
+            } else {
+                eprintln!("merged style {old} into {new}");
+            }
+        }
+        if report.style_resets > 0 {
+            eprintln!(
+                "warning: {} event(s) reset to another style with \\r",
+                report.style_resets
+            );
+        }
+    }
+
+    pub fn run(mut self) -> anyhow::Result<()> {
+        self.apply_profile();
+        let options = self.options()?;
+        let output = std::mem::take(&mut self.output).resolve(&self.file)?;
+        let input = InputOutputLocation::new(self.file.clone());
+        let contents = input.read_as_string()?;
+        let mut progress = Progress::for_input(&contents, 2);
+        progress.step("parsing");
+        let report = match detect_format(&contents)? {
+            format @ (SubtitleFormat::Srt | SubtitleFormat::Vtt) => {
+                let mut dialogue = if format == SubtitleFormat::Vtt {
+                    self.parse.parse_vtt(&contents)?
+                } else {
+                    self.parse.parse_srt(&contents)?
+                };
+                progress.step("cleaning up");
+                let report = cleanup_dialogue(&mut dialogue, &options);
+                drop(progress);
+                self.print_report(&report);
+                self.preview.preview_srt(&dialogue);
+                if format == SubtitleFormat::Vtt {
+                    output.save_vtt(&dialogue, &self.write.vtt_options())?;
+                } else {
+                    output.save_srt(&dialogue, &self.write.srt_options())?;
+                }
+                report
+            }
+            SubtitleFormat::Ass => {
+                let mut subs = self.parse.parse_ass(&contents)?;
+                progress.step("cleaning up");
+                let report = cleanup_ass(&mut subs, &options)?;
+                drop(progress);
+                self.print_report(&report);
+                self.preview.preview_ass(&subs);
+                output.save_ass(&subs, &self.write.options())?;
+                report
+            }
+        };
+        if self.summary {
+            eprintln!("{report}");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct MergeArgs {
+    /// The primary subtitle file to merge into (.ass only)
+    ///
+    /// If `-` is given, then it's interpreted as stdin.
+    file: PathBuf,
+    #[command(flatten)]
+    output: InPlaceOutputArgs,
+    #[command(flatten)]
+    parse: ParseArgs,
+    #[command(flatten)]
+    write: WriteArgs,
+    /// The subtitle file whose styles and dialogue are merged in (.ass only)
+    ///
+    /// Styles that share a name with one in the primary file are renamed
+    /// with a numbered suffix, e.g. `Default` becomes `Default-2`.
+    #[arg(long, verbatim_doc_comment)]
+    overlay: PathBuf,
+    /// Position the overlay's dialogue at the top of the screen.
+    ///
+    /// This adds `\an8` to every overlay dialogue line that doesn't
+    /// already specify an alignment.
+    #[arg(long, verbatim_doc_comment)]
+    top: bool,
+}
+
+impl MergeArgs {
+    fn load_ass(&self, location: &InputOutputLocation) -> anyhow::Result<Ass> {
+        let contents = location.read_as_string()?;
+        match detect_format(&contents)? {
+            SubtitleFormat::Ass => self.parse.parse_ass(&contents),
+            format => Err(unsupported_format(format, "merge")),
+        }
+    }
+
+    pub fn run(self) -> anyhow::Result<()> {
+        let mut primary = self.load_ass(&InputOutputLocation::new(self.file.clone()))?;
+        let mut overlay = self.load_ass(&InputOutputLocation::Path(self.overlay.clone()))?;
+        if self.top {
+            // Both \an and the legacy \a tags set the alignment
+            let is_alignment = |tag: &str| {
+                let value = tag.strip_prefix("\\an").or_else(|| tag.strip_prefix("\\a"));
+                value.is_some_and(|v| v.parse::<u8>().is_ok())
+            };
+            overlay
+                .events_mut()
+                .filter(|e| e.kind.is_dialogue())
+                .filter(|e| {
+                    !e.spans()
+                        .iter()
+                        .find(|s| s.is_override())
+                        .is_some_and(|s| s.tags().into_iter().any(is_alignment))
+                })
+                .for_each(|e| e.text.insert_str(0, "{\\an8}"));
+        }
+
+        for (old, new) in primary.overlay(overlay) {
+            eprintln!("renamed overlay style {old} to {new}");
+        }
+
+        let output = self.output.resolve(&self.file)?;
+        output.save_ass(&primary, &self.write.options())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_replacement() {
+        let replacement = parse_replacement("/colour/color/").unwrap();
+        assert_eq!(replacement.pattern, "colour");
+        assert_eq!(replacement.replacement, "color");
+
+        let replacement = parse_replacement(r"|a\|b|(\d+)|").unwrap();
+        assert_eq!(replacement.pattern, "a|b");
+        assert_eq!(replacement.replacement, r"(\d+)");
+
+        let replacement = parse_replacement("/remove me//").unwrap();
+        assert_eq!(replacement.replacement, "");
+
+        assert!(parse_replacement("/missing/end").is_err());
+        assert!(parse_replacement("//empty/").is_err());
+        assert!(parse_replacement("/too/many/parts/").is_err());
+    }
+
+    #[test]
+    fn test_nearest_terminal_colours() {
+        assert_eq!(nearest_ansi16(RgbColor(0, 0, 0)), 0);
+        assert_eq!(nearest_ansi16(RgbColor(250, 10, 10)), 9);
+        assert_eq!(nearest_ansi16(RgbColor(255, 255, 255)), 15);
+        assert_eq!(nearest_ansi256(RgbColor(255, 0, 0)), 196);
+        assert_eq!(nearest_ansi256(RgbColor(0, 0, 0)), 16);
+        assert_eq!(nearest_ansi256(RgbColor(128, 128, 128)), 244);
+    }
+
+    #[test]
+    fn test_frame_offsets() {
+        let offset = frames_to_offset(3, 24.0);
+        assert_eq!(offset, SignedDuration::forward(Duration::from_millis(125)));
+        let offset = frames_to_offset(-24, 23.976);
+        assert!(offset.negative);
+        assert_eq!(offset.duration.as_millis(), 1001);
+        assert!(valid_frames("0").is_err());
+        assert!(valid_frames("1.5").is_err());
+    }
+
+    #[test]
+    fn test_convert_format_from_path() {
+        let format = |path: &str| ConvertFormat::from_path(Path::new(path));
+        assert_eq!(format("a.ass"), Some(ConvertFormat::Ass));
+        assert_eq!(format("a.SRT"), Some(ConvertFormat::Srt));
+        assert_eq!(format("a.txt"), Some(ConvertFormat::Table));
+        assert_eq!(format("a.vtt"), None);
+        assert_eq!(format("a"), None);
+        assert_eq!(ConvertFormat::Table.extension(), "txt");
+    }
+
+    #[test]
+    fn test_info_without_styles() {
+        let subs = "[Script Info]\nScriptType: v4.00+\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,One\n\
+Dialogue: 0,0:00:02.00,0:00:03.00,Sign,,0,0,0,,Two\n\
+Comment: 0,0:00:03.00,0:00:04.00,Sign,,0,0,0,,Note\n\
+Dialogue: 0,0:00:04.00,0:00:05.00,Default,,0,0,0,,Three\n"
+            .parse::<Ass>()
+            .unwrap();
+        assert!(!subs.has_styles());
+        assert_eq!(dialogue_by_style(&subs), [("Default", 2), ("Sign", 1)]);
+        assert_eq!(subs.undefined_styles(), [("Default", 2), ("Sign", 2)]);
+    }
+
+    #[test]
+    fn test_stacked_dialogue() {
+        let s = Duration::from_secs;
+        let dialogue = [
+            (s(1), s(2), "Top"),
+            (s(1), s(2), "Bottom"),
+            (s(1), s(3), "Longer"),
+            (s(4), s(5), "Same"),
+            (s(4), s(5), "Same"),
+            (s(6), s(7), "A"),
+            (s(6), s(7), "B"),
+            (s(6), s(7), "C"),
+        ];
+        assert_eq!(stacked_groups(dialogue.into_iter()), 2);
+        assert_eq!(stacked_groups(dialogue[2..6].iter().copied()), 0);
+    }
+
+    #[test]
+    fn test_file_list() {
+        let list = "# episodes\nep01.ass\n\n  ep02.srt  \n#ep03.ass\nsub dir/ep04.vtt\n";
+        assert_eq!(
+            parse_file_list(list),
+            [
+                PathBuf::from("ep01.ass"),
+                PathBuf::from("ep02.srt"),
+                PathBuf::from("sub dir/ep04.vtt"),
+            ]
+        );
+
+        let cli = Cli::try_parse_from(["sub-tools", "info", "--files-from", "-"]).unwrap();
+        let Subcommands::Info(args) = cli.command else {
+            panic!()
+        };
+        assert!(args.files.is_empty());
+        assert!(Cli::try_parse_from(["sub-tools", "info"]).is_err());
+    }
+
+    #[test]
+    fn test_explain_file() {
+        let timings = [
+            (Duration::from_secs(12), Duration::from_secs(14)),
+            (Duration::from_secs(1419), Duration::from_secs(1420)),
+        ];
+        let mut description =
+            Description::new(SubtitleFormat::Ass, &timings, Some(Script::Japanese));
+        description.version = Some(String::from("v4.00+"));
+        description.styles = Some(3);
+        assert_eq!(
+            description.to_string(),
+            "This is a SubStation Alpha (.ass) v4.00+ file with 3 styles and 2 dialogue lines \
+             spanning 0:00:12 to 0:23:40. Most of the text is written in Japanese."
+        );
+
+        let description = Description::new(SubtitleFormat::Srt, &timings[..1], Some(Script::Latin));
+        assert_eq!(
+            description.to_string(),
+            "This is a SubRip (.srt) file with 1 dialogue line spanning 0:00:12 to 0:00:14. \
+             Most of the text is written in Latin script."
+        );
+
+        let description = Description::new(SubtitleFormat::Vtt, &[], None);
+        assert_eq!(
+            description.to_string(),
+            "This is a WebVTT (.vtt) file with no dialogue lines."
+        );
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let report = EncodingReport::detect(b"1\r\n00:00:01,000");
+        assert_eq!(report.encoding, "ASCII");
+        assert!(report.crlf && !report.bom);
+
+        let report = EncodingReport::detect("\u{feff}1\nこんにちは\n\n\u{feff}2".as_bytes());
+        assert_eq!(report.encoding, "UTF-8");
+        assert!(report.bom && !report.crlf);
+        assert_eq!(report.stray_boms, 1);
+
+        let report = EncodingReport::detect(&[0xFF, 0xFE, b'1', 0]);
+        assert_eq!(report.encoding, "UTF-16LE");
+        assert!(!report.supported);
+
+        // Shift-JIS
+        let report = EncodingReport::detect(&[0x82, 0xB1, 0x82, 0xF1]);
+        assert_eq!(report.encoding, "unknown");
+        assert!(!report.supported);
+    }
+
+    #[test]
+    fn test_keep_order_over_config() {
+        let config = CleanupConfig {
+            stable: Some(true),
+            ..Default::default()
+        };
+        let cli = Cli::try_parse_from(["sub-tools", "cleanup", "a.ass", "--keep-order"]).unwrap();
+        let Subcommands::Cleanup(mut args) = cli.command else {
+            panic!()
+        };
+        args.apply_config(&config).unwrap();
+        assert!(args.keep_order && !args.stable);
+
+        let cli = Cli::try_parse_from(["sub-tools", "cleanup", "a.ass"]).unwrap();
+        let Subcommands::Cleanup(mut args) = cli.command else {
+            panic!()
+        };
+        args.apply_config(&config).unwrap();
+        assert!(args.stable);
+        assert!(
+            Cli::try_parse_from(["sub-tools", "cleanup", "a.ass", "--stable", "--keep-order"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_shift_segments() {
+        let s = Duration::from_secs;
+        let contents = "# Ad break at 10 minutes\n10:00 20:00 2.5\n\n20:00 01:00:00 4.1\n";
+        let segments = parse_shift_segments(contents).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].range.start, Some(s(600)));
+        assert_eq!(segments[1].range.end, Some(s(3600)));
+        assert_eq!(
+            segments[1].by,
+            SignedDuration::forward(Duration::from_millis(4100))
+        );
+
+        // A line starting where the first segment ends is only shifted by the second
+        let starts = [s(599), s(600), s(1200), s(3600)];
+        assert_eq!(
+            assign_segments(&segments, &starts),
+            [None, Some(0), Some(1), None]
+        );
+
+        assert!(parse_shift_segments("10:00 20:00 2.5\n15:00 30:00 1\n").is_err());
+        assert!(parse_shift_segments("20:00 10:00 2.5\n").is_err());
+        assert!(parse_shift_segments("10:00 20:00\n").is_err());
+        assert!(parse_shift_segments("10:00 20:00 0\n").is_err());
+    }
+
+    #[test]
+    fn test_timing_list() {
+        let ms = Duration::from_millis;
+        let list =
+            "# index start end\n2\t00:00:15,100\t00:00:17,000\n\n1  12.48  14.920\n3 00:20.500 21\n";
+        assert_eq!(
+            parse_timing_list(list).unwrap(),
+            [
+                (ms(12480), ms(14920)),
+                (ms(15100), ms(17000)),
+                (ms(20500), ms(21000)),
+            ]
+        );
+        assert!(parse_timing_list("1 1 2\n3 3 4\n").is_err());
+        assert!(parse_timing_list("1 1 2\n1 3 4\n").is_err());
+        assert!(parse_timing_list("0 1 2\n").is_err());
+        assert!(parse_timing_list("1 2 1\n").is_err());
+        assert!(parse_timing_list("1 1\n").is_err());
+        assert!(check_timing_count(&[(ms(0), ms(1))], 2).is_err());
+    }
+
+    #[test]
+    fn test_cleanup_options() {
+        let cli = Cli::try_parse_from([
+            "sub-tools",
+            "cleanup",
+            "a.ass",
+            "--fix-zero-duration",
+            "--pad-end",
+            "0.05",
+            "--max-lines",
+            "2",
+            "--flatten-layers",
+        ])
+        .unwrap();
+        let Subcommands::Cleanup(args) = cli.command else {
+            panic!("expected the cleanup subcommand");
+        };
+        let options = args.options().unwrap();
+        assert_eq!(options.fix_zero_duration, Some(Duration::from_secs(1)));
+        assert_eq!(options.pad_start, None);
+        assert_eq!(options.pad_end, Some(Duration::from_millis(50)));
+        assert_eq!(options.max_lines, Some(2));
+        assert_eq!(options.layer, Some(0));
+    }
+
+    #[test]
+    fn test_optional_values_need_equals() {
+        let parse = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["sub-tools", "cleanup"].iter().chain(args)).unwrap();
+            let Subcommands::Cleanup(args) = cli.command else {
+                panic!("expected the cleanup subcommand");
+            };
+            args
+        };
+        // The file isn't taken as the value of the option
+        let args = parse(&["--fix-zero-duration", "a.ass"]);
+        assert_eq!(args.file, PathBuf::from("a.ass"));
+        assert_eq!(args.fix_zero_duration, Some(1.0));
+        let args = parse(&["--fix-zero-duration=0.5", "a.ass"]);
+        assert_eq!(args.fix_zero_duration, Some(0.5));
+        let args = parse(&["--split-lines", "a.ass"]);
+        assert_eq!(args.file, PathBuf::from("a.ass"));
+        assert_eq!(args.split_lines, Some(SplitTimingArg::Share));
+        let args = parse(&["--split-lines=divide", "a.ass"]);
+        assert_eq!(args.split_lines, Some(SplitTimingArg::Divide));
+        let args = parse(&["--join-sentences", "a.ass"]);
+        assert_eq!(args.file, PathBuf::from("a.ass"));
+        assert_eq!(args.join_sentences, Some(0.5));
+    }
+
+    #[test]
+    fn test_cleanup_config_precedence() {
+        let config: crate::config::Config =
+            toml::from_str("[cleanup]\nfix-jp = true\ncase = \"upper\"\nmax-lines = 2\n").unwrap();
+        let cli =
+            Cli::try_parse_from(["sub-tools", "cleanup", "a.srt", "--max-lines", "3"]).unwrap();
+        let Subcommands::Cleanup(mut args) = cli.command else {
+            panic!("expected the cleanup subcommand");
+        };
+        args.apply_config(&config.cleanup).unwrap();
+        assert!(args.fix_japanese);
+        assert_eq!(args.case, Some(TextCaseArg::Upper));
+        assert_eq!(args.max_lines, Some(3));
+        assert!(toml::from_str::<crate::config::Config>("[cleanup]\nunknown = 1\n").is_err());
+
+        let config: crate::config::Config =
+            toml::from_str("[cleanup]\ntrim = true\nstrict = true\nprofile = \"netflix\"\n")
+                .unwrap();
+        for (flags, trim) in [
+            (&["--no-trim"][..], false),
+            (&["--no-trim", "--trim"][..], true),
+            (&["--trim", "--no-trim"][..], false),
+        ] {
+            let cli = Cli::try_parse_from(
+                [
+                    "sub-tools",
+                    "cleanup",
+                    "a.srt",
+                    "--no-strict",
+                    "--no-collapse-spaces",
+                ]
+                .iter()
+                .chain(flags),
+            )
+            .unwrap();
+            let Subcommands::Cleanup(mut args) = cli.command else {
+                panic!("expected the cleanup subcommand");
+            };
+            args.apply_config(&config.cleanup).unwrap();
+            args.apply_profile();
+            assert_eq!(args.trim, trim);
+            assert!(!args.parse.strict && !args.collapse_spaces);
+        }
+
+        for invalid in ["min-gap = nan", "pad-end = inf", "snap-fps = -inf"] {
+            let config: crate::config::Config =
+                toml::from_str(&format!("[cleanup]\n{invalid}\n")).unwrap();
+            let cli = Cli::try_parse_from(["sub-tools", "cleanup", "a.srt"]).unwrap();
+            let Subcommands::Cleanup(mut args) = cli.command else {
+                panic!("expected the cleanup subcommand");
+            };
+            assert!(args.apply_config(&config.cleanup).is_err());
+        }
+
+        let config: crate::config::Config = toml::from_str("[cleanup]\nmerge-gap = 0.5\n").unwrap();
+        let cli =
+            Cli::try_parse_from(["sub-tools", "cleanup", "a.srt", "--min-gap", "0.1"]).unwrap();
+        let Subcommands::Cleanup(mut args) = cli.command else {
+            panic!("expected the cleanup subcommand");
+        };
+        assert!(args.apply_config(&config.cleanup).is_err());
+    }
+
+    #[test]
+    fn test_netflix_profile() {
+        let args = |flags: &[&str]| {
+            let cli = Cli::try_parse_from(
+                ["sub-tools", "cleanup", "a.srt", "--profile", "netflix"]
+                    .iter()
+                    .chain(flags),
+            )
+            .unwrap();
+            let Subcommands::Cleanup(mut args) = cli.command else {
+                panic!("expected the cleanup subcommand");
+            };
+            args.apply_profile();
+            args
+        };
+        let netflix = args(&[]);
+        assert_eq!(netflix.snap_fps, Some(NETFLIX_SNAP_FPS));
+        assert_eq!(netflix.max_lines, Some(2));
+        assert!(netflix.collapse_spaces);
+        assert_eq!(args(&["--snap-fps", "25"]).snap_fps, Some(25.0));
+    }
+
+    #[test]
+    fn test_fit_scale_factor() {
+        let cli = Cli::try_parse_from(["sub-tools", "fit", "a.srt", "--video-duration", "20:00"])
+            .unwrap();
+        let Subcommands::Fit(args) = cli.command else {
+            panic!("expected the fit subcommand");
+        };
+        assert_eq!(args.scale_factor(Duration::from_secs(1200)), None);
+        assert_eq!(args.scale_factor(Duration::from_secs(1250)), Some(0.96));
+    }
 }
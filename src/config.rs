@@ -0,0 +1,108 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::cli::{
+    CleanupProfile, ConvertFormat, PunctuationWidthArg, SplitTimingArg, SrtSeparator,
+    TagCategoryArg, TextCaseArg,
+};
+
+/// The configuration file that is read from the working directory.
+pub const CONFIG_FILE_NAME: &str = ".sub-tools.toml";
+
+/// Default options for the subcommands.
+///
+/// Every option is optional and options given on the command line take
+/// precedence over the ones in the configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub convert: ConvertConfig,
+    pub shift: ShiftConfig,
+    pub cleanup: CleanupConfig,
+}
+
+/// The `[convert]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ConvertConfig {
+    /// Only used when neither `--to` nor an output file is given.
+    pub to: Option<ConvertFormat>,
+    pub strict: Option<bool>,
+    pub compact_format: Option<bool>,
+    pub bom: Option<bool>,
+    pub srt_sep: Option<SrtSeparator>,
+    pub keep_blank_cues: Option<bool>,
+}
+
+/// The `[shift]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ShiftConfig {
+    pub strict: Option<bool>,
+    pub compact_format: Option<bool>,
+    pub bom: Option<bool>,
+    pub srt_sep: Option<SrtSeparator>,
+}
+
+/// The `[cleanup]` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CleanupConfig {
+    pub strict: Option<bool>,
+    pub compact_format: Option<bool>,
+    pub bom: Option<bool>,
+    pub srt_sep: Option<SrtSeparator>,
+    pub keep_blank_cues: Option<bool>,
+    pub comments: Option<bool>,
+    pub unused_styles: Option<bool>,
+    pub dedup_styles: Option<bool>,
+    pub fix_jp: Option<bool>,
+    pub auto_fix_jp: Option<bool>,
+    pub merge_simultaneous: Option<bool>,
+    pub stable: Option<bool>,
+    pub keep_order: Option<bool>,
+    pub split_lines: Option<SplitTimingArg>,
+    pub flatten_layers: Option<bool>,
+    pub case: Option<TextCaseArg>,
+    pub punct: Option<PunctuationWidthArg>,
+    pub strip_tags: Option<Vec<TagCategoryArg>>,
+    pub join_sentences: Option<f32>,
+    pub strip_html: Option<bool>,
+    pub collapse_spaces: Option<bool>,
+    pub trim: Option<bool>,
+    pub max_lines: Option<u16>,
+    pub min_duration: Option<f32>,
+    pub max_duration: Option<f32>,
+    pub fix_zero_duration: Option<f32>,
+    pub merge_gap: Option<f32>,
+    pub min_gap: Option<f32>,
+    pub pad_start: Option<f32>,
+    pub pad_end: Option<f32>,
+    pub snap_fps: Option<f64>,
+    pub profile: Option<CleanupProfile>,
+}
+
+impl Config {
+    /// Loads the configuration from the given path.
+    ///
+    /// If no path is given then [`CONFIG_FILE_NAME`] is read from the working
+    /// directory if it exists, otherwise the default configuration is returned.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let path = Path::new(CONFIG_FILE_NAME);
+                if !path.exists() {
+                    return Ok(Self::default());
+                }
+                path
+            }
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read config file {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("invalid config file {}", path.display()))
+    }
+}
@@ -3,9 +3,26 @@ fn is_halfwidth_kana(c: char) -> bool {
     ('\u{FF65}'..='\u{FF9F}').contains(&c)
 }
 
-#[inline]
-fn is_katakana(c: char) -> bool {
-    ('\u{30A0}'..='\u{30FA}').contains(&c)
+/// Returns the katakana with a dakuten (゛), e.g. カ becomes ガ.
+fn voiced_katakana(c: char) -> Option<char> {
+    match c {
+        'ウ' => Some('ヴ'),
+        // In the katakana table, a voiced dakuten is merely +1 char from the previous one
+        'カ' | 'キ' | 'ク' | 'ケ' | 'コ' | 'サ' | 'シ' | 'ス' | 'セ' | 'ソ' | 'タ' | 'チ'
+        | 'ツ' | 'テ' | 'ト' | 'ハ' | 'ヒ' | 'フ' | 'ヘ' | 'ホ' => {
+            char::from_u32(c as u32 + 1)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the katakana with a handakuten (゜), e.g. ハ becomes パ.
+fn semi_voiced_katakana(c: char) -> Option<char> {
+    match c {
+        // In the katakana table, a handakuten is merely +2 chars from the previous one
+        'ハ' | 'ヒ' | 'フ' | 'ヘ' | 'ホ' => char::from_u32(c as u32 + 2),
+        _ => None,
+    }
 }
 
 #[inline]
@@ -82,38 +99,22 @@ fn replace_halfwith_kana(input: &str) -> Option<String> {
             'ﾛ' => output.push('ロ'),
             'ﾜ' => output.push('ワ'),
             'ﾝ' => output.push('ン'),
-            // These two are a bit of a special case
-            // They might cause some mess ups, but ideally it doesn't
-            'ﾞ' => {
-                // In the katakana table, a voiced dakuten
-                // is merely +1 char from the previous one
-                match output.pop() {
-                    Some(ch) => {
-                        if is_katakana(ch) {
-                            output.push(char::from_u32(ch as u32 + 1).unwrap());
-                        } else {
-                            output.push(ch);
-                            output.push('゛');
-                        }
-                    }
-                    None => output.push('゛'),
+            // These two combine with the previous character if it can take
+            // the mark, otherwise the stand-alone full-width mark is used
+            'ﾞ' => match output.chars().next_back().and_then(voiced_katakana) {
+                Some(voiced) => {
+                    output.pop();
+                    output.push(voiced);
                 }
-            }
-            'ﾟ' => {
-                // In the katakana table, a voiced dakuten
-                // is merely +2 char from the previous one
-                match output.pop() {
-                    Some(ch) => {
-                        if is_katakana(ch) {
-                            output.push(char::from_u32(ch as u32 + 2).unwrap());
-                        } else {
-                            output.push(ch);
-                            output.push('゜');
-                        }
-                    }
-                    None => output.push('゜'),
+                None => output.push('゛'),
+            },
+            'ﾟ' => match output.chars().next_back().and_then(semi_voiced_katakana) {
+                Some(semi_voiced) => {
+                    output.pop();
+                    output.push(semi_voiced);
                 }
-            }
+                None => output.push('゜'),
+            },
             _ => output.push(ch),
         }
     }
@@ -146,3 +147,223 @@ pub fn fix_broken_text(text: &mut String) {
 pub fn contains_japanese(s: &str) -> bool {
     s.chars().any(is_japanese)
 }
+
+/// Japanese punctuation and the ASCII punctuation it corresponds to.
+const PUNCTUATION: [(char, char); 4] = [('、', ','), ('。', '.'), ('！', '!'), ('？', '?')];
+
+/// Full-width forms of `,` and `.` that are only ever converted to ASCII,
+/// since [`PUNCTUATION`] is preferred when converting to full-width.
+const FULLWIDTH_ALTERNATIVES: [(char, char); 2] = [('，', ','), ('．', '.')];
+
+/// Converts full-width punctuation such as `、。！？` into ASCII.
+pub fn punctuation_to_ascii(text: &str) -> String {
+    text.chars()
+        .map(|ch| {
+            PUNCTUATION
+                .iter()
+                .chain(&FULLWIDTH_ALTERNATIVES)
+                .find_map(|&(full, ascii)| (full == ch).then_some(ascii))
+                .unwrap_or(ch)
+        })
+        .collect()
+}
+
+/// Converts ASCII punctuation such as `,.!?` into full-width.
+///
+/// This is conservative, punctuation is only converted if it comes right
+/// after a Japanese character and isn't part of a run such as `...` or `!?`.
+pub fn punctuation_to_fullwidth(text: &str) -> String {
+    let to_fullwidth = |ch: char| {
+        PUNCTUATION
+            .iter()
+            .find_map(|&(full, ascii)| (ascii == ch).then_some(full))
+    };
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut previous = None;
+    while let Some(ch) = chars.next() {
+        let next_is_punctuation = chars.peek().is_some_and(|&c| to_fullwidth(c).is_some());
+        match to_fullwidth(ch) {
+            Some(full) if previous.is_some_and(is_japanese) && !next_is_punctuation => {
+                result.push(full)
+            }
+            _ => result.push(ch),
+        }
+        previous = Some(ch);
+    }
+    result
+}
+
+/// The writing system that most of the text in a subtitle file is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Script {
+    /// Kana, possibly mixed with kanji.
+    Japanese,
+    /// Han characters without any kana.
+    Chinese,
+    Korean,
+    Latin,
+    Cyrillic,
+    Arabic,
+    /// Letters that don't belong to any of the other scripts.
+    Other,
+}
+
+impl Script {
+    /// Returns the script the letter belongs to.
+    ///
+    /// Han characters are returned as [`Script::Chinese`] since they can't
+    /// be told apart from kanji without looking at the surrounding text.
+    fn classify(ch: char) -> Self {
+        const RANGES: [(std::ops::RangeInclusive<char>, Script); 9] = [
+            ('\u{3040}'..='\u{30ff}', Script::Japanese), // Hiragana + Katakana
+            ('\u{ff66}'..='\u{ff9d}', Script::Japanese), // Half-width Katakana
+            ('\u{4e00}'..='\u{9fff}', Script::Chinese),  // CJK Unified Ideographs
+            ('\u{1100}'..='\u{11ff}', Script::Korean),   // Hangul Jamo
+            ('\u{ac00}'..='\u{d7af}', Script::Korean),   // Hangul Syllables
+            ('\u{0400}'..='\u{04ff}', Script::Cyrillic),
+            ('\u{0600}'..='\u{06ff}', Script::Arabic),
+            ('A'..='z', Script::Latin),
+            ('\u{00c0}'..='\u{024f}', Script::Latin), // Latin-1 Supplement + Extended
+        ];
+        RANGES
+            .iter()
+            .find(|(range, _)| range.contains(&ch))
+            .map_or(Script::Other, |(_, script)| *script)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Script::Japanese => "Japanese",
+            Script::Chinese => "Chinese",
+            Script::Korean => "Korean",
+            Script::Latin => "Latin",
+            Script::Cyrillic => "Cyrillic",
+            Script::Arabic => "Arabic",
+            Script::Other => "Other",
+        }
+    }
+}
+
+impl std::fmt::Display for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Guesses the dominant script of the given lines of dialogue.
+///
+/// This counts the letters of each script in the first few hundred lines
+/// and returns the most common one. Han characters count as Japanese if
+/// any kana is found, since kanji and hanzi share the same range.
+///
+/// Ties go to the script that comes first in [`Script`], so the result
+/// doesn't depend on hashing order.
+///
+/// Returns `None` if there are no letters at all.
+pub fn detect_script<'a, I>(lines: I) -> Option<Script>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    const SAMPLE_SIZE: usize = 500;
+
+    let mut counts = std::collections::HashMap::new();
+    let letters = lines
+        .into_iter()
+        .take(SAMPLE_SIZE)
+        .flat_map(str::chars)
+        .filter(|c| c.is_alphabetic());
+    for ch in letters {
+        *counts.entry(Script::classify(ch)).or_insert(0usize) += 1;
+    }
+
+    if counts.contains_key(&Script::Japanese) {
+        if let Some(han) = counts.remove(&Script::Chinese) {
+            *counts.entry(Script::Japanese).or_default() += han;
+        }
+    }
+    most_common(counts)
+}
+
+/// Returns the script with the highest count, see [`detect_script`] for how
+/// ties are broken.
+fn most_common<I>(counts: I) -> Option<Script>
+where
+    I: IntoIterator<Item = (Script, usize)>,
+{
+    counts
+        .into_iter()
+        .max_by_key(|&(script, count)| (count, std::cmp::Reverse(script)))
+        .map(|(script, _)| script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_script() {
+        let japanese = ["もう ４月というのに", "何やら 今日は冷えますね", "OK"];
+        assert_eq!(detect_script(japanese), Some(Script::Japanese));
+        assert_eq!(
+            detect_script(["Hello there.", "How are you?"]),
+            Some(Script::Latin)
+        );
+        assert_eq!(detect_script(["Привет, мир", "OK"]), Some(Script::Cyrillic));
+        assert_eq!(detect_script(["你好世界"]), Some(Script::Chinese));
+        assert_eq!(detect_script(["안녕하세요"]), Some(Script::Korean));
+        assert_eq!(detect_script(["123 ...", ""]), None);
+    }
+
+    #[test]
+    fn test_most_common_ties() {
+        let latin_first = [(Script::Latin, 2), (Script::Cyrillic, 2)];
+        let cyrillic_first = [(Script::Cyrillic, 2), (Script::Latin, 2)];
+        assert_eq!(most_common(latin_first), Some(Script::Latin));
+        assert_eq!(most_common(cyrillic_first), Some(Script::Latin));
+        assert_eq!(
+            most_common([(Script::Cyrillic, 1), (Script::Korean, 1)]),
+            Some(Script::Korean)
+        );
+        assert_eq!(
+            most_common([(Script::Latin, 1), (Script::Cyrillic, 3)]),
+            Some(Script::Cyrillic)
+        );
+        assert_eq!(most_common([]), None);
+    }
+
+    #[test]
+    fn test_halfwidth_voiced_marks() {
+        assert_eq!(replace_halfwith_kana("ｶﾞｷﾞﾊﾟﾋﾟｳﾞ").unwrap(), "ガギパピヴ");
+        // Nothing to combine with at the start of the string
+        assert_eq!(replace_halfwith_kana("ﾞｱ").unwrap(), "゛ア");
+        assert_eq!(replace_halfwith_kana("ﾟ").unwrap(), "゜");
+        // The preceding character can't take the mark so it's left as-is
+        assert_eq!(replace_halfwith_kana("aﾞ").unwrap(), "a゛");
+        assert_eq!(replace_halfwith_kana("あﾞ").unwrap(), "あ゛");
+        assert_eq!(replace_halfwith_kana("ｱﾞ").unwrap(), "ア゛");
+        assert_eq!(replace_halfwith_kana("ガﾞ").unwrap(), "ガ゛");
+        assert_eq!(replace_halfwith_kana("ｶﾟ").unwrap(), "カ゜");
+        // Full-width kana before the first half-width character still combines
+        assert_eq!(replace_halfwith_kana("カﾞ").unwrap(), "ガ");
+    }
+
+    #[test]
+    fn test_punctuation() {
+        assert_eq!(
+            punctuation_to_ascii("はい、そうです。本当！？"),
+            "はい,そうです.本当!?"
+        );
+        assert_eq!(punctuation_to_ascii("Ｑ．３，４"), "Ｑ.３,４");
+        assert_eq!(
+            punctuation_to_fullwidth("はい,そうです.本当!?"),
+            "はい、そうです。本当!?"
+        );
+        assert_eq!(
+            punctuation_to_fullwidth("そう...3.14, OK!"),
+            "そう...3.14, OK!"
+        );
+        assert_eq!(punctuation_to_fullwidth("ね?"), "ね？");
+    }
+}
@@ -0,0 +1,213 @@
+//! A JSON representation of the subtitle model.
+//!
+//! This is meant for processing subtitles in other languages, e.g. by
+//! dumping them with `convert --to json`. Timings are in milliseconds and
+//! colours are `#RRGGBBAA` strings.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ass::{Ass, Event, Style},
+    srt::Dialogue,
+};
+
+/// An error that occurs when reading the JSON model.
+#[derive(Debug)]
+pub enum JsonError {
+    /// The JSON is malformed or a required field is missing.
+    Syntax(serde_json::Error),
+    /// The cue at the given index (starting from 1) ends before it starts.
+    InvertedTiming(usize),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Syntax(_) => f.write_str("invalid subtitle json"),
+            JsonError::InvertedTiming(index) => {
+                write!(
+                    f,
+                    "invalid subtitle json: cue {index} ends before it starts"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JsonError::Syntax(e) => Some(e),
+            JsonError::InvertedTiming(_) => None,
+        }
+    }
+}
+
+/// The structured model of a subtitle file.
+///
+/// The format is stored in a `"format"` key, either `"ass"` or `"srt"`.
+/// .vtt files use the same model as .srt files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Subtitles {
+    Ass {
+        /// The `[Script Info]` key-value pairs in order.
+        script_info: Vec<(String, String)>,
+        styles: Vec<Style>,
+        events: Vec<Event>,
+    },
+    Srt {
+        dialogue: Vec<Dialogue>,
+    },
+}
+
+impl Subtitles {
+    /// Creates the model from an .ass file.
+    ///
+    /// Only the script info, styles, and events are kept. Other sections,
+    /// such as embedded fonts, and comments in the script info are dropped.
+    pub fn from_ass(ass: &Ass) -> Self {
+        let script_info = ass
+            .sections
+            .iter()
+            .filter_map(|s| s.as_script_info())
+            .flat_map(|s| s.items())
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+        Self::Ass {
+            script_info,
+            styles: ass.styles().cloned().collect(),
+            events: ass.events().cloned().collect(),
+        }
+    }
+
+    pub fn from_dialogue(dialogue: Vec<Dialogue>) -> Self {
+        Self::Srt { dialogue }
+    }
+
+    /// Parses the model from JSON, as written by [`Subtitles::to_json`].
+    ///
+    /// Every field is required except for comments. Cues that end
+    /// before they start are rejected.
+    pub fn from_json(s: &str) -> Result<Self, JsonError> {
+        let subtitles = serde_json::from_str::<Self>(s).map_err(JsonError::Syntax)?;
+        let timings = match &subtitles {
+            Self::Ass { events, .. } => events.iter().map(|e| (e.start, e.end)).collect::<Vec<_>>(),
+            Self::Srt { dialogue } => dialogue.iter().map(|d| (d.start, d.end)).collect(),
+        };
+        if let Some(index) = timings.iter().position(|(start, end)| start > end) {
+            return Err(JsonError::InvertedTiming(index + 1));
+        }
+        Ok(subtitles)
+    }
+
+    /// Builds an .ass script from the model.
+    ///
+    /// .srt dialogue is converted the same way as [`Ass::from_srt`].
+    pub fn into_ass(self) -> Ass {
+        match self {
+            Self::Ass {
+                script_info,
+                styles,
+                events,
+            } => Ass::from_parts(script_info, styles, events),
+            Self::Srt { dialogue } => Ass::from_srt(dialogue),
+        }
+    }
+
+    /// Serializes the model into pretty printed JSON ending with a newline.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ass::Colour;
+
+    #[test]
+    fn test_ass_to_json() {
+        let ass = "[Script Info]\nTitle: Test\nScriptType: v4.00+\n\n[V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\nStyle: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H80000000,0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.50,0:00:03.25,Default,,0,0,0,,Hello\n"
+            .parse::<Ass>()
+            .unwrap();
+        let json = Subtitles::from_ass(&ass).to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["format"], "ass");
+        assert_eq!(
+            value["script_info"][0],
+            serde_json::json!(["Title", "Test"])
+        );
+        assert_eq!(value["styles"][0]["background_colour"], "#00000080");
+        assert_eq!(value["events"][0]["start"], 1500);
+        assert_eq!(value["events"][0]["end"], 3250);
+        assert_eq!(value["events"][0]["kind"], "Dialogue");
+
+        let Subtitles::Ass { styles, events, .. } = serde_json::from_str(&json).unwrap() else {
+            panic!("expected ass subtitles");
+        };
+        assert_eq!(
+            styles[0].background_colour,
+            Colour::from_rgba(0, 0, 0, 0x80)
+        );
+        assert_eq!(events[0].start, Duration::from_millis(1500));
+        assert_eq!(events[0].text, "Hello");
+    }
+
+    #[test]
+    fn test_json_to_ass() {
+        let json = r##"{
+            "format": "ass",
+            "script_info": [["Title", "Generated"], ["ScriptType", "v4.00+"]],
+            "styles": [],
+            "events": [{
+                "kind": "Dialogue", "layer": 0, "start": 1000, "end": 2000,
+                "style": "Default", "name": "", "margin_l": 0, "margin_r": 0,
+                "margin_v": 0, "effect": "", "text": "Hello"
+            }]
+        }"##;
+        let ass = Subtitles::from_json(json).unwrap().into_ass();
+        assert_eq!(
+            ass.sections[0].as_script_info().unwrap().title(),
+            "Generated"
+        );
+        let mut output = Vec::new();
+        ass.save_to_writer(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n"));
+
+        let missing = json.replace(r#""layer": 0, "#, "");
+        assert!(matches!(
+            Subtitles::from_json(&missing),
+            Err(JsonError::Syntax(_))
+        ));
+        let inverted = json.replace(r#""end": 2000"#, r#""end": 500"#);
+        assert!(matches!(
+            Subtitles::from_json(&inverted),
+            Err(JsonError::InvertedTiming(1))
+        ));
+    }
+
+    #[test]
+    fn test_dialogue_to_json() {
+        let dialogue =
+            crate::srt::load_from_string("1\n00:00:01,000 --> 00:00:02,500\nHello\n\n").unwrap();
+        let json = Subtitles::from_dialogue(dialogue.clone())
+            .to_json()
+            .unwrap();
+        assert_eq!(
+            json,
+            "{\n  \"format\": \"srt\",\n  \"dialogue\": [\n    {\n      \"position\": 1,\n      \"start\": 1000,\n      \"end\": 2500,\n      \"text\": \"Hello\"\n    }\n  ]\n}\n"
+        );
+        match serde_json::from_str(&json).unwrap() {
+            Subtitles::Srt { dialogue: parsed } => assert_eq!(parsed, dialogue),
+            _ => panic!("expected srt subtitles"),
+        }
+    }
+}
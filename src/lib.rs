@@ -1,27 +1,47 @@
-use std::io::{Read as _, Seek as _};
-
 pub mod ass;
+pub mod cleanup;
 pub mod cli;
+pub mod config;
 pub mod japanese;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod srt;
+pub mod testing;
 pub mod utils;
 pub mod vtt;
 
+/// The UTF-8 byte order mark.
+pub(crate) const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
+/// Removes every UTF-8 BOM from the buffer.
+///
+/// Files that were concatenated together can have one where they were
+/// joined rather than only at the start, which would end up in the text.
+pub(crate) fn strip_bom(buffer: &mut String) {
+    if buffer.contains('\u{feff}') {
+        buffer.retain(|c| c != '\u{feff}');
+    }
+}
+
 /// Loads a file into a string.
 ///
-/// This checks for the UTF-8 BOM and strips it
+/// See [`decode_file`] for how the contents are cleaned up.
 pub(crate) fn load_file(path: &std::path::Path) -> std::io::Result<String> {
-    let mut fp = std::fs::File::open(path)?;
-
-    let mut buffer = String::new();
-    // Try to check if there's a UTF-8 BOM somewhere
-    let mut bom: [u8; 3] = [0; 3];
-    fp.read_exact(&mut bom)?;
-    if bom != [0xEF, 0xBB, 0xBF] {
-        fp.rewind()?;
-    }
+    decode_file(std::fs::read(path)?)
+}
 
-    fp.read_to_string(&mut buffer)?;
+/// Turns the raw contents of a file into a string.
+///
+/// This strips the UTF-8 BOM, including any in the middle of the file,
+/// and converts CRLF line endings into LF.
+pub(crate) fn decode_file(bytes: Vec<u8>) -> std::io::Result<String> {
+    let mut buffer = String::from_utf8(bytes).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        )
+    })?;
+    strip_bom(&mut buffer);
 
     if buffer.contains("\r\n") {
         buffer = buffer.replace("\r\n", "\n");
@@ -30,8 +50,74 @@ pub(crate) fn load_file(path: &std::path::Path) -> std::io::Result<String> {
     Ok(buffer)
 }
 
+/// Converts the dialogue events of an .ass file into .srt dialogue.
+///
+/// Comments are skipped and override tags are removed, apart from `\an`
+/// positioning. If `font_sizes` is true then a `\fs` override at the start
+/// of the text is kept as a `<font size>` tag.
+pub fn ass_to_dialogue(ass: ass::Ass, font_sizes: bool) -> Vec<srt::Dialogue> {
+    ass.sections
+        .into_iter()
+        .filter_map(|s| s.try_into_events().ok())
+        .flat_map(|e| e.into_events().filter(|e| e.kind.is_dialogue()).enumerate())
+        .map(|(idx, e)| srt::Dialogue {
+            position: idx as u32 + 1,
+            start: e.start,
+            end: e.end,
+            text: if font_sizes {
+                ass::add_font_size(&e.text, ass::clean_ass_text(&e.text))
+            } else {
+                ass::clean_ass_text(&e.text)
+            },
+        })
+        .collect()
+}
+
+/// Parses the dialogue of a subtitle file in any of the supported formats.
+///
+/// Dialogue events from .ass files are converted the same way as `convert`
+/// does, see [`ass_to_dialogue`]. With [`ass::ParseOptions::strict`], dialogue
+/// that ends before it starts is rejected in every format.
+///
+/// ```
+/// use sub_tools::ass::ParseOptions;
+///
+/// let dialogue = sub_tools::parse_dialogue("[Script Info]\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,{\\b1}Hello\\Nworld\n", ParseOptions::default()).unwrap();
+/// assert_eq!(dialogue[0].text, "Hello\nworld");
+/// ```
+pub fn parse_dialogue(
+    contents: &str,
+    options: ass::ParseOptions,
+) -> anyhow::Result<Vec<srt::Dialogue>> {
+    let dialogue = match SubtitleFormat::detect(contents).ok_or(FormatError::Unrecognized)? {
+        SubtitleFormat::Ass => ass_to_dialogue(ass::Ass::parse_with(contents, options)?, false),
+        SubtitleFormat::Srt => srt::load_from_string(contents)?,
+        SubtitleFormat::Vtt => vtt::load_from_string(contents)?,
+    };
+    if let Some(d) = dialogue.iter().find(|d| options.strict && !d.is_valid()) {
+        anyhow::bail!("dialogue {} ends before it starts", d.position);
+    }
+    Ok(dialogue)
+}
+
+/// Loads the dialogue of a subtitle file in any of the supported formats.
+///
+/// See [`parse_dialogue`] for how each format is handled.
+pub fn load_dialogue(
+    path: &std::path::Path,
+    options: ass::ParseOptions,
+) -> anyhow::Result<Vec<srt::Dialogue>> {
+    use anyhow::Context;
+    let contents = load_file(path).with_context(|| format!("could not read {}", path.display()))?;
+    parse_dialogue(&contents, options).with_context(|| format!("could not load {}", path.display()))
+}
+
 /// Support subtitle formats
+///
+/// More formats might be added in the future, so matching on this
+/// requires a wildcard arm.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum SubtitleFormat {
     Srt,
     Ass,
@@ -51,4 +137,89 @@ impl SubtitleFormat {
             None
         }
     }
+
+    /// Returns the format that uses the given file extension, ignoring case.
+    ///
+    /// The extension is given without the leading dot.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [Self::Srt, Self::Ass, Self::Vtt]
+            .into_iter()
+            .find(|format| extension.eq_ignore_ascii_case(format.extension()))
+    }
+
+    /// Returns the format that uses the extension of the path.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use sub_tools::SubtitleFormat;
+    ///
+    /// assert_eq!(SubtitleFormat::from_path(Path::new("a.SRT")), Some(SubtitleFormat::Srt));
+    /// assert_eq!(SubtitleFormat::from_path(Path::new("a.txt")), None);
+    /// ```
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    /// Returns the file extension used by the format, without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Ass => "ass",
+            Self::Vtt => "vtt",
+        }
+    }
+
+    /// Returns a human readable name for the format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Srt => "SubRip (.srt)",
+            Self::Ass => "SubStation Alpha (.ass)",
+            Self::Vtt => "WebVTT (.vtt)",
+        }
+    }
+}
+
+impl std::fmt::Display for SubtitleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// An error for subtitle formats that can't be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    /// The subtitle format could not be recognised.
+    Unrecognized,
+    /// The subtitle format isn't supported by the operation.
+    Unsupported(SubtitleFormat, &'static str),
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::Unrecognized => f.write_str("could not recognize subtitle type"),
+            FormatError::Unsupported(format, operation) => {
+                write!(f, "{format} subtitles are not supported by {operation}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_bom() {
+        let mut buffer = String::from(
+            "\u{feff}1\n00:00:01,000 --> 00:00:02,000\nHello\n\n\u{feff}2\n00:00:03,000 --> 00:00:04,000\n\u{feff}Wor\u{feff}ld\n",
+        );
+        strip_bom(&mut buffer);
+        let dialogue = srt::load_from_string(&buffer).unwrap();
+        assert_eq!(dialogue.len(), 2);
+        assert_eq!(dialogue[1].position, 2);
+        assert_eq!(dialogue[1].text, "World");
+    }
 }
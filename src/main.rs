@@ -1,15 +1,45 @@
-use clap::Parser;
-use sub_tools::cli::{Cli, Subcommands};
+use std::process::ExitCode;
 
-fn main() -> anyhow::Result<()> {
-    let args = Cli::parse();
+use clap::Parser;
+use sub_tools::{
+    cli::{exit_code, Cli, Subcommands},
+    config::Config,
+};
 
+fn run(args: Cli) -> anyhow::Result<()> {
+    // Only the subcommands with options in the configuration file read it
+    let config = || Config::load(args.config.as_deref());
     match args.command {
-        Subcommands::Convert(convert_args) => convert_args.run()?,
+        Subcommands::Convert(mut convert_args) => {
+            convert_args.apply_config(&config()?.convert);
+            convert_args.run()?
+        }
         Subcommands::Info(info_args) => info_args.run()?,
-        Subcommands::Shift(shift_args) => shift_args.run()?,
-        Subcommands::Cleanup(cleanup_args) => cleanup_args.run()?,
+        Subcommands::Shift(mut shift_args) => {
+            shift_args.apply_config(&config()?.shift);
+            shift_args.run()?
+        }
+        Subcommands::Fit(fit_args) => fit_args.run()?,
+        Subcommands::Cleanup(mut cleanup_args) => {
+            cleanup_args.apply_config(&config()?.cleanup)?;
+            cleanup_args.run()?
+        }
+        Subcommands::Merge(merge_args) => merge_args.run()?,
+        Subcommands::Check(check_args) => check_args.run()?,
+        Subcommands::Diff(diff_args) => diff_args.run()?,
     }
 
     Ok(())
 }
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
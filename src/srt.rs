@@ -9,47 +9,103 @@ use std::{
 
 use anyhow::Context;
 
+use crate::utils::SignedDuration;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dialogue {
     pub position: u32,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_millis"))]
     pub start: Duration,
+    #[cfg_attr(feature = "serde", serde(with = "crate::utils::serde_millis"))]
     pub end: Duration,
     pub text: String,
 }
 
 impl Dialogue {
-    pub fn shift_by(&mut self, seconds: f32) {
-        let duration = Duration::from_secs_f32(seconds.abs());
-        if seconds < 0.0 {
-            self.start = self.start.saturating_sub(duration);
-            self.end = self.end.saturating_sub(duration);
-        } else {
-            self.start = self.start.saturating_add(duration);
-            self.end = self.end.saturating_add(duration);
+    /// Returns `true` if the dialogue doesn't end before it starts.
+    pub fn is_valid(&self) -> bool {
+        self.start <= self.end
+    }
+
+    /// Returns how long the dialogue is shown for.
+    ///
+    /// Dialogue that ends before it starts has a duration of zero.
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Returns `true` if the dialogue has no visible text, e.g. it's empty or
+    /// only has whitespace and formatting tags such as `<i>` or `{\an8}`.
+    ///
+    /// Anything else in brackets is text, e.g. `<laughs>` or `{sighs}`.
+    pub fn is_blank(&self) -> bool {
+        let mut rest = self.text.as_str();
+        while let Some(ch) = rest.chars().next() {
+            let tag_end = match ch {
+                '<' => rest
+                    .find('>')
+                    .filter(|&end| is_formatting_tag(&rest[1..end])),
+                '{' if rest[1..].starts_with('\\') => rest.find('}'),
+                _ => None,
+            };
+            match tag_end {
+                Some(end) => rest = &rest[end + 1..],
+                None if ch.is_whitespace() => rest = &rest[ch.len_utf8()..],
+                None => return false,
+            }
         }
+        true
+    }
+
+    /// Moves the start and end by the given delta.
+    ///
+    /// Timings saturate at zero rather than going negative.
+    pub fn shift(&mut self, delta: SignedDuration) {
+        self.start = delta.apply(self.start);
+        self.end = delta.apply(self.end);
+    }
+
+    /// Moves the start and end by the given seconds, negative being backward.
+    ///
+    /// This is a convenience wrapper around [`shift`](Self::shift).
+    pub fn shift_by(&mut self, seconds: f32) {
+        self.shift(SignedDuration::from_secs_f32(seconds));
     }
 }
 
-impl Display for Dialogue {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        fn duration_to_srt(f: &mut std::fmt::Formatter<'_>, d: &Duration) -> std::fmt::Result {
+impl Dialogue {
+    /// Writes the cue with the given separator before the milliseconds.
+    fn write_cue<W: std::fmt::Write>(&self, f: &mut W, separator: char) -> std::fmt::Result {
+        fn duration_to_srt<W: std::fmt::Write>(
+            f: &mut W,
+            d: &Duration,
+            separator: char,
+        ) -> std::fmt::Result {
             let seconds = d.as_secs();
             let (hours, seconds) = (seconds / 3600, seconds % 3600);
             let (minutes, seconds) = (seconds / 60, seconds % 60);
             let ms = d.subsec_millis();
             f.write_fmt(format_args!(
-                "{:02}:{:02}:{:02},{:03}",
-                hours, minutes, seconds, ms
+                "{:02}:{:02}:{:02}{}{:03}",
+                hours, minutes, seconds, separator, ms
             ))
         }
 
-        self.position.fmt(f)?;
-        f.write_str("\n")?;
-        duration_to_srt(f, &self.start)?;
+        writeln!(f, "{}", self.position)?;
+        duration_to_srt(f, &self.start, separator)?;
         f.write_str(" --> ")?;
-        duration_to_srt(f, &self.end)?;
+        duration_to_srt(f, &self.end, separator)?;
         f.write_str("\n")?;
-        f.write_str(&self.text)
+        // A blank line would end the cue early, so the text is written
+        // the same way it's read back
+        f.write_str(&normalize_cue_text(&self.text))
+    }
+}
+
+impl Display for Dialogue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_cue(f, ',')
     }
 }
 
@@ -80,14 +136,36 @@ impl Display for ParseDialogueError {
     }
 }
 
+/// Normalises the line breaks inside the text of a cue.
+///
+/// Lone `\r` characters become line breaks, trailing whitespace is
+/// removed from every line, and empty lines are dropped since a blank
+/// line would end the cue.
+///
+/// This is applied both when reading and writing cues so that text that
+/// was set by hand can't split a cue in two when it's read back.
+pub(crate) fn normalize_cue_text(text: &str) -> String {
+    text.split(['\r', '\n'])
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub(crate) fn parse_srt_time(s: &str) -> Option<Duration> {
     // HH:MM:SS,mmm
-    // HH is optional (due to VTT)
+    // HH is optional (due to VTT) so the components are read from the right
     let (rest, ms) = s.split_once([',', '.'])?;
-    let mut split = rest.trim().splitn(3, ':');
-    let hours: u64 = split.next()?.parse().ok().unwrap_or_default();
-    let minutes: u64 = split.next()?.parse().ok()?;
+    let mut split = rest.trim().rsplit(':');
     let seconds: u64 = split.next()?.parse().ok()?;
+    let minutes: u64 = split.next()?.parse().ok()?;
+    let hours: u64 = match split.next() {
+        Some(hours) => hours.parse().ok()?,
+        None => 0,
+    };
+    if split.next().is_some() {
+        return None;
+    }
     let seconds = seconds + (minutes * 60) + (hours * 3600);
     let nanos = ms.trim().parse::<u32>().ok()?.saturating_mul(1_000_000);
     Some(Duration::new(seconds, nanos))
@@ -106,19 +184,17 @@ impl FromStr for Dialogue {
             .ok_or(ParseDialogueError::Position)?;
         let (start, end) = match lines.next() {
             Some(times) => {
+                // Some tools write the arrow without the spaces around it, or with extra ones
                 let (start, end) = times
-                    .split_once(" --> ")
+                    .split_once("-->")
                     .ok_or(ParseDialogueError::Separator)?;
-                let start = parse_srt_time(start).ok_or(ParseDialogueError::Start)?;
-                let end = parse_srt_time(end).ok_or(ParseDialogueError::End)?;
+                let start = parse_srt_time(start.trim()).ok_or(ParseDialogueError::Start)?;
+                let end = parse_srt_time(end.trim()).ok_or(ParseDialogueError::End)?;
                 (start, end)
             }
             None => return Err(ParseDialogueError::Start),
         };
-        let text = lines
-            .next()
-            .ok_or(ParseDialogueError::EmptyDialogue)?
-            .to_owned();
+        let text = normalize_cue_text(lines.next().ok_or(ParseDialogueError::EmptyDialogue)?);
         Ok(Self {
             position,
             start,
@@ -128,10 +204,100 @@ impl FromStr for Dialogue {
     }
 }
 
+/// Calls the closure with the start and end of every dialogue so they can be modified.
+///
+/// This is the .srt equivalent of [`Ass::retime`](crate::ass::Ass::retime).
+///
+/// ```
+/// use std::time::Duration;
+/// use sub_tools::srt;
+///
+/// let mut dialogue = srt::load_from_string("1\n00:00:01,000 --> 00:00:02,000\nHello\n\n").unwrap();
+/// // Delay everything after the first second by 2 seconds
+/// srt::retime(&mut dialogue, |start, end| {
+///     if *start >= Duration::from_secs(1) {
+///         *start += Duration::from_secs(2);
+///         *end += Duration::from_secs(2);
+///     }
+/// });
+/// assert_eq!(dialogue[0].start, Duration::from_secs(3));
+/// ```
+pub fn retime<F>(dialogue: &mut [Dialogue], mut f: F)
+where
+    F: FnMut(&mut Duration, &mut Duration),
+{
+    for d in dialogue {
+        f(&mut d.start, &mut d.end);
+    }
+}
+
+/// A difference between two sets of dialogue, as returned by [`diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogueChange<'a> {
+    /// The dialogue only exists in the new set.
+    Added(&'a Dialogue),
+    /// The dialogue only exists in the old set.
+    Removed(&'a Dialogue),
+    /// The dialogue exists in both sets but the text or timing changed.
+    Changed(&'a Dialogue, &'a Dialogue),
+}
+
+/// Compares two sets of dialogue and returns the changes between them.
+///
+/// Dialogue is aligned by start time, so two cues are considered the same
+/// if their start times are within `tolerance` of each other. The changes
+/// are ordered by start time. Trailing whitespace in the text is ignored.
+pub fn diff<'a>(
+    old: &'a [Dialogue],
+    new: &'a [Dialogue],
+    tolerance: Duration,
+) -> Vec<DialogueChange<'a>> {
+    let mut old = old.iter().collect::<Vec<_>>();
+    let mut new = new.iter().collect::<Vec<_>>();
+    old.sort_by_key(|d| d.start);
+    new.sort_by_key(|d| d.start);
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        let (a, b) = (old[i], new[j]);
+        if a.start.abs_diff(b.start) <= tolerance {
+            if a.start != b.start || a.end != b.end || a.text.trim_end() != b.text.trim_end() {
+                changes.push(DialogueChange::Changed(a, b));
+            }
+            i += 1;
+            j += 1;
+        } else if a.start < b.start {
+            changes.push(DialogueChange::Removed(a));
+            i += 1;
+        } else {
+            changes.push(DialogueChange::Added(b));
+            j += 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|d| DialogueChange::Removed(d)));
+    changes.extend(new[j..].iter().map(|d| DialogueChange::Added(d)));
+    changes
+}
+
+/// Returns `true` if the inside of a `<...>` tag is one of the formatting
+/// tags players understand, e.g. `i`, `/b` or `font color="red"`.
+fn is_formatting_tag(tag: &str) -> bool {
+    let tag = tag.strip_prefix('/').unwrap_or(tag);
+    let name = tag.split_whitespace().next().unwrap_or_default();
+    ["i", "b", "u", "s", "font"]
+        .iter()
+        .any(|known| name.eq_ignore_ascii_case(known))
+}
+
 pub fn load_from_string(buffer: &str) -> anyhow::Result<Vec<Dialogue>> {
     use anyhow::Context;
+    // Extra blank lines between cues would otherwise leave empty blocks,
+    // or blocks starting with a newline when there's an odd number of them
     buffer
         .split_terminator("\n\n")
+        .map(|s| s.trim_start_matches('\n'))
+        .filter(|s| !s.trim().is_empty())
         .enumerate()
         .map(|(i, s)| {
             s.parse::<Dialogue>()
@@ -145,22 +311,63 @@ pub fn load(path: &Path) -> anyhow::Result<Vec<Dialogue>> {
         .with_context(|| format!("Failed to extract dialogue from {}", path.display()))
 }
 
+/// Options that control how an .srt file is written.
+///
+/// New options may be added, so start from [`WriteOptions::default`] and
+/// set the fields that are needed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteOptions {
+    /// Start the output with a UTF-8 byte order mark.
+    pub bom: bool,
+    /// Separate the milliseconds of timestamps with `.` rather than `,`.
+    ///
+    /// Some players need it even though `,` is the standard.
+    pub dot_separator: bool,
+}
+
 pub fn save(path: &Path, dialogue: &[Dialogue]) -> anyhow::Result<()> {
-    let new_contents = save_to_string(dialogue);
+    save_with(path, dialogue, &WriteOptions::default())
+}
+
+/// Saves the dialogue into an .srt file with the given options.
+pub fn save_with(path: &Path, dialogue: &[Dialogue], options: &WriteOptions) -> anyhow::Result<()> {
+    let new_contents = save_to_string_with(dialogue, options);
     let mut new_fp = std::fs::File::create(path)
         .with_context(|| "could not create new subtitle file".to_string())?;
+    if options.bom {
+        new_fp.write_all(&crate::UTF8_BOM)?;
+    }
     new_fp.write_all(new_contents.as_bytes())?;
     Ok(())
 }
 
+/// Serialises the dialogue into an .srt file.
+///
+/// Cues are separated by a single blank line and the output ends with
+/// exactly one newline.
 pub fn save_to_string(dialogue: &[Dialogue]) -> String {
+    save_to_string_with(dialogue, &WriteOptions::default())
+}
+
+/// Serialises the dialogue into an .srt file with the given options.
+///
+/// The BOM is left to the caller.
+pub fn save_to_string_with(dialogue: &[Dialogue], options: &WriteOptions) -> String {
+    let separator = if options.dot_separator { '.' } else { ',' };
     let mut new_contents = dialogue
         .iter()
-        .map(|f| f.to_string())
+        .map(|f| {
+            let mut cue = String::new();
+            let _ = f.write_cue(&mut cue, separator);
+            cue.trim_end_matches('\n').to_owned()
+        })
         .collect::<Vec<_>>()
         .join("\n\n");
 
-    new_contents.push_str("\n\n");
+    if !new_contents.is_empty() {
+        new_contents.push('\n');
+    }
     new_contents
 }
 
@@ -182,4 +389,193 @@ mod tests {
 
         assert_eq!(result.to_string(), fragment);
     }
+
+    #[test]
+    fn test_separator_spacing() {
+        for fragment in [
+            "1\n00:00:01,000-->00:00:02,500\nHello",
+            "1\n00:00:01,000  -->\t00:00:02,500\nHello",
+            "1\n00:00:01,000 -->00:00:02,500 \nHello",
+        ] {
+            let result = fragment.parse::<Dialogue>().expect("could not parse");
+            assert_eq!(result.start, Duration::from_secs(1));
+            assert_eq!(result.end, Duration::from_millis(2500));
+            assert_eq!(
+                result.to_string(),
+                "1\n00:00:01,000 --> 00:00:02,500\nHello"
+            );
+        }
+        assert_eq!(
+            "1\n00:00:01,000 -> 00:00:02,500\nHello".parse::<Dialogue>(),
+            Err(ParseDialogueError::Separator)
+        );
+    }
+
+    #[test]
+    fn test_parse_srt_time() {
+        assert_eq!(
+            parse_srt_time("01:02:03,456"),
+            Some(Duration::new(3723, 456_000_000))
+        );
+        assert_eq!(parse_srt_time("00:00:05.000"), Some(Duration::from_secs(5)));
+        // VTT allows the hours to be omitted
+        assert_eq!(parse_srt_time("00:05.000"), Some(Duration::from_secs(5)));
+        assert_eq!(
+            parse_srt_time("01:05.250"),
+            Some(Duration::new(65, 250_000_000))
+        );
+        assert_eq!(parse_srt_time("05.000"), None);
+        assert_eq!(parse_srt_time("00:00:00:05.000"), None);
+    }
+
+    #[test]
+    fn test_normalize_cue_text() {
+        let dialogue = "1\n00:00:01,000 --> 00:00:02,000\nHello \rWorld\r\r\n\tagain\t"
+            .parse::<Dialogue>()
+            .unwrap();
+        assert_eq!(dialogue.text, "Hello\nWorld\n\tagain");
+        assert!(!dialogue.to_string().contains('\r'));
+    }
+
+    #[test]
+    fn test_blank_line_in_text() {
+        let dialogue = [
+            Dialogue {
+                position: 1,
+                start: Duration::from_secs(1),
+                end: Duration::from_secs(2),
+                text: String::from("\nHello\n\nWorld\n"),
+            },
+            Dialogue {
+                position: 2,
+                start: Duration::from_secs(3),
+                end: Duration::from_secs(4),
+                text: String::from("Bye"),
+            },
+        ];
+        let saved = save_to_string(&dialogue);
+        let loaded = load_from_string(&saved).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].text, "Hello\nWorld");
+        assert_eq!(loaded[1].text, "Bye");
+        assert_eq!(save_to_string(&loaded), saved);
+    }
+
+    #[test]
+    fn test_extra_blank_lines() {
+        for newlines in 3..=5 {
+            let separator = "\n".repeat(newlines);
+            let dialogue = load_from_string(&format!(
+                "1\n00:00:01,000 --> 00:00:02,000\nHello{separator}2\n00:00:03,000 --> 00:00:04,000\nWorld{separator}",
+            ))
+            .unwrap();
+            assert_eq!(dialogue.len(), 2, "{newlines} newlines");
+            assert_eq!(dialogue[0].text, "Hello");
+            assert_eq!(dialogue[1].position, 2);
+            assert_eq!(dialogue[1].start, Duration::from_secs(3));
+            assert_eq!(dialogue[1].text, "World");
+        }
+    }
+
+    #[test]
+    fn test_save_trailing_newline() {
+        let dialogue = load_from_string(
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:03,000 --> 00:00:04,000\nWorld\n\n",
+        )
+        .unwrap();
+        assert_eq!(
+            save_to_string(&dialogue),
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:03,000 --> 00:00:04,000\nWorld\n"
+        );
+        assert_eq!(save_to_string(&[]), "");
+    }
+
+    #[test]
+    fn test_save_dot_separator() {
+        let dialogue = load_from_string("1\n00:00:01,000 --> 01:00:02,500\nHello\n").unwrap();
+        let options = WriteOptions {
+            dot_separator: true,
+            ..Default::default()
+        };
+        let saved = save_to_string_with(&dialogue, &options);
+        assert_eq!(saved, "1\n00:00:01.000 --> 01:00:02.500\nHello\n");
+        assert_eq!(load_from_string(&saved).unwrap(), dialogue);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = load_from_string(
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:03,000 --> 00:00:04,000\nWorld\n\n3\n00:00:05,000 --> 00:00:06,000\nBye\n\n",
+        )
+        .unwrap();
+        let new = load_from_string(
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:03,200 --> 00:00:04,000\nWorld!\n\n3\n00:00:08,000 --> 00:00:09,000\nNew\n\n",
+        )
+        .unwrap();
+        let changes = diff(&old, &new, Duration::from_millis(500));
+        assert_eq!(
+            changes,
+            [
+                DialogueChange::Changed(&old[1], &new[1]),
+                DialogueChange::Removed(&old[2]),
+                DialogueChange::Added(&new[2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_blank() {
+        let blank = |text: &str| {
+            Dialogue {
+                position: 1,
+                start: Duration::ZERO,
+                end: Duration::ZERO,
+                text: text.to_owned(),
+            }
+            .is_blank()
+        };
+        assert!(blank(""));
+        assert!(blank(" \n\t"));
+        assert!(blank("<i> </i>"));
+        assert!(blank("{\\an8}<font color=\"red\"></font>"));
+        assert!(!blank("<i>Hi</i>"));
+        assert!(!blank("<3"));
+        assert!(!blank("<laughs>"));
+        assert!(!blank("{sighs}"));
+        assert!(!blank("<i>[music]</i>"));
+    }
+
+    #[test]
+    fn test_duration() {
+        let mut dialogue = "1\n00:00:01,000 --> 00:00:03,500\nHello"
+            .parse::<Dialogue>()
+            .unwrap();
+        assert_eq!(dialogue.duration(), Duration::from_millis(2500));
+        dialogue.end = Duration::ZERO;
+        assert_eq!(dialogue.duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut dialogue = "1\n00:00:01,000 --> 00:00:03,500\nHello"
+            .parse::<Dialogue>()
+            .unwrap();
+        let delta = "0.1".parse::<SignedDuration>().unwrap();
+        assert_eq!(delta, SignedDuration::forward(Duration::from_millis(100)));
+        for _ in 0..10 {
+            dialogue.shift(delta);
+        }
+        assert_eq!(dialogue.start, Duration::from_secs(2));
+        assert_eq!(dialogue.end, Duration::from_millis(4500));
+
+        let delta = "-2.25".parse::<SignedDuration>().unwrap();
+        assert_eq!(delta.to_string(), "-2.25");
+        dialogue.shift(delta);
+        assert_eq!(dialogue.start, Duration::ZERO);
+        assert_eq!(dialogue.end, Duration::from_millis(2250));
+
+        for invalid in ["", "-", ".", "1e3", "1.2.3", "0.0000000001", "inf"] {
+            assert!(invalid.parse::<SignedDuration>().is_err(), "{invalid}");
+        }
+    }
 }
@@ -0,0 +1,62 @@
+//! Helpers for checking that subtitle files survive a parse and save unchanged.
+//!
+//! These are meant to be used with golden files, i.e. fixtures that are
+//! already in the canonical form that the writers produce.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{ass::Ass, srt, vtt, SubtitleFormat};
+
+/// Parses the subtitle and saves it again in the same format.
+pub fn round_trip(contents: &str) -> anyhow::Result<String> {
+    let format = SubtitleFormat::detect(contents).context("unrecognised subtitle format")?;
+    match format {
+        SubtitleFormat::Ass => {
+            let ass = contents.parse::<Ass>()?;
+            let mut output = Vec::new();
+            ass.save_to_writer(&mut output)?;
+            Ok(String::from_utf8(output)?)
+        }
+        SubtitleFormat::Srt => Ok(srt::save_to_string(&srt::load_from_string(contents)?)),
+        SubtitleFormat::Vtt => Ok(vtt::save_to_string(&vtt::load_from_string(contents)?)),
+    }
+}
+
+/// Asserts that the file at the given path round trips byte for byte.
+///
+/// # Panics
+///
+/// Panics if the file can't be read or parsed, or if the output differs
+/// from the file. The first differing line is included in the message.
+#[track_caller]
+pub fn assert_round_trip(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("could not read {}: {e}", path.display()));
+    let output = round_trip(&contents)
+        .unwrap_or_else(|e| panic!("could not round trip {}: {e:?}", path.display()));
+    if output == contents {
+        return;
+    }
+
+    let mismatch = contents
+        .split_inclusive('\n')
+        .zip(output.split_inclusive('\n'))
+        .enumerate()
+        .find(|(_, (expected, actual))| expected != actual);
+    match mismatch {
+        Some((index, (expected, actual))) => panic!(
+            "{} did not round trip, line {} differs\n  expected: {expected:?}\n    actual: {actual:?}",
+            path.display(),
+            index + 1
+        ),
+        None => panic!(
+            "{} did not round trip, expected {} bytes but got {}",
+            path.display(),
+            contents.len(),
+            output.len()
+        ),
+    }
+}
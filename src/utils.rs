@@ -1,3 +1,5 @@
+use std::{error::Error, fmt::Display, str::FromStr, time::Duration};
+
 pub(crate) trait LendingIterator {
     type Item<'this>
     where
@@ -29,3 +31,287 @@ pub(crate) fn windows_mut<T, const SIZE: usize>(slice: &mut [T]) -> WindowsMut<'
     assert_ne!(SIZE, 0);
     WindowsMut { slice, start: 0 }
 }
+
+/// Multiplies the duration by the factor, saturating instead of panicking.
+///
+/// Results that are negative or NaN become [`Duration::ZERO`] and results
+/// that are too large to fit become [`Duration::MAX`].
+pub fn scale_duration(duration: Duration, factor: f64) -> Duration {
+    if factor == 1.0 {
+        return duration;
+    }
+    let seconds = duration.as_secs_f64() * factor;
+    if seconds.is_nan() || seconds <= 0.0 {
+        Duration::ZERO
+    } else {
+        Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Converts seconds into a [`Duration`] using [`scale_duration`].
+pub(crate) fn duration_from_secs(seconds: f64) -> Duration {
+    scale_duration(Duration::from_secs(1), seconds)
+}
+
+/// Common video frame rates along with how they're usually written.
+pub const COMMON_FRAME_RATES: [(&str, f64); 8] = [
+    ("23.976", 24000.0 / 1001.0),
+    ("24", 24.0),
+    ("25", 25.0),
+    ("29.97", 30000.0 / 1001.0),
+    ("30", 30.0),
+    ("50", 50.0),
+    ("59.94", 60000.0 / 1001.0),
+    ("60", 60.0),
+];
+
+/// Returns the fraction of timings that line up with the frames for each of
+/// the [`COMMON_FRAME_RATES`], from the best match to the worst.
+///
+/// Timings that were made against a video are all at the same point within
+/// a frame, e.g. right at the start of one or halfway through depending on
+/// the tool, so the frame rate with the highest fraction is most likely the
+/// video's. Since a timing can only be as close to a frame as its format
+/// allows, timings that are all multiples of 10ms (e.g. from .ass files) are
+/// given more leeway. Timings at zero are skipped since they line up with
+/// every frame rate.
+///
+/// Frame rates that are a multiple of another one, e.g. 25 and 50, line up
+/// equally well with the lower one. Ties are ordered from the lowest frame rate.
+pub fn frame_alignment(timings: &[Duration]) -> Vec<(&'static str, f64)> {
+    let timings = timings
+        .iter()
+        .filter(|t| !t.is_zero())
+        .map(Duration::as_secs_f64)
+        .collect::<Vec<_>>();
+    let centiseconds = timings
+        .iter()
+        .all(|t| ((t * 1000.0).round() as u64).is_multiple_of(10));
+    let window = if centiseconds { 0.010 } else { 0.002 };
+
+    let mut result = COMMON_FRAME_RATES
+        .iter()
+        .map(|&(name, fps)| {
+            // How far into a frame each timing is, from 0 to 1
+            let mut phases = timings
+                .iter()
+                .map(|t| (t * fps).fract())
+                .collect::<Vec<_>>();
+            phases.sort_by(f64::total_cmp);
+            // Wrap around so a window can span the end and start of a frame
+            let wrapped = phases.iter().map(|p| p + 1.0);
+            let phases = phases.iter().copied().chain(wrapped).collect::<Vec<_>>();
+
+            let width = window * fps;
+            let mut best = 0;
+            let mut end = 0;
+            for start in 0..phases.len() / 2 {
+                while end < phases.len() && phases[end] - phases[start] <= width {
+                    end += 1;
+                }
+                best = best.max(end - start);
+            }
+            (name, best as f64 / timings.len().max(1) as f64)
+        })
+        .collect::<Vec<_>>();
+    result.sort_by(|a, b| b.1.total_cmp(&a.1));
+    result
+}
+
+/// A [`Duration`] with a direction, used to move timings forward or backward.
+///
+/// Unlike `f32` seconds this keeps the full precision of a [`Duration`], so
+/// repeatedly shifting by the same amount doesn't accumulate rounding errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SignedDuration {
+    pub duration: Duration,
+    /// Whether the timings are moved backward.
+    pub negative: bool,
+}
+
+impl SignedDuration {
+    pub const ZERO: Self = Self::forward(Duration::ZERO);
+
+    pub const fn forward(duration: Duration) -> Self {
+        Self {
+            duration,
+            negative: false,
+        }
+    }
+
+    pub const fn backward(duration: Duration) -> Self {
+        Self {
+            duration,
+            negative: true,
+        }
+    }
+
+    /// Creates the delta from a number of seconds, negative being backward.
+    ///
+    /// Seconds that don't fit in a [`Duration`] saturate and NaN is zero.
+    pub fn from_secs_f32(seconds: f32) -> Self {
+        Self {
+            duration: duration_from_secs(seconds.abs().into()),
+            negative: seconds < 0.0,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.duration.is_zero()
+    }
+
+    /// Moves the given time by this delta, saturating at zero and [`Duration::MAX`].
+    pub fn apply(&self, time: Duration) -> Duration {
+        if self.negative {
+            time.saturating_sub(self.duration)
+        } else {
+            time.saturating_add(self.duration)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseSignedDurationError;
+
+impl Display for ParseSignedDurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid duration, expected seconds such as `1.5` or `-0.25`")
+    }
+}
+
+impl Error for ParseSignedDurationError {}
+
+impl FromStr for SignedDuration {
+    type Err = ParseSignedDurationError;
+
+    /// Parses decimal seconds with an optional sign, e.g. `-1.25`.
+    ///
+    /// The fraction is read exactly, up to nanosecond precision.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty())
+            || !is_digits(whole)
+            || !is_digits(fraction)
+            || fraction.len() > 9
+        {
+            return Err(ParseSignedDurationError);
+        }
+
+        let secs = match whole {
+            "" => 0,
+            whole => whole.parse::<u64>().map_err(|_| ParseSignedDurationError)?,
+        };
+        let nanos = fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(9)
+            .fold(0, |acc, b| acc * 10 + u32::from(b - b'0'));
+        Ok(Self {
+            duration: Duration::new(secs, nanos),
+            negative,
+        })
+    }
+}
+
+impl Display for SignedDuration {
+    /// Writes the delta as signed seconds, e.g. `+2.5` or `-0.04`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.negative { '-' } else { '+' };
+        write!(f, "{sign}{}", self.duration.as_secs())?;
+        let nanos = self.duration.subsec_nanos();
+        if nanos != 0 {
+            let fraction = format!("{nanos:09}");
+            write!(f, ".{}", fraction.trim_end_matches('0'))?;
+        }
+        Ok(())
+    }
+}
+
+/// Serializes a [`Duration`] as a whole number of milliseconds.
+///
+/// Used with `#[serde(with = "crate::utils::serde_millis")]`.
+#[cfg(feature = "serde")]
+pub(crate) mod serde_millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_alignment() {
+        let frames = |fps: f64| {
+            (1..200)
+                .map(|frame| {
+                    Duration::from_millis((frame as f64 * 97.0 / fps * 1000.0).round() as u64)
+                })
+                .collect::<Vec<_>>()
+        };
+        let score =
+            |result: &[(&str, f64)], name: &str| result.iter().find(|(n, _)| *n == name).unwrap().1;
+
+        // Every frame at 25 fps is also a frame at 50 fps
+        let result = frame_alignment(&frames(25.0));
+        assert_eq!(result[0], ("25", 1.0));
+        assert_eq!(result[1], ("50", 1.0));
+        assert!(score(&result, "24") < 0.5);
+        let result = frame_alignment(&frames(24000.0 / 1001.0));
+        assert_eq!(result[0], ("23.976", 1.0));
+        assert!(score(&result, "24") < 0.5);
+        assert!(frame_alignment(&[Duration::ZERO])
+            .iter()
+            .all(|(_, f)| *f == 0.0));
+    }
+
+    #[test]
+    fn test_scale_duration() {
+        let d = Duration::from_secs(10);
+        assert_eq!(scale_duration(d, 1.0), d);
+        assert_eq!(scale_duration(d, 0.5), Duration::from_secs(5));
+        assert_eq!(scale_duration(d, 0.0), Duration::ZERO);
+        assert_eq!(scale_duration(d, -2.0), Duration::ZERO);
+        assert_eq!(scale_duration(d, f64::NAN), Duration::ZERO);
+        assert_eq!(scale_duration(d, f64::NEG_INFINITY), Duration::ZERO);
+        assert_eq!(scale_duration(d, f64::INFINITY), Duration::MAX);
+        assert_eq!(scale_duration(d, f64::MAX), Duration::MAX);
+        assert_eq!(scale_duration(Duration::MAX, 2.0), Duration::MAX);
+        assert_eq!(
+            scale_duration(Duration::ZERO, f64::INFINITY),
+            Duration::ZERO
+        );
+        assert_eq!(scale_duration(d, f64::MIN_POSITIVE), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_signed_duration_from_secs() {
+        assert_eq!(
+            SignedDuration::from_secs_f32(-1.5),
+            SignedDuration::backward(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            SignedDuration::from_secs_f32(f32::INFINITY),
+            SignedDuration::forward(Duration::MAX)
+        );
+        assert_eq!(
+            SignedDuration::from_secs_f32(f32::NEG_INFINITY),
+            SignedDuration::backward(Duration::MAX)
+        );
+        assert!(SignedDuration::from_secs_f32(f32::NAN).is_zero());
+    }
+}
@@ -1,8 +1,13 @@
 use regex::Regex;
 
-use crate::srt::{parse_srt_time, Dialogue};
-use std::{path::Path, sync::OnceLock};
+use crate::srt::{normalize_cue_text, parse_srt_time, Dialogue};
+use std::{path::Path, sync::OnceLock, time::Duration};
 
+/// Matches the timing line of a cue.
+///
+/// WebVTT requires a `.` before the milliseconds but files that were
+/// converted from .srt sometimes keep the `,` so both are accepted.
+/// The output always uses a `.`.
 fn cue_regex() -> &'static Regex {
     static REGEX: OnceLock<Regex> = OnceLock::new();
     REGEX.get_or_init(|| {
@@ -22,10 +27,24 @@ fn text_cleanup_regex() -> &'static Regex {
     REGEX.get_or_init(|| Regex::new(r#"(</?c\.[a-zA-Z_\s]+>|&lrm;|&rlm;)"#).unwrap())
 }
 
-fn parse_dialogue(segment: &str) -> Option<Dialogue> {
-    let mut lines = segment.splitn(3, '\n');
-    let position: u32 = lines.next().and_then(|s| s.parse().ok())?;
-    let cue = cue_regex().captures(lines.next()?)?;
+/// Parses a block of lines into dialogue if it has a cue timing line.
+///
+/// The line before the timing is the optional cue identifier, which is used
+/// as the position if it's a number. Otherwise the given position is used.
+fn parse_dialogue(segment: &str, position: u32) -> Option<Dialogue> {
+    let mut identifier = None;
+    let mut rest = segment;
+    let (cue, text) = loop {
+        let (line, remaining) = rest.split_once('\n')?;
+        if let Some(cue) = cue_regex().captures(line) {
+            break (cue, remaining);
+        }
+        identifier = Some(line);
+        rest = remaining;
+    };
+    let position = identifier
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(position);
     let start = parse_srt_time(&cue["start"])?;
     let end = parse_srt_time(&cue["end"])?;
     let top = cue
@@ -34,9 +53,7 @@ fn parse_dialogue(segment: &str) -> Option<Dialogue> {
         .map(|f| f < 50.0)
         .unwrap_or_default();
 
-    let mut text = text_cleanup_regex()
-        .replace_all(lines.next()?, "")
-        .into_owned();
+    let mut text = normalize_cue_text(&text_cleanup_regex().replace_all(text, ""));
 
     if top {
         text.insert_str(0, "{\\an8}")
@@ -55,13 +72,226 @@ pub fn load(path: &Path) -> std::io::Result<Vec<Dialogue>> {
     load_from_string(&buffer)
 }
 
+/// Returns `true` if the file is a chapter or metadata track rather than captions.
+///
+/// These are marked as such in the header, e.g. `WEBVTT - Chapters` or
+/// `Kind: metadata`, and have cues. Files without any cues are left to the
+/// "no dialogue found" error instead.
+fn is_non_caption_track(buffer: &str) -> bool {
+    let header = buffer.split("\n\n").next().unwrap_or_default();
+    let marked = header.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("webvtt") && (line.contains("chapters") || line.contains("metadata"))
+            || line
+                .strip_prefix("kind:")
+                .is_some_and(|kind| matches!(kind.trim(), "chapters" | "metadata"))
+    });
+    marked && buffer.contains("-->")
+}
+
 pub fn load_from_string(buffer: &str) -> std::io::Result<Vec<Dialogue>> {
-    let Some(index) = buffer.find("\n1\n") else {
-        return Err(std::io::Error::other("no dialogue found"));
-    };
+    if is_non_caption_track(buffer) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "this .vtt file appears to be a metadata or chapter track, not captions",
+        ));
+    }
+
+    // Cues are found by their timing line since the identifiers are optional
+    // and a lone number could just as well be the text of a cue
+    let mut dialogue = Vec::new();
+    for segment in buffer.split_terminator("\n\n") {
+        if let Some(d) = parse_dialogue(segment, dialogue.len() as u32 + 1) {
+            dialogue.push(d);
+        }
+    }
+
+    if dialogue.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "no dialogue found",
+        ));
+    }
+    Ok(dialogue)
+}
 
-    Ok(buffer[index - 1..]
-        .split_terminator("\n\n")
-        .flat_map(parse_dialogue)
-        .collect::<Vec<_>>())
+fn duration_to_vtt(d: &Duration) -> String {
+    let seconds = d.as_secs();
+    let (hours, seconds) = (seconds / 3600, seconds % 3600);
+    let (minutes, seconds) = (seconds / 60, seconds % 60);
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        hours,
+        minutes,
+        seconds,
+        d.subsec_millis()
+    )
+}
+
+/// Serialises the dialogue into a WebVTT file.
+///
+/// Dialogue that starts with `{\an8}` is placed at the top of the screen.
+/// Cues are numbered from 1, separated by a single blank line, and the
+/// output ends with exactly one newline.
+pub fn save_to_string(dialogue: &[Dialogue]) -> String {
+    let mut output = String::from("WEBVTT\n");
+    for (index, d) in dialogue.iter().enumerate() {
+        let (text, settings) = match d.text.strip_prefix("{\\an8}") {
+            Some(text) => (text, " line:10%"),
+            None => (d.text.as_str(), ""),
+        };
+        output.push_str(&format!(
+            "\n{}\n{} --> {}{}\n{}\n",
+            index + 1,
+            duration_to_vtt(&d.start),
+            duration_to_vtt(&d.end),
+            settings,
+            normalize_cue_text(text)
+        ));
+    }
+    output
+}
+
+/// Options that control how a .vtt file is written.
+///
+/// New options may be added, so start from [`WriteOptions::default`] and
+/// set the fields that are needed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteOptions {
+    /// Start the output with a UTF-8 byte order mark.
+    pub bom: bool,
+}
+
+pub fn save(path: &Path, dialogue: &[Dialogue]) -> std::io::Result<()> {
+    save_with(path, dialogue, &WriteOptions::default())
+}
+
+/// Saves the dialogue into a .vtt file with the given options.
+pub fn save_with(
+    path: &Path,
+    dialogue: &[Dialogue],
+    options: &WriteOptions,
+) -> std::io::Result<()> {
+    let mut contents = Vec::new();
+    if options.bom {
+        contents.extend_from_slice(&crate::UTF8_BOM);
+    }
+    contents.extend_from_slice(save_to_string(dialogue).as_bytes());
+    std::fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamps_without_hours() {
+        let buffer = "WEBVTT\n\n1\n00:05.000 --> 01:02.500\nHello\n\n2\n00:01:03.000 --> 00:01:04.000\nWorld\n";
+        let dialogue = load_from_string(buffer).unwrap();
+        assert_eq!(dialogue.len(), 2);
+        assert_eq!(dialogue[0].start, Duration::from_secs(5));
+        assert_eq!(dialogue[0].end, Duration::from_millis(62_500));
+        assert_eq!(dialogue[1].start, Duration::from_secs(63));
+    }
+
+    #[test]
+    fn test_cue_text_is_a_number() {
+        let buffer = "WEBVTT\n\nNOTE\n1\n\n1\n00:00:01.000 --> 00:00:02.000\n3\n\n2\n00:00:02.000 --> 00:00:03.000\n2\n\n3\n00:00:03.000 --> 00:00:04.000\n1\n\n4\n00:00:04.000 --> 00:00:05.000\nGo!\n";
+        let dialogue = load_from_string(buffer).unwrap();
+        assert_eq!(dialogue.len(), 4);
+        assert_eq!(dialogue[2].position, 3);
+        assert_eq!(dialogue[2].text, "1");
+        assert_eq!(dialogue[3].text, "Go!");
+
+        let buffer = "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\n2\n\nintro\n00:00:02.000 --> 00:00:03.000\n1\n\n00:00:03.000 --> 00:00:04.000\nGo!\n";
+        let dialogue = load_from_string(buffer).unwrap();
+        assert_eq!(
+            dialogue
+                .iter()
+                .map(|d| (d.position, d.text.as_str()))
+                .collect::<Vec<_>>(),
+            [(1, "2"), (2, "1"), (3, "Go!")]
+        );
+    }
+
+    #[test]
+    fn test_first_cue_identifier() {
+        let buffer = "WEBVTT\n\n5\n00:00:01.000 --> 00:00:02.000\nHello\n\n6\n00:00:02.000 --> 00:00:03.000\nWorld\n";
+        let dialogue = load_from_string(buffer).unwrap();
+        assert_eq!(dialogue.len(), 2);
+        assert_eq!(dialogue[0].position, 5);
+        assert_eq!(dialogue[0].text, "Hello");
+
+        let buffer = "WEBVTT\n\nSTYLE\n::cue { color: yellow }\n\nopening\n00:00:01.000 --> 00:00:02.000\nHello\n\n00:00:02.000 --> 00:00:03.000\nWorld\n";
+        let dialogue = load_from_string(buffer).unwrap();
+        assert_eq!(dialogue.len(), 2);
+        assert_eq!(dialogue[0].position, 1);
+        assert_eq!(dialogue[0].text, "Hello");
+        assert_eq!(dialogue[1].position, 2);
+    }
+
+    #[test]
+    fn test_save_blank_line_in_text() {
+        let dialogue = [Dialogue {
+            position: 1,
+            start: Duration::from_secs(1),
+            end: Duration::from_secs(2),
+            text: String::from("Hello\n\nWorld"),
+        }];
+        let saved = save_to_string(&dialogue);
+        assert_eq!(
+            saved,
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.000\nHello\nWorld\n"
+        );
+        assert_eq!(load_from_string(&saved).unwrap()[0].text, "Hello\nWorld");
+    }
+
+    #[test]
+    fn test_non_caption_tracks() {
+        for buffer in [
+            "WEBVTT - Chapters\n\n1\n00:00:00.000 --> 00:05:00.000\nOpening\n",
+            "WEBVTT\nKind: metadata\n\n1\n00:00:00.000 --> 00:00:01.000\n{\"id\": 1}\n",
+        ] {
+            let error = load_from_string(buffer).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+            assert!(error.to_string().contains("not captions"), "{buffer}");
+        }
+        for buffer in [
+            "",
+            "WEBVTT\n",
+            "WEBVTT\n\nNOTE nothing here\n",
+            "WEBVTT - Chapters\n",
+        ] {
+            let error = load_from_string(buffer).unwrap_err();
+            assert_eq!(error.to_string(), "no dialogue found", "{buffer}");
+        }
+        assert!(load_from_string(
+            "WEBVTT\nKind: captions\n\n1\n00:00:01.000 --> 00:00:02.000\nHi\n"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_comma_timestamps() {
+        let buffer = "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02,500\nHello\n\n2\n00:00:03,000 --> 00:00:04,000\nWorld\n";
+        let dialogue = load_from_string(buffer).unwrap();
+        assert_eq!(dialogue.len(), 2);
+        assert_eq!(dialogue[0].start, Duration::from_secs(1));
+        assert_eq!(dialogue[0].end, Duration::from_millis(2500));
+        assert_eq!(dialogue[1].start, Duration::from_secs(3));
+        assert_eq!(
+            save_to_string(&dialogue),
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.500\nHello\n\n2\n00:00:03.000 --> 00:00:04.000\nWorld\n"
+        );
+    }
+
+    #[test]
+    fn test_save() {
+        let buffer = "WEBVTT\n\n1\n00:00:05.000 --> 00:01:02.500 line:10%\nHello\n\n2\n01:00:03.000 --> 01:00:04.000\nWorld\n";
+        let dialogue = load_from_string(buffer).unwrap();
+        assert_eq!(dialogue[0].text, "{\\an8}Hello");
+        assert_eq!(save_to_string(&dialogue), buffer);
+        assert_eq!(save_to_string(&[]), "WEBVTT\n");
+    }
 }
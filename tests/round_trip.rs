@@ -0,0 +1,22 @@
+use sub_tools::testing::assert_round_trip;
+
+macro_rules! fixture {
+    ($name:literal) => {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/", $name)
+    };
+}
+
+#[test]
+fn test_ass_round_trip() {
+    assert_round_trip(fixture!("basic.ass"));
+}
+
+#[test]
+fn test_srt_round_trip() {
+    assert_round_trip(fixture!("basic.srt"));
+}
+
+#[test]
+fn test_vtt_round_trip() {
+    assert_round_trip(fixture!("basic.vtt"));
+}